@@ -0,0 +1,219 @@
+use std::{collections::HashMap, io::Result, sync::Mutex};
+
+use crate::disk::{
+    block_device::BlockDevice,
+    types::{Block, BLOCK_SIZE},
+};
+
+/// 带 LRU 淘汰的块缓存，包装任意 BlockDevice。
+/// 读命中直接返回缓存；写入只标记脏块，`flush` 或被淘汰时才写回底层设备。
+#[derive(Debug)]
+pub struct BlockCache<D: BlockDevice> {
+    device: D,
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<u64, CacheEntry>,
+    tick: u64, // 逻辑时钟，用于找出最久未使用的块
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    data: Box<Block>,
+    dirty: bool,
+    last_used: u64,
+}
+
+impl<D: BlockDevice> BlockCache<D> {
+    pub fn new(device: D, capacity: usize) -> Self {
+        Self {
+            device,
+            capacity: capacity.max(1),
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// 当前缓存的块数
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 把所有脏块写回底层设备
+    pub fn flush(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        for (&block_id, entry) in state.entries.iter_mut().filter(|(_, e)| e.dirty) {
+            self.device.write_block(block_id, &entry.data)?;
+            entry.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// 直写：先写到底层设备，成功后再以干净块的形式放入缓存；失败时缓存保持不变
+    pub fn write_through(&self, block_id: u64, buf: &Block) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.device.write_block(block_id, buf)?;
+        self.insert(&mut state, block_id, Box::new(*buf), false)
+    }
+
+    // 放入一个块，必要时先淘汰最久未使用的块（脏块先写回）
+    fn insert(
+        &self,
+        state: &mut CacheState,
+        block_id: u64,
+        data: Box<Block>,
+        dirty: bool,
+    ) -> Result<()> {
+        if !state.entries.contains_key(&block_id) && state.entries.len() >= self.capacity {
+            let victim = state
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(&id, _)| id);
+            if let Some(victim) = victim {
+                let entry = &state.entries[&victim];
+                if entry.dirty {
+                    self.device.write_block(victim, &entry.data)?;
+                }
+                state.entries.remove(&victim);
+            }
+        }
+
+        state.tick += 1;
+        let last_used = state.tick;
+        state.entries.insert(
+            block_id,
+            CacheEntry {
+                data,
+                dirty,
+                last_used,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> BlockDevice for BlockCache<D> {
+    fn read_block(&self, block_id: u64, buf: &mut Block) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        if let Some(entry) = state.entries.get_mut(&block_id) {
+            entry.last_used = tick;
+            buf.copy_from_slice(&entry.data[..]);
+            return Ok(());
+        }
+
+        let mut data = Box::new([0u8; BLOCK_SIZE]);
+        self.device.read_block(block_id, &mut data)?;
+        buf.copy_from_slice(&data[..]);
+        self.insert(&mut state, block_id, data, false)
+    }
+
+    fn write_block(&self, block_id: u64, buf: &Block) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.insert(&mut state, block_id, Box::new(*buf), true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    // 统计读写次数的内存设备
+    #[derive(Debug, Default)]
+    struct CountingDevice {
+        blocks: Mutex<HashMap<u64, Block>>,
+        reads: AtomicUsize,
+        writes: AtomicUsize,
+        fail_writes: AtomicBool,
+    }
+
+    impl BlockDevice for CountingDevice {
+        fn read_block(&self, block_id: u64, buf: &mut Block) -> Result<()> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            *buf = self
+                .blocks
+                .lock()
+                .unwrap()
+                .get(&block_id)
+                .copied()
+                .unwrap_or([0u8; BLOCK_SIZE]);
+            Ok(())
+        }
+
+        fn write_block(&self, block_id: u64, buf: &Block) -> Result<()> {
+            if self.fail_writes.load(Ordering::SeqCst) {
+                return Err(std::io::Error::other("injected write failure"));
+            }
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            self.blocks.lock().unwrap().insert(block_id, *buf);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_second_read_hits_cache() {
+        let cache = BlockCache::new(CountingDevice::default(), 4);
+        let mut buf = [0u8; BLOCK_SIZE];
+
+        cache.read_block(7, &mut buf).unwrap();
+        cache.read_block(7, &mut buf).unwrap();
+        assert_eq!(cache.device().reads.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_write_back_and_lru_eviction() {
+        let cache = BlockCache::new(CountingDevice::default(), 2);
+        let mut buf = [0u8; BLOCK_SIZE];
+
+        cache.write_block(1, &[1u8; BLOCK_SIZE]).unwrap();
+        cache.write_block(2, &[2u8; BLOCK_SIZE]).unwrap();
+        assert_eq!(cache.device().writes.load(Ordering::SeqCst), 0);
+
+        // 访问 1 之后，2 成为最久未使用的块，会被淘汰并写回
+        cache.read_block(1, &mut buf).unwrap();
+        cache.read_block(3, &mut buf).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.device().writes.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.device().blocks.lock().unwrap()[&2], [2u8; BLOCK_SIZE]);
+
+        cache.flush().unwrap();
+        assert_eq!(cache.device().blocks.lock().unwrap()[&1], [1u8; BLOCK_SIZE]);
+
+        // 淘汰后的块重新从设备读出
+        cache.read_block(2, &mut buf).unwrap();
+        assert_eq!(buf, [2u8; BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn test_write_through() {
+        let cache = BlockCache::new(CountingDevice::default(), 4);
+        let mut buf = [0u8; BLOCK_SIZE];
+
+        cache.write_through(5, &[5u8; BLOCK_SIZE]).unwrap();
+        assert_eq!(cache.device().writes.load(Ordering::SeqCst), 1);
+        // 直写后的块是干净的，flush 不会重复写
+        cache.flush().unwrap();
+        assert_eq!(cache.device().writes.load(Ordering::SeqCst), 1);
+        cache.read_block(5, &mut buf).unwrap();
+        assert_eq!(cache.device().reads.load(Ordering::SeqCst), 0);
+
+        // 设备写失败时缓存里仍是旧数据
+        cache.device().fail_writes.store(true, Ordering::SeqCst);
+        assert!(cache.write_through(5, &[6u8; BLOCK_SIZE]).is_err());
+        cache.read_block(5, &mut buf).unwrap();
+        assert_eq!(buf, [5u8; BLOCK_SIZE]);
+    }
+}
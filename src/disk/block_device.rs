@@ -1,8 +1,28 @@
-use std::io::Result;
+use std::{io::Result, sync::Arc};
 
 use crate::disk::types::Block;
 
-pub trait BlockDevice: Send + Sync {
+pub trait BlockDevice: Send + Sync + std::fmt::Debug {
     fn read_block(&self, block_id: u64, buf: &mut Block) -> Result<()>;
     fn write_block(&self, block_id: u64, buf: &Block) -> Result<()>;
+
+    /// 设备当前能完整读出的块数；无法得知容量的设备视为不受限制
+    fn block_count(&self) -> Result<u64> {
+        Ok(u64::MAX)
+    }
+}
+
+// 共享的设备同样可以当作块设备使用（例如 FileSystem 与 DataArea 共用一块磁盘）
+impl<D: BlockDevice + ?Sized> BlockDevice for Arc<D> {
+    fn read_block(&self, block_id: u64, buf: &mut Block) -> Result<()> {
+        (**self).read_block(block_id, buf)
+    }
+
+    fn write_block(&self, block_id: u64, buf: &Block) -> Result<()> {
+        (**self).write_block(block_id, buf)
+    }
+
+    fn block_count(&self) -> Result<u64> {
+        (**self).block_count()
+    }
 }
@@ -1,6 +1,6 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Result, Seek, SeekFrom, Write},
+    fs::{File, OpenOptions, TryLockError},
+    io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write},
     sync::{mpsc::Sender, Mutex},
     thread,
     time::Duration,
@@ -11,11 +11,50 @@ use crate::{
         block_device::BlockDevice,
         types::{Block, BLOCK_SIZE, DISK_SIZE},
     },
+    fs::error::FileSystemError,
     shell::BootProgress,
 };
+/// 瞬时 I/O 错误（Interrupted / WouldBlock）的重试策略
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,    // 最多尝试次数（含第一次）
+    pub base_delay: Duration, // 首次重试前的等待时间，之后每次翻倍
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(5),
+        }
+    }
+}
+
+/// 按策略执行 I/O 操作，仅对瞬时错误退避重试，其他错误立即返回
+pub fn with_retry<T>(policy: &RetryPolicy, mut op: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut delay = policy.base_delay;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e)
+                if attempt < policy.max_attempts
+                    && matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock) =>
+            {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 镜像文件。打开期间持有文件上的排他建议锁，防止两个进程同时写同一个镜像；
+/// 锁随 File 关闭（FileDisk drop）自动释放
 #[derive(Debug)]
 pub struct FileDisk {
     file: Mutex<File>,
+    retry: RetryPolicy,
 }
 
 impl FileDisk {
@@ -24,7 +63,15 @@ impl FileDisk {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(path)?;
+        file.try_lock().map_err(|e| match e {
+            TryLockError::WouldBlock => Error::new(
+                ErrorKind::ResourceBusy,
+                format!("Disk image already in use by another process: {}", path),
+            ),
+            TryLockError::Error(e) => e,
+        })?;
 
         if file.metadata()?.len() < DISK_SIZE {
             tx.send(BootProgress::Step("🪶 Allocating disk space..."))
@@ -42,22 +89,67 @@ impl FileDisk {
 
         Ok(Self {
             file: Mutex::new(file),
+            retry: RetryPolicy::default(),
         })
     }
+
+    /// 以只读方式打开已存在的镜像，只持有共享锁，也不会像 new 那样把短文件扩到 DISK_SIZE；
+    /// 用于 diff 等只读比较，不改动用户的文件
+    pub fn open_readonly(path: &str) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        file.try_lock_shared().map_err(|e| match e {
+            TryLockError::WouldBlock => Error::new(
+                ErrorKind::ResourceBusy,
+                format!("Disk image already in use by another process: {}", path),
+            ),
+            TryLockError::Error(e) => e,
+        })?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
 }
 
 impl BlockDevice for FileDisk {
     fn read_block(&self, block_id: u64, buf: &mut Block) -> std::io::Result<()> {
         let mut file = self.file.lock().unwrap();
-        file.seek(SeekFrom::Start(block_id * BLOCK_SIZE as u64))?;
-        file.read_exact(buf)?;
+        with_retry(&self.retry, || {
+            file.seek(SeekFrom::Start(block_id * BLOCK_SIZE as u64))?;
+            file.read_exact(buf)
+        })
+        .map_err(|e| {
+            if e.kind() == ErrorKind::UnexpectedEof {
+                // 镜像比预期短：换成带块号的损坏错误，方便定位
+                Error::new(
+                    ErrorKind::UnexpectedEof,
+                    FileSystemError::Corrupted(format!(
+                        "block {} lies beyond the end of the disk image",
+                        block_id
+                    )),
+                )
+            } else {
+                e
+            }
+        })?;
         Ok(())
     }
 
     fn write_block(&self, block_id: u64, buf: &Block) -> std::io::Result<()> {
         let mut file = self.file.lock().unwrap();
-        file.seek(SeekFrom::Start(block_id * BLOCK_SIZE as u64))?;
-        file.write_all(buf)?;
-        Ok(())
+        with_retry(&self.retry, || {
+            file.seek(SeekFrom::Start(block_id * BLOCK_SIZE as u64))?;
+            file.write_all(buf)
+        })
+    }
+
+    fn block_count(&self) -> std::io::Result<u64> {
+        let len = self.file.lock().unwrap().metadata()?.len();
+        Ok(len / BLOCK_SIZE as u64)
     }
 }
@@ -1,17 +1,42 @@
-use crate::{disk::file_disk::FileDisk, fs::FileSystem, shell::BootProgress};
-use std::sync::mpsc::Sender;
+use crate::{fs::FileSystem, shell::BootProgress};
+use std::{ffi::OsString, sync::mpsc::Sender};
 
-pub fn perform_disk_initialization(tx: Sender<BootProgress>) {
-    const DISK_PATH: &str = "disk.img";
+// 默认的磁盘镜像，位于当前工作目录
+pub const DISK_PATH: &str = "disk.img";
 
+/// 选择要挂载的镜像：`--image <path>` / `--image=<path>` 优先，其次环境变量 MINIFS_IMAGE，
+/// 都没有时使用 DISK_PATH
+pub fn image_from_args<I: IntoIterator<Item = String>>(
+    args: I,
+    env_override: Option<OsString>,
+) -> Result<String, String> {
+    let mut image = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--image=") {
+            image = Some(value.to_string());
+        } else if arg == "--image" {
+            image = Some(args.next().ok_or("--image requires a path")?);
+        }
+    }
+    if let Some(image) = image {
+        return Ok(image);
+    }
+    Ok(match env_override {
+        Some(path) if !path.is_empty() => path.to_string_lossy().into_owned(),
+        _ => DISK_PATH.to_string(),
+    })
+}
+
+pub fn perform_disk_initialization(image: &str, tx: Sender<BootProgress>) {
     tx.send(BootProgress::Step("🧠 Initializing virtual disk..."))
         .unwrap();
 
-    let disk_exists = std::path::Path::new(DISK_PATH).exists();
+    let disk_exists = std::path::Path::new(image).exists();
 
-    // 初始化 FileDisk
-    let disk = match FileDisk::new(DISK_PATH, &tx) {
-        Ok(d) => d,
+    // 在镜像文件上初始化 FileDisk
+    let mut fs = match FileSystem::with_image_file(image, &tx) {
+        Ok(fs) => fs,
         Err(e) => {
             tx.send(BootProgress::Finished(Err(Box::new(e)))).unwrap();
             return;
@@ -21,7 +46,8 @@ pub fn perform_disk_initialization(tx: Sender<BootProgress>) {
     tx.send(BootProgress::Step("⚙️ Mounting file system..."))
         .unwrap();
 
-    let mut fs = FileSystem::new(disk);
+    // shell 下保留格式化等过程输出，可用 verbose off 关闭
+    fs.verbose = true;
 
     if !disk_exists {
         // 只有“明确是新磁盘”才格式化
@@ -47,5 +73,35 @@ pub fn perform_disk_initialization(tx: Sender<BootProgress>) {
         std::thread::sleep(std::time::Duration::from_millis(5));
     }
 
-    tx.send(BootProgress::Finished(Ok(fs))).unwrap();
+    tx.send(BootProgress::Finished(Ok(Box::new(fs)))).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_from_args() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(image_from_args(args(&[]), None).unwrap(), DISK_PATH);
+        assert_eq!(
+            image_from_args(args(&[]), Some("env.img".into())).unwrap(),
+            "env.img"
+        );
+        assert_eq!(
+            image_from_args(args(&[]), Some("".into())).unwrap(),
+            DISK_PATH
+        );
+
+        // 命令行参数优先于环境变量
+        assert_eq!(
+            image_from_args(args(&["--image", "a.img"]), Some("env.img".into())).unwrap(),
+            "a.img"
+        );
+        assert_eq!(
+            image_from_args(args(&["--color=never", "--image=b.img"]), None).unwrap(),
+            "b.img"
+        );
+        assert!(image_from_args(args(&["--image"]), None).is_err());
+    }
 }
@@ -0,0 +1,70 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    sync::Mutex,
+};
+
+use crate::{
+    disk::{
+        block_device::BlockDevice,
+        types::{Block, BLOCK_SIZE, DISK_SIZE},
+    },
+    fs::error::FileSystemError,
+};
+
+/// 完全在内存中的块设备，读写语义与 FileDisk 相同，但不触碰宿主文件系统。
+/// 主要用于测试：不留下镜像文件，也省去文件 I/O
+#[derive(Debug)]
+pub struct MemDisk {
+    data: Mutex<Vec<u8>>,
+}
+
+impl MemDisk {
+    /// 创建 DISK_SIZE 字节、全部为 0 的内存磁盘
+    pub fn new() -> Self {
+        Self {
+            data: Mutex::new(vec![0; DISK_SIZE as usize]),
+        }
+    }
+}
+
+impl Default for MemDisk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockDevice for MemDisk {
+    fn read_block(&self, block_id: u64, buf: &mut Block) -> Result<()> {
+        let data = self.data.lock().unwrap();
+        let start = block_id as usize * BLOCK_SIZE;
+        match data.get(start..start + BLOCK_SIZE) {
+            Some(block) => {
+                buf.copy_from_slice(block);
+                Ok(())
+            }
+            // 与 FileDisk 读到镜像末尾之后时返回同样的错误
+            None => Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                FileSystemError::Corrupted(format!(
+                    "block {} lies beyond the end of the disk image",
+                    block_id
+                )),
+            )),
+        }
+    }
+
+    fn write_block(&self, block_id: u64, buf: &Block) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let start = block_id as usize * BLOCK_SIZE;
+        // 写到末尾之后时像文件一样自动扩展
+        if data.len() < start + BLOCK_SIZE {
+            data.resize(start + BLOCK_SIZE, 0);
+        }
+        data[start..start + BLOCK_SIZE].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn block_count(&self) -> Result<u64> {
+        Ok((self.data.lock().unwrap().len() / BLOCK_SIZE) as u64)
+    }
+}
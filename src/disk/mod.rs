@@ -1,18 +1,23 @@
+pub mod block_cache;
 pub mod block_device;
 pub mod file_disk;
 pub mod init;
+pub mod mem_disk;
 pub mod types;
 
 // 对外导出常用类型，便于上层使用
+pub use block_cache::BlockCache;
 pub use block_device::BlockDevice;
 pub use file_disk::FileDisk;
 pub use init::perform_disk_initialization;
-pub use types::{Block, BLOCK_SIZE};
+pub use mem_disk::MemDisk;
+pub use types::{Block, BLOCK_SIZE, DISK_SIZE};
 
 // src/disk/mod.rs 底部添加
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fs::error::FileSystemError;
     use std::sync::{mpsc::channel, Arc};
 
     #[test]
@@ -46,4 +51,110 @@ mod tests {
 
         println!("✅ Disk read/write test passed! Read: {}", read_str);
     }
+
+    #[test]
+    fn test_image_lock_is_exclusive() {
+        let (tx, _rx) = channel();
+        let path = std::env::temp_dir().join(format!("minifs_lock_{}.img", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let disk = FileDisk::new(path, &tx).unwrap();
+        let err = FileDisk::new(path, &tx).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ResourceBusy);
+        assert!(err.to_string().contains("already in use"));
+
+        // 释放后可以重新打开
+        drop(disk);
+        assert!(FileDisk::new(path, &tx).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_past_truncated_image() {
+        let (tx, _rx) = channel();
+        let path = std::env::temp_dir().join(format!("minifs_short_{}.img", std::process::id()));
+        let disk = FileDisk::new(path.to_str().unwrap(), &tx).unwrap();
+
+        // 人为把镜像截短到 2 个块
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(2 * BLOCK_SIZE as u64)
+            .unwrap();
+
+        let mut buf: Block = [0u8; BLOCK_SIZE];
+        assert!(disk.read_block(1, &mut buf).is_ok());
+
+        let err = disk.read_block(5, &mut buf).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        match err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<FileSystemError>())
+        {
+            Some(FileSystemError::Corrupted(msg)) => assert!(msg.contains("block 5")),
+            other => panic!("expected Corrupted error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_mem_disk_read_write() {
+        let disk = MemDisk::new();
+        let mut buf: Block = [0u8; BLOCK_SIZE];
+        disk.read_block(7, &mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == 0));
+
+        buf[..5].copy_from_slice(b"hello");
+        disk.write_block(7, &buf).unwrap();
+        let mut read_buf: Block = [0u8; BLOCK_SIZE];
+        disk.read_block(7, &mut read_buf).unwrap();
+        assert_eq!(read_buf, buf);
+
+        // 越过末尾读取与 FileDisk 一样报告损坏
+        let last = DISK_SIZE / BLOCK_SIZE as u64;
+        let err = disk.read_block(last, &mut read_buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_retry_transient_errors() {
+        use std::io::{Error, ErrorKind};
+        use std::time::Duration;
+
+        let policy = file_disk::RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+        };
+
+        // 前两次瞬时失败，第三次成功
+        let mut calls = 0;
+        let result = file_disk::with_retry(&policy, || {
+            calls += 1;
+            if calls < 3 {
+                Err(Error::from(ErrorKind::Interrupted))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        // 超过次数后放弃
+        let mut calls = 0;
+        let result: std::io::Result<()> = file_disk::with_retry(&policy, || {
+            calls += 1;
+            Err(Error::from(ErrorKind::WouldBlock))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert_eq!(calls, 3);
+
+        // 非瞬时错误不重试
+        let mut calls = 0;
+        let result: std::io::Result<()> = file_disk::with_retry(&policy, || {
+            calls += 1;
+            Err(Error::from(ErrorKind::PermissionDenied))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
 }
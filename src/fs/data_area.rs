@@ -1,76 +1,141 @@
-use crate::disk::{BlockDevice, FileDisk, BLOCK_SIZE};
-use serde::{Deserialize, Serialize};
+use crate::{
+    disk::{Block, BlockCache, BlockDevice, BLOCK_SIZE},
+    fs::error::FileSystemError,
+    utils::crc32,
+};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// 写回缓存最多暂存的脏块数，超过后整体刷到磁盘
+pub const WRITE_BACK_LIMIT: usize = 256;
+// 读缓存容量（块），目录遍历等重复读取直接命中缓存
+pub const READ_CACHE_BLOCKS: usize = 64;
+// 校验模式下每块末尾保留给 CRC32 的字节数
+pub const CHECKSUM_LEN: usize = 4;
+
+/// 数据区：按需通过底层块设备读写块，只在内存里暂存尚未写回的脏块
+#[derive(Debug)]
 pub struct DataArea {
-    pub blocks: Vec<u8>,   // 数据块
-    pub total_blocks: u64, // 块总数
-    pub start_block: u64,  // 起始块号
-    #[serde(skip)] // 不序列化
-    dirty: Vec<bool>, // 每个块是否被修改
+    disk: BlockCache<Arc<dyn BlockDevice>>,
+    pub total_blocks: u64,            // 块总数
+    pub start_block: u64,             // 起始块号
+    dirty: BTreeMap<u64, Box<Block>>, // 已修改、尚未写回的块（按块号排序写回）
+    pub checksums: bool,              // 每块末尾 4 字节存 CRC32，读时校验
 }
 
 impl DataArea {
-    pub fn new(start_block: u64, total_blocks: u64) -> Self {
+    pub fn new(disk: Arc<dyn BlockDevice>, start_block: u64, total_blocks: u64) -> Self {
         Self {
-            blocks: vec![0u8; (total_blocks as usize) * BLOCK_SIZE], // 扁平化存储
+            disk: BlockCache::new(disk, READ_CACHE_BLOCKS),
             total_blocks,
             start_block,
-            dirty: vec![false; total_blocks as usize],
+            dirty: BTreeMap::new(),
+            checksums: false,
         }
     }
 
+    /// 开启或关闭块校验；必须与镜像格式化时的设置一致
+    pub fn with_checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// 每块可存放的有效数据字节数：校验模式下为 4092，否则为整块 4096
+    pub fn payload_size(&self) -> usize {
+        if self.checksums {
+            BLOCK_SIZE - CHECKSUM_LEN
+        } else {
+            BLOCK_SIZE
+        }
+    }
+
+    // 用固定字节填充整个数据区，直接写到磁盘，不经过缓存；
+    // interrupt 置位时在下一块之前停下，返回已填充的块数
+    pub fn fill(&mut self, byte: u8, interrupt: &AtomicBool) -> std::io::Result<u64> {
+        self.dirty.clear();
+        let buf = [byte; BLOCK_SIZE];
+        for i in 0..self.total_blocks {
+            if interrupt.load(Ordering::SeqCst) {
+                return Ok(i);
+            }
+            self.disk.device().write_block(self.start_block + i, &buf)?;
+        }
+        Ok(self.total_blocks)
+    }
+
     pub fn write_block(&mut self, index: u64, buf: &[u8]) -> Result<(), String> {
         if index >= self.total_blocks {
             return Err("Block index out of range".to_string());
         }
-        if buf.len() > BLOCK_SIZE {
+        if buf.len() > self.payload_size() {
             return Err("Data too large".to_string());
         }
-        let start = (index as usize) * BLOCK_SIZE;
-        self.blocks[start..start + buf.len()].copy_from_slice(buf);
-        if buf.len() < BLOCK_SIZE {
-            self.blocks[start + buf.len()..start + BLOCK_SIZE].fill(0);
+        let mut block = Box::new([0u8; BLOCK_SIZE]);
+        block[..buf.len()].copy_from_slice(buf);
+        if self.checksums {
+            let (payload, tail) = block.split_at_mut(BLOCK_SIZE - CHECKSUM_LEN);
+            tail.copy_from_slice(&crc32(payload).to_le_bytes());
+        }
+        self.dirty.insert(index, block);
+
+        if self.dirty.len() > WRITE_BACK_LIMIT {
+            self.sync().map_err(|e| e.to_string())?;
         }
-        self.dirty[index as usize] = true;
         Ok(())
     }
 
-    pub fn read_block(&self, index: u64) -> Option<&[u8]> {
+    /// 读取一个块：优先返回缓存中的脏块，否则从磁盘读取。
+    /// 校验模式下从磁盘读出的块 CRC 不符时返回 Corrupted。
+    pub fn read_block(&self, index: u64) -> Result<Block, FileSystemError> {
         if index >= self.total_blocks {
-            return None;
+            return Err(FileSystemError::Corrupted(format!(
+                "data block {} out of range",
+                index
+            )));
         }
-        let start = (index as usize) * BLOCK_SIZE;
-        Some(&self.blocks[start..start + BLOCK_SIZE])
-    }
-
-    pub fn sync(&mut self, disk: &mut FileDisk) -> std::io::Result<()> {
-        for i in 0..self.total_blocks {
-            if self.dirty[i as usize] {
-                let start = (i as usize) * BLOCK_SIZE;
-
-                // 临时数组，写入 disk
-                let mut buf = [0u8; BLOCK_SIZE];
-                buf.copy_from_slice(&self.blocks[start..start + BLOCK_SIZE]);
-
-                disk.write_block(self.start_block + i, &mut buf)?;
-                self.dirty[i as usize] = false;
+        if let Some(block) = self.dirty.get(&index) {
+            return Ok(**block);
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.disk.read_block(self.start_block + index, &mut buf)?;
+        if self.checksums {
+            let (payload, tail) = buf.split_at(BLOCK_SIZE - CHECKSUM_LEN);
+            if crc32(payload).to_le_bytes() != tail {
+                return Err(FileSystemError::Corrupted(format!(
+                    "checksum mismatch in data block {}",
+                    index
+                )));
             }
         }
-        Ok(())
+        Ok(buf)
     }
 
-    pub fn load(&mut self, disk: &mut FileDisk) -> std::io::Result<()> {
-        for i in 0..self.total_blocks {
-            let start = (i as usize) * BLOCK_SIZE;
+    /// 把所有脏块写回磁盘，返回写回的块数。
+    /// 每个块直写到磁盘成功后才清除它的脏标记，中途失败时剩余的脏块保留，下次 sync 重试。
+    pub fn sync(&mut self) -> std::io::Result<u64> {
+        let indices: Vec<u64> = self.dirty.keys().copied().collect();
+        let written = indices.len() as u64;
+        for index in indices {
+            self.disk
+                .write_through(self.start_block + index, &self.dirty[&index])?;
+            self.dirty.remove(&index);
+        }
+        self.disk.flush()?;
+        Ok(written)
+    }
 
-            // 临时数组，读取 disk
-            let mut buf = [0u8; BLOCK_SIZE];
-            disk.read_block(self.start_block + i, &mut buf)?;
+    /// 尚未写回磁盘的脏块数
+    pub fn dirty_count(&self) -> usize {
+        self.dirty.len()
+    }
 
-            self.blocks[start..start + BLOCK_SIZE].copy_from_slice(&buf);
-            self.dirty[i as usize] = false;
-        }
-        Ok(())
+    /// 当前缓存占用的字节数（脏块 + 读缓存）
+    pub fn cached_bytes(&self) -> usize {
+        (self.dirty.len() + self.disk.len()) * BLOCK_SIZE
     }
 }
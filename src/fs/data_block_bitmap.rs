@@ -1,6 +1,10 @@
-use crate::disk::{Block, BlockDevice, FileDisk};
+use crate::disk::{Block, BlockDevice};
 use serde::{Deserialize, Serialize};
 
+/// 数据块编号一律相对数据区起始位置（位图下标），只有 DataArea 内部才换算成磁盘绝对块号。
+/// 0 号块在 inode 的块指针里表示“空槽”，format 时预留，永远不分配给文件。
+pub const RESERVED_BLOCK: u64 = 0;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DataBlockBitmap {
     pub bits: Vec<u8>,     // 位图数据，每个 bit 表示一个数据块是否被使用
@@ -11,7 +15,7 @@ pub struct DataBlockBitmap {
 
 impl DataBlockBitmap {
     pub fn new(total_blocks: u64, start_block: u64) -> Self {
-        let byte_len = ((total_blocks + 7) / 8) as usize;
+        let byte_len = total_blocks.div_ceil(8) as usize;
 
         Self {
             bits: vec![0; byte_len],
@@ -26,10 +30,15 @@ impl DataBlockBitmap {
         for (byte_index, byte) in self.bits.iter_mut().enumerate() {
             if *byte != 0xFF {
                 for bit in 0..8 {
+                    let index = (byte_index * 8 + bit) as u64;
+                    // 最后一个字节里超出 total_blocks 的位不对应真实的块
+                    if index >= self.total_blocks {
+                        return None;
+                    }
                     if *byte & (1 << bit) == 0 {
                         *byte |= 1 << bit;
                         self.free_blocks -= 1;
-                        return Some((byte_index * 8 + bit) as u64);
+                        return Some(index);
                     }
                 }
             }
@@ -37,10 +46,48 @@ impl DataBlockBitmap {
         None
     }
 
+    // 优先分配 hint 及其之后的第一个空闲块，让同一文件的块尽量连续；
+    // hint 之后都已占用时退回从头扫描
+    pub fn alloc_near(&mut self, hint: u64) -> Option<u64> {
+        for index in hint.max(RESERVED_BLOCK + 1)..self.total_blocks {
+            let byte_index = (index / 8) as usize;
+            let bit_index = (index % 8) as u8;
+            if self.bits[byte_index] & (1 << bit_index) == 0 {
+                self.bits[byte_index] |= 1 << bit_index;
+                self.free_blocks -= 1;
+                return Some(index);
+            }
+        }
+        self.alloc()
+    }
+
+    // 找到 n 个连续的空闲块并一次性全部标记，返回起始编号；
+    // 没有足够长的空闲段时返回 None，不标记任何块
+    pub fn alloc_contiguous(&mut self, n: usize) -> Option<u64> {
+        if n == 0 {
+            return None;
+        }
+        let mut run_start = RESERVED_BLOCK + 1;
+        for index in RESERVED_BLOCK + 1..self.total_blocks {
+            if self.is_used(index) {
+                run_start = index + 1;
+                continue;
+            }
+            if index + 1 - run_start == n as u64 {
+                for block in run_start..=index {
+                    self.bits[(block / 8) as usize] |= 1 << (block % 8);
+                }
+                self.free_blocks -= n as u64;
+                return Some(run_start);
+            }
+        }
+        None
+    }
+
     // 释放一个数据块
     pub fn free(&mut self, block_index: u64) {
-        if block_index > self.total_blocks {
-            return; // 防止越界
+        if block_index >= self.total_blocks || block_index == RESERVED_BLOCK {
+            return; // 防止越界，预留块不可释放
         }
 
         let byte_index = (block_index / 8) as usize;
@@ -52,6 +99,21 @@ impl DataBlockBitmap {
         }
     }
 
+    // 把指定块标记为已占用（整理时把块搬到确定位置）
+    pub fn mark_used(&mut self, block_index: u64) {
+        if block_index >= self.total_blocks {
+            return;
+        }
+
+        let byte_index = (block_index / 8) as usize;
+        let bit_index = (block_index % 8) as u8;
+
+        if self.bits[byte_index] & (1 << bit_index) == 0 {
+            self.bits[byte_index] |= 1 << bit_index;
+            self.free_blocks -= 1;
+        }
+    }
+
     pub fn is_used(&self, block_index: u64) -> bool {
         let byte_index = (block_index / 8) as usize;
         let bit_index = (block_index % 8) as u8;
@@ -59,8 +121,8 @@ impl DataBlockBitmap {
     }
 
     // 从磁盘加载数据块位图
-    pub fn load(disk: &mut FileDisk, start_block: u64, total_blocks: u64) -> Self {
-        let size_in_block = ((total_blocks + 8 * 4096 - 1) / (8 * 4096)) as u64;
+    pub fn load(disk: &dyn BlockDevice, start_block: u64, total_blocks: u64) -> Self {
+        let size_in_block = total_blocks.div_ceil(8 * 4096);
         let mut bits = Vec::with_capacity((size_in_block * 4096) as usize);
         let mut block_buf: Block = [0; 4096];
 
@@ -70,7 +132,7 @@ impl DataBlockBitmap {
         }
 
         // 截掉多余字节，只保留有效位
-        let byte_len = ((total_blocks + 7) / 8) as usize;
+        let byte_len = total_blocks.div_ceil(8) as usize;
         bits.truncate(byte_len);
 
         let free_blocks = total_blocks - bits.iter().map(|b| b.count_ones() as u64).sum::<u64>();
@@ -84,11 +146,12 @@ impl DataBlockBitmap {
     }
 
     // 将数据块位图写回磁盘
-    pub fn sync(&self, disk: &mut FileDisk) -> std::io::Result<()> {
+    /// 写回位图，返回写入的块数
+    pub fn sync(&self, disk: &dyn BlockDevice) -> std::io::Result<u64> {
         let mut bits_to_write = self.bits.clone();
 
         // 每块 4KB，不够用 0 填充
-        let total_blocks_in_bitmap = (bits_to_write.len() as u64 + 4096 - 1) / 4096;
+        let total_blocks_in_bitmap = (bits_to_write.len() as u64).div_ceil(4096);
         bits_to_write.resize((total_blocks_in_bitmap * 4096) as usize, 0);
 
         let mut block_buf: Block = [0; 4096];
@@ -99,6 +162,36 @@ impl DataBlockBitmap {
             disk.write_block(self.start_block + i, &block_buf)?;
         }
 
-        Ok(())
+        Ok(total_blocks_in_bitmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_contiguous_skips_small_holes() {
+        let mut bitmap = DataBlockBitmap::new(64, 0);
+        assert_eq!(bitmap.alloc(), Some(RESERVED_BLOCK));
+        for _ in 1..=20 {
+            bitmap.alloc().unwrap();
+        }
+        // 留下 2 块和 4 块两个空洞
+        for block in [3, 4, 8, 9, 10, 11] {
+            bitmap.free(block);
+        }
+
+        assert_eq!(bitmap.alloc_contiguous(3), Some(8));
+        assert!((8..11).all(|b| bitmap.is_used(b)));
+        assert!(!bitmap.is_used(11));
+        assert_eq!(bitmap.alloc_contiguous(2), Some(3));
+
+        // 找不到时什么都不标记
+        let free = bitmap.free_blocks;
+        assert_eq!(bitmap.alloc_contiguous(50), None);
+        assert_eq!(bitmap.free_blocks, free);
+        assert_eq!(bitmap.alloc_contiguous(0), None);
+        assert_eq!(bitmap.alloc_contiguous(43), Some(21));
     }
 }
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use crate::fs::{
+    data_block_bitmap::RESERVED_BLOCK, encode_ptrs, error::FileSystemError, FileSystem,
+};
+
+/// defragment 的结果
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DefragReport {
+    pub inodes: u64,            // 参与整理的 inode 数
+    pub fragmented_before: u64, // 整理前数据块不连续的 inode 数
+    pub blocks_moved: u64,      // 换了位置的块数
+    pub interrupted: bool,      // 被中断时已搬的块依然有效，只是没有全部整理完
+}
+
+impl FileSystem {
+    /// 碎片整理：按 inode 编号把每个 inode 的块依次排到数据区开头，
+    /// 数据块按文件内顺序连续存放，随后是间接块和属性块，空闲空间全部留在末尾。
+    /// 只在 fsck 无误时进行；中途被中断也会按已搬动的位置改写指针，结束时 sync
+    pub fn defragment(&mut self) -> Result<DefragReport, String> {
+        self.ensure_writable()?;
+        if !self.fsck(false).is_clean() {
+            return Err(FileSystemError::Corrupted(
+                "fsck reported problems, refusing to defragment".to_string(),
+            )
+            .to_string());
+        }
+
+        // 1. 规划：order[i] 是应当放到 RESERVED_BLOCK + 1 + i 的块
+        let mut report = DefragReport::default();
+        let mut order = Vec::new();
+        let mut location = HashMap::new(); // 块当前所在位置
+        let mut occupant = HashMap::new(); // 位置上当前存放的块
+        for inode in self.inode_table.inodes.iter().filter(|i| !i.is_free()) {
+            let data = self.file_blocks(inode)?;
+            if data.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+                report.fragmented_before += 1;
+            }
+            let meta = [
+                inode.indirect_block,
+                inode.double_indirect_block,
+                inode.xattr_block,
+            ];
+            for block in data.into_iter().chain(meta.into_iter().flatten()) {
+                if location.insert(block, block).is_some() {
+                    return Err(FileSystemError::Corrupted(format!(
+                        "block {} is referenced twice",
+                        block
+                    ))
+                    .to_string());
+                }
+                occupant.insert(block, block);
+                order.push(block);
+            }
+            report.inodes += 1;
+        }
+
+        // 2. 按目标位置依次搬块；目标位置上有别的块时两者交换
+        for (i, &block) in order.iter().enumerate() {
+            if self.take_interrupt() {
+                report.interrupted = true;
+                break;
+            }
+            let dst = RESERVED_BLOCK + 1 + i as u64;
+            let src = location[&block];
+            if src == dst {
+                continue;
+            }
+            let moving = self.read_payload(src)?;
+            match occupant.remove(&dst) {
+                Some(other) => {
+                    let displaced = self.read_payload(dst)?;
+                    self.data_area.write_block(src, &displaced)?;
+                    occupant.insert(src, other);
+                    location.insert(other, src);
+                }
+                None => {
+                    occupant.remove(&src);
+                    self.data_bitmap.free(src);
+                    self.data_bitmap.mark_used(dst);
+                }
+            }
+            self.data_area.write_block(dst, &moving)?;
+            occupant.insert(dst, block);
+            location.insert(block, dst);
+            report.blocks_moved += 1;
+        }
+
+        // 3. 按块的最终位置改写 inode 指针和间接块里的指针
+        for id in 0..self.inode_table.inodes.len() {
+            let mut inode = self.inode_table.inodes[id].clone();
+            if inode.is_free() {
+                continue;
+            }
+            for ptr in inode.direct_blocks.iter_mut().filter(|p| **p != 0) {
+                *ptr = location[ptr];
+            }
+            for ptr in [
+                &mut inode.indirect_block,
+                &mut inode.double_indirect_block,
+                &mut inode.xattr_block,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *ptr = location[ptr];
+            }
+
+            if let Some(indirect) = inode.indirect_block {
+                let mut ptrs = self.read_ptr_block(indirect)?;
+                for ptr in ptrs.iter_mut().filter(|p| **p != 0) {
+                    *ptr = location[ptr];
+                }
+                self.data_area.write_block(indirect, &encode_ptrs(&ptrs))?;
+            }
+            if let Some(slot) = self.inode_table.get_inode_mut(id as u64) {
+                *slot = inode;
+            }
+        }
+
+        self.super_block.dirty = true;
+        self.sync().map_err(|e| e.to_string())?;
+        Ok(report)
+    }
+
+    // 辅助方法：读出一个块的有效载荷（不含校验尾），用于原样搬到别处
+    fn read_payload(&self, block_id: u64) -> Result<Vec<u8>, String> {
+        let data = self
+            .data_area
+            .read_block(block_id)
+            .map_err(|e| e.to_string())?;
+        Ok(data[..self.data_area.payload_size()].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::{data_block_bitmap::RESERVED_BLOCK, tests::test_fs, OpenFlags};
+
+    #[test]
+    fn test_defragment_makes_files_contiguous() {
+        let mut fs = test_fs();
+        let payload = fs.data_area.payload_size();
+        let first: Vec<u8> = (0..payload).map(|i| (i % 251) as u8).collect();
+        let second: Vec<u8> = (0..payload).map(|i| (i % 13) as u8).collect();
+
+        // 跨过直接指针、带间接块的大文件，前面留一个空洞让它整体前移
+        let big: Vec<u8> = (0..20 * payload).map(|i| (i % 241) as u8).collect();
+        fs.create_or_write_file("/", "z.txt", b"z").unwrap();
+        fs.create_or_write_file("/", "big.bin", &big).unwrap();
+        fs.delete_file("/", "z.txt").unwrap();
+
+        // a.txt 的第二块只能排在 b.txt 之后，删掉 b.txt 再留下一个空洞
+        fs.create_or_write_file("/", "a.txt", &first).unwrap();
+        fs.create_or_write_file("/", "b.txt", b"b").unwrap();
+        let mut fh = fs
+            .open("/a.txt", OpenFlags::WRITE | OpenFlags::APPEND)
+            .unwrap();
+        fs.write_at(&mut fh, &second).unwrap();
+        fs.delete_file("/", "b.txt").unwrap();
+        fs.set_xattr("/a.txt", "user.tag", b"kept").unwrap();
+
+        let blocks_of = |fs: &crate::fs::FileSystem| {
+            let inode = fs.stat_path("/a.txt").unwrap();
+            fs.file_blocks(&inode).unwrap()
+        };
+        let before = blocks_of(&fs);
+        assert_ne!(before[1], before[0] + 1);
+        let free = fs.data_bitmap.free_blocks;
+
+        let report = fs.defragment().unwrap();
+        assert!(report.fragmented_before >= 1);
+        assert!(report.blocks_moved > 0);
+        assert!(!report.interrupted);
+
+        let after = blocks_of(&fs);
+        assert_eq!(after[1], after[0] + 1);
+        let mut expected = first.clone();
+        expected.extend(&second);
+        assert_eq!(fs.read_path("/a.txt").unwrap(), expected);
+        assert_eq!(fs.read_path("/big.bin").unwrap(), big);
+        assert_eq!(
+            fs.get_xattr("/a.txt", "user.tag").unwrap().unwrap(),
+            b"kept"
+        );
+
+        // 已用块全部排在开头，空闲计数不变且 fsck 依然干净
+        let used = fs.data_bitmap.total_blocks - fs.data_bitmap.free_blocks;
+        assert!((RESERVED_BLOCK + 1..used).all(|b| fs.data_bitmap.is_used(b)));
+        assert!(!fs.data_bitmap.is_used(used));
+        assert_eq!(fs.data_bitmap.free_blocks, free);
+        assert!(fs.fsck(false).is_clean());
+
+        // 再次整理无需移动
+        assert_eq!(fs.defragment().unwrap().blocks_moved, 0);
+    }
+}
@@ -1,11 +1,65 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::fs::inode_table::InodeType;
+
+/// 单个目录项名字的最大字节数
+pub const MAX_NAME_LEN: usize = 255;
+
+/// 检查目录项名字是否合法：非空、不含 `/` 和 NUL、不是 `.`/`..`、不超过 MAX_NAME_LEN
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("name is empty".into());
+    }
+    if name == "." || name == ".." {
+        return Err(format!("'{}' is reserved", name));
+    }
+    if name.contains('/') || name.contains('\0') {
+        return Err(format!("'{}' contains '/' or NUL", name.escape_default()));
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "name is {} bytes, longer than {}",
+            name.len(),
+            MAX_NAME_LEN
+        ));
+    }
+    Ok(())
+}
+
 // 目录项类型
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum DirEntryType {
     File,
     Directory,
+    Symlink,
+}
+
+impl DirEntryType {
+    /// 按类型分组时的先后：目录、文件、符号链接（`SortBy::Type`）
+    pub fn type_rank(&self) -> u8 {
+        match self {
+            DirEntryType::Directory => 0,
+            DirEntryType::File => 1,
+            DirEntryType::Symlink => 2,
+        }
+    }
+
+    /// 列目录时的默认排序先后：只把目录排在前面，文件和符号链接按名字混排；
+    /// 与 type_rank 的区别只在于不再区分文件和符号链接
+    pub fn sort_rank(&self) -> u8 {
+        self.type_rank().min(1)
+    }
+}
+
+impl From<&InodeType> for DirEntryType {
+    fn from(inode_type: &InodeType) -> Self {
+        match inode_type {
+            InodeType::File => DirEntryType::File,
+            InodeType::Directory => DirEntryType::Directory,
+            InodeType::Symlink => DirEntryType::Symlink,
+        }
+    }
 }
 
 // 一个目录项
@@ -54,6 +108,26 @@ impl Directory {
         inode_index: usize,
         name: &str,
         entry_type: DirEntryType,
+    ) -> Result<(), String> {
+        validate_name(name)?;
+        self.push_entry(inode_index, name, entry_type)
+    }
+
+    /// 写入新目录必需的 `.` 与 `..`（`add` 不接受这两个保留名）
+    pub fn add_dot_entries(
+        &mut self,
+        self_index: usize,
+        parent_index: usize,
+    ) -> Result<(), String> {
+        self.push_entry(self_index, ".", DirEntryType::Directory)?;
+        self.push_entry(parent_index, "..", DirEntryType::Directory)
+    }
+
+    fn push_entry(
+        &mut self,
+        inode_index: usize,
+        name: &str,
+        entry_type: DirEntryType,
     ) -> Result<(), String> {
         if self.index_map.contains_key(name) {
             return Err(format!("Entry '{}' already exists", name));
@@ -79,9 +153,27 @@ impl Directory {
         }
     }
 
+    /// 原地修改目录项的名字，inode 编号和类型不变
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), String> {
+        if old == "." || old == ".." {
+            return Err(format!("'{}' is reserved", old));
+        }
+        validate_name(new)?;
+        let idx = *self
+            .index_map
+            .get(old)
+            .ok_or_else(|| format!("Entry '{}' not found", old))?;
+        if self.index_map.contains_key(new) {
+            return Err(format!("Entry '{}' already exists", new));
+        }
+        self.entries[idx].name = new.to_string();
+        self.index_map.remove(old);
+        self.index_map.insert(new.to_string(), idx);
+        Ok(())
+    }
+
     // 查找目录项，返回 inode_index
     pub fn find(&self, name: &str) -> Option<usize> {
-        println!("Directory: {:?}", self);
         self.index_map
             .get(name)
             .map(|&idx| self.entries[idx].inode_index)
@@ -91,13 +183,87 @@ impl Directory {
         self.index_map.get(name).map(|&idx| &self.entries[idx])
     }
 
+    /// 把分散在多个目录块中的目录项按原顺序重新装箱，每块序列化后不超过 capacity 字节。
+    /// 返回的块数不会多于输入，删除大量目录项后用来回收尾部的目录块
+    pub fn compact(parts: Vec<Directory>, capacity: u64) -> Result<Vec<Directory>, String> {
+        let Some(inode_index) = parts.first().map(|dir| dir.inode_index) else {
+            return Ok(Vec::new());
+        };
+        let mut packed = Vec::new();
+        let mut current = Directory::new(inode_index);
+        for entry in parts.into_iter().flat_map(|dir| dir.entries) {
+            current.push_entry(entry.inode_index, &entry.name, entry.entry_type.clone())?;
+            if bincode::serialized_size(&current).map_err(|e| e.to_string())? <= capacity {
+                continue;
+            }
+            // 放不下：把这一项挪到新块
+            current.remove(&entry.name);
+            if current.entries.is_empty() {
+                return Err(format!("Entry '{}' does not fit in a block", entry.name));
+            }
+            packed.push(std::mem::replace(&mut current, Directory::new(inode_index)));
+            current.push_entry(entry.inode_index, &entry.name, entry.entry_type)?;
+        }
+        packed.push(current);
+        Ok(packed)
+    }
+
     pub fn list_sorted(&self) -> Vec<String> {
         let mut entries = self.entries.clone();
-        entries.sort_by(|a, b| match (&a.entry_type, &b.entry_type) {
-            (DirEntryType::Directory, DirEntryType::File) => std::cmp::Ordering::Less,
-            (DirEntryType::File, DirEntryType::Directory) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
+        entries.sort_by(|a, b| {
+            a.entry_type
+                .sort_rank()
+                .cmp(&b.entry_type.sort_rank())
+                .then_with(|| a.name.cmp(&b.name))
         });
         entries.into_iter().map(|e| e.name).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_rejects_bad_names() {
+        let mut dir = Directory::new(0);
+        dir.add_dot_entries(0, 0).unwrap();
+
+        for bad in ["", ".", "..", "a/b", "nul\0byte"] {
+            assert!(dir.add(1, bad, DirEntryType::File).is_err(), "{:?}", bad);
+        }
+        assert!(dir
+            .add(1, &"x".repeat(MAX_NAME_LEN + 1), DirEntryType::File)
+            .is_err());
+        dir.add(1, &"x".repeat(MAX_NAME_LEN), DirEntryType::File)
+            .unwrap();
+        assert_eq!(dir.entries.len(), 3);
+    }
+
+    #[test]
+    fn test_rename_in_place() {
+        let mut dir = Directory::new(0);
+        dir.add_dot_entries(0, 0).unwrap();
+        dir.add(1, "a", DirEntryType::File).unwrap();
+        dir.add(2, "b", DirEntryType::File).unwrap();
+
+        dir.rename("a", "c").unwrap();
+        assert_eq!(dir.find("c"), Some(1));
+        assert_eq!(dir.find("a"), None);
+        assert_eq!(dir.entries[2].name, "c");
+
+        assert!(dir.rename("missing", "d").is_err());
+        assert!(dir.rename("c", "b").is_err());
+        assert!(dir.rename("..", "up").is_err());
+        assert!(dir.rename("c", "x/y").is_err());
+    }
+
+    #[test]
+    fn test_list_sorted_puts_directories_first() {
+        let mut dir = Directory::new(0);
+        dir.add(1, "b.txt", DirEntryType::File).unwrap();
+        dir.add(2, "a.lnk", DirEntryType::Symlink).unwrap();
+        dir.add(3, "z", DirEntryType::Directory).unwrap();
+        assert_eq!(dir.list_sorted(), ["z", "a.lnk", "b.txt"]);
+    }
+}
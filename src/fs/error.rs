@@ -14,6 +14,9 @@ pub enum FileSystemError {
     InvalidPath(String),       // 路径非法
     InvalidInode(u32),         // inode 无效
     Corrupted(String),         // 文件系统损坏
+    InvalidLayout(String),     // 格式化参数无法构成合法布局
+    Interrupted(String),       // 长操作被中断，带已完成的进度
+    UnsupportedVersion(u32),   // 镜像格式版本比当前程序新
                                // 可以继续扩展其他错误类型
 }
 
@@ -38,6 +41,14 @@ impl fmt::Display for FileSystemError {
             Self::InvalidPath(path) => write!(f, "Invalid path: {}", path),
             Self::InvalidInode(inode) => write!(f, "Invalid inode: {}", inode),
             Self::Corrupted(desc) => write!(f, "File system corrupted: {}", desc),
+            Self::InvalidLayout(desc) => write!(f, "Invalid file system layout: {}", desc),
+            Self::Interrupted(progress) => write!(f, "Interrupted: {}", progress),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported image format version {} (this build supports up to {})",
+                version,
+                crate::fs::super_block::CURRENT_VERSION
+            ),
         }
     }
 }
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+
+use crate::{
+    fs::{data_block_bitmap::RESERVED_BLOCK, inode_table::InodeType, FileSystem},
+    utils::join_path,
+};
+
+/// fsck 检查结果
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FsckReport {
+    pub free_inodes: (u64, u64),          // (超级块记录, 位图统计)
+    pub free_blocks: (u64, u64),          // (超级块记录, 位图统计)
+    pub inode_mismatches: Vec<u64>,       // 位图与 inode 表对是否已分配看法不一致的 inode
+    pub unmarked_blocks: Vec<(u64, u64)>, // (inode, 块号)：被引用但位图中标记为空闲
+    pub leaked_blocks: Vec<u64>,          // 位图中已占用但没有任何 inode 引用的块
+    pub dangling_entries: Vec<String>,    // 指向空闲 inode 的目录项
+    pub orphan_inodes: Vec<u64>,          // link_count 为 0 且没有被打开、却仍占着的 inode
+    pub repaired: bool,                   // 是否做过修复（回收孤儿 inode 或按位图修正计数）
+}
+
+impl FsckReport {
+    pub fn counters_match(&self) -> bool {
+        self.free_inodes.0 == self.free_inodes.1 && self.free_blocks.0 == self.free_blocks.1
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.counters_match()
+            && self.inode_mismatches.is_empty()
+            && self.unmarked_blocks.is_empty()
+            && self.leaked_blocks.is_empty()
+            && self.dangling_entries.is_empty()
+            && self.orphan_inodes.is_empty()
+    }
+}
+
+impl FileSystem {
+    /// 一致性检查：对照位图、inode 表、目录树和超级块计数。
+    /// `repair` 为 true 时回收孤儿 inode，并把超级块的空闲计数修正为位图的统计值。
+    pub fn fsck(&mut self, repair: bool) -> FsckReport {
+        // 只读挂载时只检查、不修复
+        let repair = repair && !self.read_only;
+        let mut report = FsckReport {
+            free_inodes: (self.super_block.free_inode, self.inode_bitmap.free_inodes),
+            free_blocks: (self.super_block.free_blocks, self.data_bitmap.free_blocks),
+            ..FsckReport::default()
+        };
+
+        // 1. inode 位图与 inode 表互相印证，同时收集所有被引用的块
+        let mut referenced = HashSet::new();
+        for (id, inode) in self.inode_table.inodes.iter().enumerate() {
+            let id = id as u64;
+            let allocated = !inode.is_free();
+            if allocated != self.inode_bitmap.is_used(id) {
+                report.inode_mismatches.push(id);
+            }
+            if !allocated {
+                continue;
+            }
+
+            let mut blocks = self.file_blocks(inode).unwrap_or_default();
+            blocks.extend(inode.indirect_block);
+            blocks.extend(inode.double_indirect_block);
+            blocks.extend(inode.xattr_block);
+            for block in blocks {
+                if !self.data_bitmap.is_used(block) {
+                    report.unmarked_blocks.push((id, block));
+                }
+                referenced.insert(block);
+            }
+        }
+
+        // 2. 位图中占用却无人引用的块视为泄漏
+        // 0 号块是预留的空指针，不算泄漏
+        report.leaked_blocks = (RESERVED_BLOCK + 1..self.data_bitmap.total_blocks)
+            .filter(|b| self.data_bitmap.is_used(*b) && !referenced.contains(b))
+            .collect();
+
+        // 3. 从根目录出发找出指向空闲 inode 的目录项
+        let mut visited = HashSet::new();
+        self.check_entries(0, "/", &mut visited, &mut report.dangling_entries);
+
+        // 4. 没有名字、也没有句柄的 inode 永远不会被释放
+        report.orphan_inodes = self.orphan_inodes();
+        if repair && !report.orphan_inodes.is_empty() && self.reclaim_orphans().is_ok() {
+            report.repaired = true;
+        }
+
+        if repair && !report.counters_match() {
+            self.super_block.free_inode = self.inode_bitmap.free_inodes;
+            self.super_block.free_blocks = self.data_bitmap.free_blocks;
+            self.super_block.dirty = true;
+            report.repaired = true;
+        }
+        report
+    }
+
+    fn check_entries(
+        &self,
+        dir_id: u64,
+        path: &str,
+        visited: &mut HashSet<u64>,
+        dangling: &mut Vec<String>,
+    ) {
+        if !visited.insert(dir_id) {
+            return;
+        }
+        let Ok(entries) = self.dir_entries(dir_id) else {
+            return;
+        };
+
+        for entry in entries {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            let child_path = join_path(path, &entry.name);
+            let child_id = entry.inode_index as u64;
+            let alive = self.inode_bitmap.is_used(child_id)
+                && self
+                    .inode_table
+                    .get_inode(child_id)
+                    .is_some_and(|inode| !inode.is_free());
+
+            if !alive {
+                dangling.push(child_path);
+            } else if self
+                .inode_table
+                .get_inode(child_id)
+                .is_some_and(|inode| inode.inode_type == InodeType::Directory)
+            {
+                self.check_entries(child_id, &child_path, visited, dangling);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::{inode_table::Inode, tests::test_fs};
+
+    #[test]
+    fn test_fsck_detects_and_repairs_drift() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "d").unwrap();
+        fs.create_or_write_file("/d", "a.txt", b"hello").unwrap();
+        fs.create_or_write_file("/", "b.txt", b"world").unwrap();
+        assert!(fs.fsck(false).is_clean());
+
+        // 人为制造几类不一致
+        fs.super_block.free_blocks += 7;
+        let a_id = fs.find_inode("/d/a.txt").unwrap();
+        fs.inode_table.inodes[a_id as usize] = Inode::empty();
+        let b_id = fs.find_inode("/b.txt").unwrap();
+        let b_block = fs.inode_table.inodes[b_id as usize].direct_blocks[0];
+        fs.data_bitmap.free(b_block);
+
+        let report = fs.fsck(false);
+        assert!(!report.is_clean());
+        assert_eq!(report.free_blocks.0, report.free_blocks.1 + 7 - 1);
+        assert!(report.inode_mismatches.contains(&a_id));
+        assert!(report.unmarked_blocks.contains(&(b_id, b_block)));
+        assert_eq!(report.dangling_entries, vec!["/d/a.txt".to_string()]);
+        assert!(!report.leaked_blocks.is_empty());
+        assert!(!report.repaired);
+
+        let report = fs.fsck(true);
+        assert!(report.repaired);
+        assert_eq!(fs.super_block.free_blocks, fs.data_bitmap.free_blocks);
+        assert_eq!(fs.super_block.free_inode, fs.inode_bitmap.free_inodes);
+        assert!(fs.fsck(false).counters_match());
+    }
+}
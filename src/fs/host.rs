@@ -0,0 +1,138 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::fs::{error::FileSystemError, FileHandle, FileSystem, OpenFlags};
+
+// 与宿主机之间拷贝时每次搬运的字节数，大文件不会整体读入内存
+const HOST_CHUNK: usize = 64 * 1024;
+
+impl FileSystem {
+    /// 把宿主机上的文件导入到 `dst`（已存在则覆盖），分块写入，返回写入的字节数。
+    /// 保留宿主机文件的访问和修改时间；被中断时保留已写入的部分并同步
+    pub fn import_host_file(&mut self, host_path: &Path, dst: &str) -> Result<u64, String> {
+        let mut src = File::open(host_path)
+            .map_err(|e| format!("Cannot open {}: {}", host_path.display(), e))?;
+
+        let mut fh = self.open(dst, OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNC)?;
+        let copied = self.copy_from_host(&mut src, host_path, &mut fh, dst);
+        self.close(fh)?;
+        let total = copied?;
+
+        let meta = src
+            .metadata()
+            .map_err(|e| format!("Cannot stat {}: {}", host_path.display(), e))?;
+        if let Some(mtime) = unix_millis(meta.modified()) {
+            let atime = unix_millis(meta.accessed()).unwrap_or(mtime);
+            self.set_times(dst, atime, mtime)?;
+        }
+        Ok(total)
+    }
+
+    // 辅助方法：把宿主机文件分块写进已打开的句柄，返回写入的字节数
+    fn copy_from_host(
+        &mut self,
+        src: &mut File,
+        host_path: &Path,
+        fh: &mut FileHandle,
+        dst: &str,
+    ) -> Result<u64, String> {
+        let mut buf = vec![0u8; HOST_CHUNK];
+        let mut total = 0u64;
+        loop {
+            if self.take_interrupt() {
+                self.sync().map_err(|e| e.to_string())?;
+                return Err(FileSystemError::Interrupted(format!(
+                    "imported {} bytes into {}",
+                    total, dst
+                ))
+                .to_string());
+            }
+            let n = src
+                .read(&mut buf)
+                .map_err(|e| format!("Read {} failed: {}", host_path.display(), e))?;
+            if n == 0 {
+                return Ok(total);
+            }
+            self.write_at(fh, &buf[..n])?;
+            total += n as u64;
+        }
+    }
+
+    /// 把 `src` 导出为宿主机上的文件（已存在则覆盖），分块读取，返回写出的字节数
+    pub fn export_to_host(&self, src: &str, host_path: &Path) -> Result<u64, String> {
+        let inode_id = self.find_inode(src)?;
+        if !self.is_file(src) {
+            return Err(format!("Not a regular file: {}", src));
+        }
+
+        let mut dst = File::create(host_path)
+            .map_err(|e| format!("Cannot create {}: {}", host_path.display(), e))?;
+        let mut buf = vec![0u8; HOST_CHUNK];
+        let mut total = 0u64;
+        loop {
+            let n = self.read_chunk(inode_id, total, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            dst.write_all(&buf[..n])
+                .map_err(|e| format!("Write {} failed: {}", host_path.display(), e))?;
+            total += n as u64;
+        }
+        Ok(total)
+    }
+}
+
+// 宿主机时间转为毫秒时间戳；平台不支持或早于 1970 年时返回 None
+fn unix_millis(time: std::io::Result<SystemTime>) -> Option<u64> {
+    time.ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unix_millis;
+    use crate::fs::tests::test_fs;
+
+    #[test]
+    fn test_import_export_round_trip() {
+        let mut fs = test_fs();
+        let dir = std::env::temp_dir();
+        let host_in = dir.join(format!("minifs_import_{}.bin", std::process::id()));
+        let host_out = dir.join(format!("minifs_export_{}.bin", std::process::id()));
+
+        // 跨越多个分块和数据块的内容
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(&host_in, &content).unwrap();
+
+        assert_eq!(
+            fs.import_host_file(&host_in, "/big.bin").unwrap(),
+            content.len() as u64
+        );
+        assert_eq!(fs.read_path("/big.bin").unwrap(), content);
+
+        // 再次导入较短的文件会截断旧内容
+        std::fs::write(&host_in, b"short").unwrap();
+        fs.import_host_file(&host_in, "/big.bin").unwrap();
+        assert_eq!(fs.read_path("/big.bin").unwrap(), b"short");
+
+        // 导入保留宿主机文件的修改时间
+        let host_mtime = std::fs::metadata(&host_in).unwrap().modified().unwrap();
+        assert_eq!(
+            Some(fs.get_attr("/big.bin").unwrap().mtime),
+            unix_millis(Ok(host_mtime))
+        );
+
+        fs.export_to_host("/big.bin", &host_out).unwrap();
+        assert_eq!(std::fs::read(&host_out).unwrap(), b"short");
+        assert!(fs.export_to_host("/", &host_out).is_err());
+
+        std::fs::remove_file(&host_in).unwrap();
+        std::fs::remove_file(&host_out).unwrap();
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::disk::{Block, BlockDevice, FileDisk};
+use crate::disk::{Block, BlockDevice};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InodeBitmap {
@@ -13,7 +13,7 @@ pub struct InodeBitmap {
 impl InodeBitmap {
     // 创建一个新的 inode 位图（所有位清零 = 空闲）
     pub fn new(total_inodes: u64, start_block: u64) -> Self {
-        let byte_len = ((total_inodes + 7) / 8) as usize;
+        let byte_len = total_inodes.div_ceil(8) as usize;
         Self {
             bits: vec![0; byte_len],
             total_inodes,
@@ -74,7 +74,7 @@ impl InodeBitmap {
     }
 
     // 检查 inode 是否被占用
-    pub fn is_used(&mut self, inode_index: u64) -> bool {
+    pub fn is_used(&self, inode_index: u64) -> bool {
         let byte_index = (inode_index / 8) as usize;
         let bit_index = (inode_index % 8) as u8;
         (self.bits[byte_index] & (1 << bit_index)) != 0
@@ -113,9 +113,8 @@ impl InodeBitmap {
     ///    - total_inodes: 16
     ///    - free_inodes: 10
     ///    - start_block: 位图在磁盘上的起始块号
-
-    pub fn load(disk: &mut FileDisk, start_block: u64, total_inodes: u64) -> Self {
-        let size_in_block = ((total_inodes + 8 * 4096 - 1) / (8 * 4096)) as u64;
+    pub fn load(disk: &dyn BlockDevice, start_block: u64, total_inodes: u64) -> Self {
+        let size_in_block = total_inodes.div_ceil(8 * 4096);
         let mut bits = Vec::with_capacity((size_in_block * 4096) as usize);
         let mut block_buf: Block = [0; 4096];
 
@@ -125,7 +124,7 @@ impl InodeBitmap {
         }
 
         // 截掉多余的字节
-        let byte_len = ((total_inodes + 7) / 8) as usize;
+        let byte_len = total_inodes.div_ceil(8) as usize;
         bits.truncate(byte_len);
 
         let free_inodes = total_inodes - bits.iter().map(|b| b.count_ones() as u64).sum::<u64>();
@@ -139,11 +138,12 @@ impl InodeBitmap {
     }
 
     // 将 inode 位图写回磁盘
-    pub fn sync(&self, disk: &mut FileDisk) -> std::io::Result<()> {
+    /// 写回位图，返回写入的块数
+    pub fn sync(&self, disk: &dyn BlockDevice) -> std::io::Result<u64> {
         let mut bits_to_write = self.bits.clone();
 
         // 每块 4KB，不够的用 0 填充
-        let total_blocks = (bits_to_write.len() as u64 + 4096 - 1) / 4096;
+        let total_blocks = (bits_to_write.len() as u64).div_ceil(4096);
 
         bits_to_write.resize((total_blocks * 4096) as usize, 0);
 
@@ -155,6 +155,6 @@ impl InodeBitmap {
             disk.write_block(self.start_block + i, &block_buf)?;
         }
 
-        Ok(())
+        Ok(total_blocks)
     }
 }
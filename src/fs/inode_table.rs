@@ -1,12 +1,28 @@
 use crate::{
-    disk::{BlockDevice, FileDisk},
+    disk::{BlockDevice, BLOCK_SIZE},
     fs::inode_bitmap::InodeBitmap,
     utils::{current_timestamp, generate_uuid},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
 
 pub const DIRECT_PTRS: usize = 12;
-pub const PTRS_PER_BLOCK: usize = 1024;
+// 一级间接块中可容纳的块号数量（每个块号 8 字节）
+pub const PTRS_PER_BLOCK: usize = BLOCK_SIZE / std::mem::size_of::<u64>();
+
+/// 把间接块的原始字节解析为块号数组（小端序，0 表示空槽）
+pub fn decode_ptrs(block: &[u8]) -> Vec<u64> {
+    block
+        .chunks_exact(8)
+        .take(PTRS_PER_BLOCK)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .collect()
+}
+
+/// 把块号数组编码为间接块字节
+pub fn encode_ptrs(ptrs: &[u64]) -> Vec<u8> {
+    ptrs.iter().flat_map(|p| p.to_le_bytes()).collect()
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum InodeType {
@@ -15,21 +31,48 @@ pub enum InodeType {
     Symlink,
 }
 
+impl InodeType {
+    /// `ls -l` 模式串的首字符：目录 d、符号链接 l、普通文件 -
+    pub fn mode_char(&self) -> char {
+        match self {
+            InodeType::Directory => 'd',
+            InodeType::Symlink => 'l',
+            InodeType::File => '-',
+        }
+    }
+}
+
+// 磁盘上每个 inode 占一个定长槽位，序列化结果不足的部分补 0，
+// 这样第 i 个 inode 的位置可以直接算出来
+pub const INODE_SLOT_SIZE: usize = 256;
+pub const INODES_PER_BLOCK: u64 = (BLOCK_SIZE / INODE_SLOT_SIZE) as u64;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InodeTable {
     pub inodes: Vec<Inode>,
     pub start_block: u64,
     pub total_inodes: u64,
     pub allocated_inodes: u64,
+    #[serde(skip)]
+    dirty: HashSet<u64>, // 自上次 sync 以来改动过的 inode 编号
+    #[serde(skip)]
+    full_sync: bool, // 新建的表需要整体写一遍，覆盖旧镜像残留
 }
 
 impl InodeTable {
+    /// 存放 total_inodes 个 inode 需要的块数
+    pub fn region_blocks(total_inodes: u64) -> u64 {
+        total_inodes.div_ceil(INODES_PER_BLOCK)
+    }
+
     pub fn new(start_block: u64, total_inodes: u64) -> Self {
         Self {
             inodes: vec![Inode::empty(); total_inodes as usize],
             start_block,
             total_inodes,
             allocated_inodes: 0,
+            dirty: HashSet::new(),
+            full_sync: true,
         }
     }
 
@@ -47,6 +90,7 @@ impl InodeTable {
         if let Some(index) = inode_bitmap.alloc() {
             let inode = Inode::new(inode_type, uid, gid, perm);
             self.inodes[index as usize] = inode;
+            self.dirty.insert(index);
             self.allocated_inodes += 1;
             Some(index as usize)
         } else {
@@ -56,6 +100,7 @@ impl InodeTable {
 
     pub fn free_inode(&mut self, inode_bitmap: &mut InodeBitmap, inode_index: u64) {
         self.inodes[inode_index as usize] = Inode::empty();
+        self.dirty.insert(inode_index);
         inode_bitmap.free(inode_index);
         self.allocated_inodes -= 1;
     }
@@ -64,79 +109,113 @@ impl InodeTable {
         self.inodes.get(index as usize)
     }
 
+    /// 可变借用的 inode 一律视为已修改，下次 sync 时写回所在的块
     pub fn get_inode_mut(&mut self, index: u64) -> Option<&mut Inode> {
-        self.inodes.get_mut(index as usize)
-    }
-
-    pub fn sync(&self, disk: &mut FileDisk) -> std::io::Result<()> {
-        let bytes = bincode::serialize(&self.inodes)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        let total_blocks = (bytes.len() as u64 + 8 + 4095) / 4096;
-        let mut block_buf = [0u8; 4096];
-        let len_bytes = (bytes.len() as u64).to_le_bytes();
-        block_buf[..8].copy_from_slice(&len_bytes);
-        let first_chunk = std::cmp::min(4096 - 8, bytes.len());
-        block_buf[8..8 + first_chunk].copy_from_slice(&bytes[..first_chunk]);
-        disk.write_block(self.start_block, &block_buf)?;
-        let mut offset = first_chunk;
-        for i in 1..total_blocks {
-            let mut block_buf = [0u8; 4096];
-            let chunk = std::cmp::min(4096, bytes.len() - offset);
-            block_buf[..chunk].copy_from_slice(&bytes[offset..offset + chunk]);
-            disk.write_block(self.start_block + i, &block_buf)?;
-            offset += chunk;
+        let inode = self.inodes.get_mut(index as usize)?;
+        self.dirty.insert(index);
+        Some(inode)
+    }
+
+    /// 直接改写 `inodes[index]` 后需要手动标记
+    pub fn mark_dirty(&mut self, index: u64) {
+        self.dirty.insert(index);
+    }
+
+    /// 自上次 sync 以来改动过、尚未写回的 inode 数
+    pub fn dirty_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// 第 index 个 inode 所在的块号
+    pub fn block_of(&self, index: u64) -> u64 {
+        self.start_block + index / INODES_PER_BLOCK
+    }
+
+    /// 写回 inode 表，只重写包含已修改 inode 的块，返回写入的块数
+    pub fn sync(&mut self, disk: &dyn BlockDevice) -> std::io::Result<u64> {
+        let region = Self::region_blocks(self.total_inodes);
+        let blocks: BTreeSet<u64> = if self.full_sync {
+            (0..(self.inodes.len() as u64).div_ceil(INODES_PER_BLOCK)).collect()
+        } else {
+            self.dirty.iter().map(|i| i / INODES_PER_BLOCK).collect()
+        };
+        // 超出预留区域会覆盖数据区，宁可报错也不写
+        if let Some(&last) = blocks.last() {
+            if last >= region {
+                return Err(std::io::Error::other(format!(
+                    "inode table needs {} blocks but only {} are reserved",
+                    last + 1,
+                    region
+                )));
+            }
+        }
+
+        for &block in &blocks {
+            let first = (block * INODES_PER_BLOCK) as usize;
+            let last = (first + INODES_PER_BLOCK as usize).min(self.inodes.len());
+            let mut block_buf = [0u8; BLOCK_SIZE];
+            for (slot, inode) in self.inodes[first..last].iter().enumerate() {
+                let bytes = bincode::serialize(inode).map_err(std::io::Error::other)?;
+                if bytes.len() > INODE_SLOT_SIZE {
+                    return Err(std::io::Error::other("inode larger than its slot"));
+                }
+                let offset = slot * INODE_SLOT_SIZE;
+                block_buf[offset..offset + bytes.len()].copy_from_slice(&bytes);
+            }
+            disk.write_block(self.start_block + block, &block_buf)?;
         }
-        Ok(())
-    }
-
-    pub fn load(disk: &mut FileDisk, start_block: u64) -> std::io::Result<Self> {
-        let mut block_buf = [0u8; 4096];
-        disk.read_block(start_block, &mut block_buf)?;
-        let mut len_bytes = [0u8; 8];
-        len_bytes.copy_from_slice(&block_buf[..8]);
-        let serialized_len = u64::from_le_bytes(len_bytes) as usize;
-        let total_blocks = (serialized_len + 8 + 4095) / 4096;
-        let mut bytes = Vec::with_capacity(serialized_len);
-        let first_chunk = std::cmp::min(4096 - 8, serialized_len);
-        bytes.extend_from_slice(&block_buf[8..8 + first_chunk]);
-        let mut read = first_chunk;
-        for i in 1..total_blocks {
-            disk.read_block(start_block + i as u64, &mut block_buf)?;
-            let chunk = std::cmp::min(4096, serialized_len - read);
-            bytes.extend_from_slice(&block_buf[..chunk]);
-            read += chunk;
+
+        self.dirty.clear();
+        self.full_sync = false;
+        Ok(blocks.len() as u64)
+    }
+
+    pub fn load(
+        disk: &dyn BlockDevice,
+        start_block: u64,
+        total_inodes: u64,
+    ) -> std::io::Result<Self> {
+        let mut inodes = Vec::with_capacity(total_inodes as usize);
+        let mut block_buf = [0u8; BLOCK_SIZE];
+        for block in 0..Self::region_blocks(total_inodes) {
+            disk.read_block(start_block + block, &mut block_buf)?;
+            for slot in block_buf.chunks(INODE_SLOT_SIZE) {
+                if inodes.len() as u64 == total_inodes {
+                    break;
+                }
+                // 全 0 的槽位恰好解码为空闲 inode
+                let inode: Inode = bincode::deserialize(slot).map_err(std::io::Error::other)?;
+                inodes.push(inode);
+            }
         }
-        let inodes: Vec<Inode> = bincode::deserialize(&bytes)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        let total_inodes = inodes.len() as u64;
-        let allocated_inodes = inodes
-            .iter()
-            .filter(|inode| inode.id != Inode::empty().id)
-            .count() as u64;
+        let allocated_inodes = inodes.iter().filter(|inode| !inode.is_free()).count() as u64;
         Ok(Self {
             inodes,
             start_block,
             total_inodes,
             allocated_inodes,
+            dirty: HashSet::new(),
+            full_sync: false,
         })
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct Inode {
-    pub id: String,
+    pub id: [u8; 16], // 随机 UUID，全 0 表示空闲 inode
     pub inode_type: InodeType,
     pub size: u64,
     pub permissions: u16,
     pub uid: u32,
     pub gid: u32,
     pub link_count: u32,
-    pub atime: u64,
+    pub atime: u64, // 时间戳都是自 1970 年起的毫秒数
     pub mtime: u64,
     pub ctime: u64,
     pub direct_blocks: [u64; DIRECT_PTRS],
     pub indirect_block: Option<u64>,
     pub double_indirect_block: Option<u64>,
+    pub xattr_block: Option<u64>, // 扩展属性表所在的数据块，见 fs::xattr
 }
 
 impl Inode {
@@ -155,12 +234,13 @@ impl Inode {
             direct_blocks: [0; DIRECT_PTRS],
             indirect_block: None,
             double_indirect_block: None,
+            xattr_block: None,
         }
     }
 
     pub fn empty() -> Self {
         Self {
-            id: String::new(),
+            id: [0; 16],
             inode_type: InodeType::File,
             size: 0,
             permissions: 0,
@@ -173,15 +253,33 @@ impl Inode {
             direct_blocks: [0; DIRECT_PTRS],
             indirect_block: None,
             double_indirect_block: None,
+            xattr_block: None,
         }
     }
 
+    /// 未分配的槽位 id 为全 0
+    pub fn is_free(&self) -> bool {
+        self.id == [0; 16]
+    }
+
+    /// 单个 inode 序列化后的最大字节数（所有间接指针都存在时）
+    pub fn max_serialized_size() -> u64 {
+        let largest = Inode {
+            indirect_block: Some(0),
+            double_indirect_block: Some(0),
+            xattr_block: Some(0),
+            ..Inode::new(InodeType::File, 0, 0, 0)
+        };
+        bincode::serialized_size(&largest).expect("inode is always serializable")
+    }
+
     pub fn touch(&mut self) {
         self.atime = current_timestamp();
         self.mtime = current_timestamp();
         self.ctime = current_timestamp();
     }
 
+    // 挂载到第一个空闲的直接指针；直接指针用完后由 FileSystem 负责间接块
     pub fn add_block(&mut self, block_id: u64) -> Result<(), String> {
         for ptr in self.direct_blocks.iter_mut() {
             if *ptr == 0 {
@@ -189,22 +287,6 @@ impl Inode {
                 return Ok(());
             }
         }
-        if self.indirect_block.is_none() {
-            self.indirect_block = Some(block_id);
-            Ok(())
-        } else {
-            Err("No space in inode block pointers".to_string())
-        }
-    }
-
-    pub fn block_count(&self) -> u64 {
-        let mut count = self.direct_blocks.iter().filter(|&&b| b != 0).count() as u64;
-        if self.indirect_block.is_some() {
-            count += 1;
-        }
-        if self.double_indirect_block.is_some() {
-            count += 1;
-        }
-        count
+        Err("No space in direct block pointers".to_string())
     }
 }
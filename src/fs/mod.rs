@@ -1,24 +1,44 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{SeekFrom, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
 use crate::{
-    disk::{BlockDevice, FileDisk},
+    disk::{types::BLOCK_COUNT, BlockDevice, FileDisk, MemDisk, BLOCK_SIZE},
     fs::{
         data_area::DataArea,
-        data_block_bitmap::DataBlockBitmap,
+        data_block_bitmap::{DataBlockBitmap, RESERVED_BLOCK},
         directory::{DirEntry, DirEntryType, Directory},
+        error::FileSystemError,
         inode_bitmap::InodeBitmap,
-        inode_table::{Inode, InodeTable, InodeType},
-        super_block::SuperBlock,
+        inode_table::{decode_ptrs, encode_ptrs, Inode, InodeTable, InodeType, DIRECT_PTRS},
+        read_dir::ReadDir,
+        super_block::{SuperBlock, CURRENT_VERSION, DEFAULT_INODES, VERSION_MILLIS_TIMESTAMPS},
+        walk::Walk,
     },
-    utils::{current_timestamp, split_path},
+    utils::{current_timestamp, glob_match, join_path, normalize_path, split_path},
 };
 
 pub mod config;
 pub mod data_area;
 pub mod data_block_bitmap;
+pub mod defrag;
 pub mod directory;
 pub mod error;
+pub mod fsck;
+pub mod host;
 pub mod inode_bitmap;
 pub mod inode_table;
+pub mod read_dir;
+pub mod snapshot;
 pub mod super_block;
+pub mod walk;
+pub mod xattr;
 
 bitflags::bitflags! {
     #[derive(Debug)]
@@ -26,11 +46,28 @@ bitflags::bitflags! {
         const READ   = 0b0001;
         const WRITE  = 0b0010;
         const CREATE = 0b0100;
+        /// 打开已有文件时清空内容；必须与 WRITE 同时使用，单独使用时 open 报错。
+        /// 只在类型和权限检查都通过后才截断，失败的 open 不改动文件
         const TRUNC  = 0b1000;
         const APPEND = 0b1_0000;
     }
 }
 
+/// `create_or_write_file` 的结果：新建了文件，还是覆盖了已有文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    Created(u64),
+    Overwrote(u64),
+}
+
+impl WriteOutcome {
+    pub fn inode_id(&self) -> u64 {
+        match *self {
+            WriteOutcome::Created(id) | WriteOutcome::Overwrote(id) => id,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct FileHandle {
     pub inode_id: u64,
@@ -38,20 +75,169 @@ pub struct FileHandle {
     pub flags: OpenFlags,
 }
 
+/// 目录树遍历允许的最大深度
+pub const MAX_WALK_DEPTH: usize = 256;
+
+// 单组 rwx 权限位，按属主 / 属组 / 其他人各占三位
+const PERM_READ: u16 = 0o4;
+const PERM_WRITE: u16 = 0o2;
+const PERM_EXEC: u16 = 0o1;
+
+// alloc_block 找不到空闲块时的错误信息
+const NO_FREE_BLOCKS: &str = "No free data blocks";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DepthReport {
+    pub max_depth: usize,     // 目录最大嵌套层数，根目录为 0
+    pub longest_path: String, // 最长的完整路径
+}
+
+/// 列目录的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortBy {
+    #[default]
+    Name, // 目录在前，同类按名字（list_dir 的默认顺序）
+    Size,  // 大小从大到小（ls -S）
+    Mtime, // 修改时间从新到旧（ls -t）
+    Type,  // 按目录、文件、符号链接分组，组内按名字
+}
+
+/// 格式化选项
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    pub fill_byte: Option<u8>, // 数据区初始化填充字节，None 表示保持全零
+    pub verify: bool,          // 写入后回读校验超级块和根目录块
+    pub total_inodes: u64,     // inode 总数
+    pub total_blocks: u64,     // 文件系统使用的总块数（不超过磁盘容量）
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            fill_byte: None,
+            verify: true,
+            total_inodes: DEFAULT_INODES,
+            total_blocks: BLOCK_COUNT as u64,
+        }
+    }
+}
+
+/// 目录数据块的原始内容（按存储顺序，不排序不过滤）
+#[derive(Debug)]
+pub struct DirBlockDump {
+    pub block_id: u64,          // 数据块编号
+    pub byte_len: u64,          // 目录序列化后的字节数
+    pub entries: Vec<DirEntry>, // 原始目录项
+}
+
+/// 两个镜像的差异
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImageDiff {
+    pub only_in_a: Vec<String>, // 只在 A 中存在的路径
+    pub only_in_b: Vec<String>, // 只在 B 中存在的路径
+    pub changed: Vec<String>,   // 两边都有但内容或元数据不同的路径
+}
+
+impl ImageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// 内存占用估算（字节）
+#[derive(Debug, PartialEq, Eq)]
+pub struct Footprint {
+    pub data_area: usize,    // data_area 中尚未写回的脏块
+    pub inode_table: usize,  // inode_table.inodes（含 id 字符串堆内存）
+    pub inode_bitmap: usize, // inode 位图
+    pub data_bitmap: usize,  // 数据块位图
+}
+
+impl Footprint {
+    pub fn total(&self) -> usize {
+        self.data_area + self.inode_table + self.inode_bitmap + self.data_bitmap
+    }
+}
+
+/// 一次 sync 写到磁盘的量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncStats {
+    pub blocks: u64, // 写入的块数（含超级块、位图、inode 表和数据块）
+    pub bytes: u64,  // 对应的字节数
+}
+
+/// 文件系统容量统计（df），数值直接取自超级块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatFs {
+    pub block_size: u64,
+    pub total_blocks: u64,    // 整块磁盘的块数（含元数据区）
+    pub free_blocks: u64,     // 数据区中尚未分配的块
+    pub metadata_blocks: u64, // 超级块、位图和 inode 表占用的块
+    pub total_inodes: u64,
+    pub free_inodes: u64,
+}
+
+impl StatFs {
+    pub fn used_blocks(&self) -> u64 {
+        self.total_blocks - self.free_blocks
+    }
+
+    pub fn used_inodes(&self) -> u64 {
+        self.total_inodes - self.free_inodes
+    }
+}
+
+/// 单个 inode 的元数据（getattr），不含块指针
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileAttr {
+    pub inode_id: u64,
+    pub inode_type: InodeType,
+    pub size: u64,
+    pub blocks: u64, // 实际占用的块数（含间接块）
+    pub permissions: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub link_count: u32,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+}
+
+/// verbose 过程信息的输出目标，默认是标准输出
+struct LogSink(Mutex<Box<dyn Write + Send>>);
+
+impl std::fmt::Debug for LogSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LogSink")
+    }
+}
+
 #[derive(Debug)]
 pub struct FileSystem {
-    pub disk: FileDisk,               // 底层磁盘抽象层
+    pub disk: Arc<dyn BlockDevice>,   // 底层块设备（与 DataArea 共享）
     pub super_block: SuperBlock,      // 文件系统总体信息
     pub inode_bitmap: InodeBitmap,    // inode 分配信息
     pub data_bitmap: DataBlockBitmap, // 数据块分配信息
     pub inode_table: InodeTable,      // 所有 inode 管理
     pub data_area: DataArea,          // 所有数据块内容管理
+    pub mount_time: Option<Duration>, // 最近一次 mount 的耗时
+    pub noatime: bool,                // 为 true 时读取/列目录不更新 atime
+    pub checksums: bool,              // 为 true 时数据块带 CRC32 校验，须与格式化时一致
+    pub current_uid: u32,             // 新建文件/目录的属主
+    pub current_gid: u32,             // 新建文件/目录的属组
+    pub read_only: bool,              // 为 true 时拒绝一切修改（只读挂载）
+    pub verbose: bool,                // 为 true 时打印格式化、建目录等过程信息；库调用默认静默
+    pub interrupt: Arc<AtomicBool>,   // 置位后长操作在下一步之前停下并 sync（shell 的 Ctrl-C 设置）
+    image_path: Option<String>,       // 镜像文件路径，快照存放在它旁边；内存磁盘为 None
+    open_counts: HashMap<u64, u32>,   // 每个 inode 上尚未 close 的句柄数
+    log_sink: LogSink,                // log() 的输出目标
 }
 
 impl FileSystem {
     /// 创建新的文件系统实例  
-    pub fn new(disk: FileDisk) -> Self {
-        let super_block = SuperBlock::new(4096);
+    pub fn new(disk: impl BlockDevice + 'static) -> Self {
+        let disk: Arc<dyn BlockDevice> = Arc::new(disk);
+        let super_block = SuperBlock::default();
 
         let inode_bitmap =
             InodeBitmap::new(super_block.total_inodes, super_block.inode_bitmap_start);
@@ -64,6 +250,7 @@ impl FileSystem {
         let inode_table = InodeTable::new(super_block.inode_table_start, super_block.total_inodes);
 
         let data_area = DataArea::new(
+            Arc::clone(&disk),
             super_block.data_block_start,
             super_block.total_blocks - super_block.data_block_start,
         );
@@ -75,44 +262,318 @@ impl FileSystem {
             data_bitmap,
             inode_table,
             data_area,
+            mount_time: None,
+            noatime: false,
+            checksums: false,
+            current_uid: 0,
+            current_gid: 0,
+            read_only: false,
+            verbose: false,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            image_path: None,
+            open_counts: HashMap::new(),
+            log_sink: LogSink(Mutex::new(Box::new(std::io::stdout()))),
+        }
+    }
+
+    /// 把 verbose 过程信息改写到 `sink`（默认是标准输出）
+    pub fn set_log_sink(&mut self, sink: impl Write + Send + 'static) {
+        self.log_sink = LogSink(Mutex::new(Box::new(sink)));
+    }
+
+    /// 在内存磁盘上创建并格式化一个文件系统，不读写任何宿主文件（主要用于测试）
+    pub fn new_in_memory() -> Result<Self, std::io::Error> {
+        let mut fs = Self::new(MemDisk::new());
+        fs.format()?;
+        Ok(fs)
+    }
+
+    /// 在镜像文件上创建文件系统实例（文件不存在时创建，不足 DISK_SIZE 时扩展），不挂载。
+    /// 记下镜像路径供快照使用，凡是基于镜像文件的实例都应通过这里创建
+    pub fn with_image_file(
+        path: &str,
+        tx: &std::sync::mpsc::Sender<crate::shell::BootProgress>,
+    ) -> Result<Self, std::io::Error> {
+        let mut fs = Self::new(FileDisk::new(path, tx)?);
+        fs.image_path = Some(path.to_string());
+        Ok(fs)
+    }
+
+    /// 镜像文件路径；内存磁盘等非文件设备为 None
+    pub fn image_path(&self) -> Option<&str> {
+        self.image_path.as_deref()
+    }
+
+    /// 打开并挂载一个已存在的镜像文件（不会创建新镜像）
+    pub fn open_image(path: &str) -> Result<Self, std::io::Error> {
+        if !std::path::Path::new(path).exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Disk image not found: {}", path),
+            ));
         }
+
+        // FileDisk 会汇报进度，这里没有 UI，接收端只需保持存活
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let mut fs = Self::with_image_file(path, &tx)?;
+        fs.mount()?;
+        Ok(fs)
+    }
+
+    /// 以只读方式打开并挂载一个已存在的镜像：不扩展文件大小，也不写入任何内容
+    pub fn open_image_readonly(path: &str) -> Result<Self, std::io::Error> {
+        let disk = FileDisk::open_readonly(path)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        let mut fs = Self::new(disk);
+        fs.mount_readonly()?;
+        Ok(fs)
     }
 
     /// 挂载文件系统：从磁盘加载所有组件  
     pub fn mount(&mut self) -> Result<(), std::io::Error> {
-        let mut block_buf = [0u8; 4096];
-        self.disk.read_block(0, &mut block_buf)?;
-
-        self.super_block = bincode::deserialize(&block_buf)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let started = Instant::now();
+        let super_block = self.read_super_block()?;
+        super_block
+            .validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        // 镜像被截短时超级块里的各区域偏移已不可信，先于加载任何组件拒绝挂载
+        let available = self.disk.block_count()?;
+        if available < super_block.total_blocks {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                FileSystemError::Corrupted(format!(
+                    "disk image has {} blocks but the super block expects {}",
+                    available, super_block.total_blocks
+                )),
+            ));
+        }
+        self.super_block = super_block;
 
         // 加载各个组件
         self.inode_bitmap = InodeBitmap::load(
-            &mut self.disk,
+            &self.disk,
             self.super_block.inode_bitmap_start,
             self.super_block.total_inodes,
         );
 
         self.data_bitmap = DataBlockBitmap::load(
-            &mut self.disk,
+            &self.disk,
             self.super_block.block_bitmap_start,
             self.super_block.total_blocks - self.super_block.data_block_start,
         );
 
-        self.inode_table = InodeTable::load(&mut self.disk, self.super_block.inode_table_start)?;
+        self.inode_table = InodeTable::load(
+            &self.disk,
+            self.super_block.inode_table_start,
+            self.super_block.total_inodes,
+        )?;
+        if self.super_block.version < VERSION_MILLIS_TIMESTAMPS {
+            self.upgrade_timestamps();
+        }
 
-        self.data_area.load(&mut self.disk)?;
+        // 数据块按需从磁盘读取，挂载时只需丢弃旧的缓存
+        self.data_area = DataArea::new(
+            Arc::clone(&self.disk),
+            self.super_block.data_block_start,
+            self.super_block.total_blocks - self.super_block.data_block_start,
+        )
+        .with_checksums(self.checksums);
 
         self.super_block.mounted = true;
+        // 刚从磁盘读出，内存与磁盘一致（落盘时超级块里的 dirty 仍是 true）
+        self.super_block.dirty = false;
+
+        // 上次没有正常卸载（崩溃或被杀）时，已删除但当时仍打开的 inode 还留在镜像里，
+        // 此时不可能再有句柄指向它们，直接回收；只改内存，下次 sync 时落盘
+        if !self.read_only {
+            let reclaimed = self
+                .reclaim_orphans()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            if !reclaimed.is_empty() {
+                self.log(format_args!("Reclaimed orphan inodes: {:?}", reclaimed));
+            }
+        }
+        self.mount_time = Some(started.elapsed());
+        Ok(())
+    }
+
+    // 辅助方法：读出磁盘上的超级块（不校验）
+    fn read_super_block(&self) -> Result<SuperBlock, std::io::Error> {
+        let mut block_buf = [0u8; 4096];
+        self.disk.read_block(0, &mut block_buf)?;
+        bincode::deserialize(&block_buf).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                FileSystemError::Corrupted(format!("super block unreadable: {}", e)),
+            )
+        })
+    }
+
+    /// 把已挂载的老版本镜像就地升级到当前格式版本并立即落盘。
+    /// 返回磁盘上原来的版本号；已经是当前版本时返回 None，什么也不写。
+    /// 老镜像挂载时各组件已在内存中升级，这里只需把它们完整写回
+    pub fn migrate(&mut self) -> Result<Option<u32>, std::io::Error> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let on_disk = self.read_super_block()?.version;
+        if on_disk >= CURRENT_VERSION {
+            return Ok(None);
+        }
+        self.log(format_args!(
+            "Migrating image from format version {} to {}",
+            on_disk, CURRENT_VERSION
+        ));
+        self.super_block.dirty = true;
+        self.sync()?;
+        Ok(Some(on_disk))
+    }
+
+    // 辅助方法：老镜像的时间戳以秒为单位，读入后换算成毫秒。
+    // 只改内存并标记 inode 待写回，下次 sync（或 migrate）时连同新的版本号一起落盘；不 sync 则磁盘保持原样
+    fn upgrade_timestamps(&mut self) {
+        for id in 0..self.inode_table.inodes.len() {
+            let inode = &mut self.inode_table.inodes[id];
+            if inode.is_free() {
+                continue;
+            }
+            for ts in [&mut inode.atime, &mut inode.mtime, &mut inode.ctime] {
+                *ts = ts.saturating_mul(1000);
+            }
+            self.inode_table.mark_dirty(id as u64);
+        }
+        self.super_block.version = VERSION_MILLIS_TIMESTAMPS;
+    }
+
+    /// 以只读方式挂载：之后所有修改操作都返回 PermissionDenied，适合检查可能已损坏的镜像
+    pub fn mount_readonly(&mut self) -> Result<(), std::io::Error> {
+        // 先置只读，mount 就不会回收孤儿 inode 之类改动内存状态
+        let was_read_only = self.read_only;
+        self.read_only = true;
+        let result = self.mount();
+        if result.is_err() {
+            self.read_only = was_read_only;
+        }
+        result
+    }
+
+    // 辅助方法：只读挂载时拒绝修改
+    fn ensure_writable(&self) -> Result<(), String> {
+        if self.read_only {
+            return Err(FileSystemError::Io(read_only_error()).to_string());
+        }
         Ok(())
     }
 
+    /// 以 `cwd` 为当前目录把 `path` 规范化为绝对路径：处理 `.`、`..`、
+    /// 绝对/相对路径和重复的 `/`。shell 的所有路径参数都经过这里
+    pub fn resolve(&self, cwd: &str, path: &str) -> String {
+        normalize_path(cwd, path)
+    }
+
+    /// 从超级块汇总容量信息
+    pub fn statfs(&self) -> StatFs {
+        StatFs {
+            block_size: self.super_block.block_size,
+            total_blocks: self.super_block.total_blocks,
+            free_blocks: self.super_block.free_blocks,
+            metadata_blocks: self.super_block.data_block_start,
+            total_inodes: self.super_block.total_inodes,
+            free_inodes: self.super_block.free_inode,
+        }
+    }
+
+    /// 统计子树实际占用的磁盘字节数（du）；硬链接指向的同一 inode 只计一次
+    pub fn disk_usage(&self, path: &str) -> Result<u64, String> {
+        let inode_id = self.find_inode(path)?;
+        self.usage_of(inode_id, &mut HashSet::new())
+    }
+
+    fn usage_of(&self, inode_id: u64, seen: &mut HashSet<u64>) -> Result<u64, String> {
+        if !seen.insert(inode_id) {
+            return Ok(0);
+        }
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("Inode not found")?;
+        let mut total = self.block_count(inode)? * BLOCK_SIZE as u64;
+
+        if inode.inode_type == InodeType::Directory {
+            for entry in ReadDir::new(self, inode_id)? {
+                let entry = entry?;
+                if entry.name != "." && entry.name != ".." {
+                    total += self.usage_of(entry.inode_index as u64, seen)?;
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    /// 估算各组件常驻内存的大小
+    pub fn footprint(&self) -> Footprint {
+        let inode_table = self.inode_table.inodes.len() * std::mem::size_of::<Inode>();
+
+        Footprint {
+            data_area: self.data_area.cached_bytes(),
+            inode_table,
+            inode_bitmap: self.inode_bitmap.bits.len(),
+            data_bitmap: self.data_bitmap.bits.len(),
+        }
+    }
+
+    /// 调试用：直接从磁盘读出一个块（绝对块号）的原始字节，
+    /// 不经过数据区缓存，也不做校验；块号超出镜像范围或读取失败时返回 None
+    pub fn dump_block(&self, absolute_block: u64) -> Option<Vec<u8>> {
+        if absolute_block >= self.super_block.total_blocks {
+            return None;
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.disk.read_block(absolute_block, &mut buf).ok()?;
+        Some(buf.to_vec())
+    }
+
+    /// 内存中是否有尚未写回磁盘的修改：超级块（位图的改动总伴随计数变化，会标记超级块）、
+    /// inode 表或数据区；为 true 时 dump_block 读到的可能是旧内容
+    pub fn has_unsynced_changes(&self) -> bool {
+        self.super_block.dirty
+            || self.inode_table.dirty_count() > 0
+            || self.data_area.dirty_count() > 0
+    }
+
+    /// 绝对块号所在的磁盘区域，只有 "data area" 以外的都是元数据
+    pub fn block_region(&self, absolute_block: u64) -> &'static str {
+        let sb = &self.super_block;
+        if absolute_block < sb.inode_bitmap_start {
+            "superblock"
+        } else if absolute_block < sb.block_bitmap_start {
+            "inode bitmap"
+        } else if absolute_block < sb.inode_table_start {
+            "data bitmap"
+        } else if absolute_block < sb.data_block_start {
+            "inode table"
+        } else {
+            "data area"
+        }
+    }
+
     /// 格式化文件系统
     pub fn format(&mut self) -> Result<(), std::io::Error> {
-        println!("💾 Formatting virtual disk...");
+        self.format_with(&FormatOptions::default())
+    }
 
-        // 初始化 super_block、位图、inode_table、data_area
-        self.super_block = SuperBlock::new(4096);
+    /// 按指定选项格式化文件系统
+    pub fn format_with(&mut self, options: &FormatOptions) -> Result<(), std::io::Error> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        self.log(format_args!("💾 Formatting virtual disk..."));
+        // 旧镜像上的句柄全部失效
+        self.open_counts.clear();
+
+        // 初始化 super_block、位图、inode_table、data_area；布局不合法时不动现有数据
+        self.super_block = SuperBlock::new(options.total_inodes, options.total_blocks)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
         self.super_block.mounted = true;
         self.super_block.dirty = true;
 
@@ -132,31 +593,39 @@ impl FileSystem {
         );
 
         self.data_area = DataArea::new(
+            Arc::clone(&self.disk),
             self.super_block.data_block_start,
             self.super_block.total_blocks - self.super_block.data_block_start,
-        );
+        )
+        .with_checksums(self.checksums);
+        // 填充被中断时照常写好根目录并同步，得到一个只填充了一部分的新文件系统
+        let mut filled = None;
+        if let Some(byte) = options.fill_byte {
+            let done = self.data_area.fill(byte, &self.interrupt)?;
+            if self.take_interrupt() {
+                filled = Some(done);
+            }
+        }
 
         // 分配 root inode
         let root_index = 0;
         self.inode_bitmap
             .alloc_specific(root_index)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         self.super_block.free_inode -= 1;
 
-        // 分配 root 数据块
+        // 预留 0 号块作为空指针，再分配 root 数据块（与其他块一样用位图下标）
+        if self.data_bitmap.alloc() != Some(RESERVED_BLOCK) {
+            return Err(std::io::Error::other("Failed to reserve data block 0"));
+        }
+        self.super_block.free_blocks -= 1;
         let root_block = self
             .data_bitmap
             .alloc()
-            .map(|b| b + self.data_area.start_block) // 加上偏移
-            .ok_or_else(|| {
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Failed to allocate block for root",
-                )
-            })?;
+            .ok_or_else(|| std::io::Error::other("Failed to allocate block for root"))?;
 
         self.super_block.free_blocks -= 1;
-        println!("Allocated root block id: {}", root_block);
+        self.log(format_args!("Allocated root block id: {}", root_block));
 
         // 创建 root inode 并挂载数据块
         let inode = Inode::new(InodeType::Directory, 0, 0, 0o755);
@@ -170,102 +639,237 @@ impl FileSystem {
             ..inode
         };
         self.inode_table.inodes[root_index] = inode.clone();
-        println!("Root inode after creation: {:?}", inode);
+        self.inode_table.allocated_inodes += 1;
+        self.log(format_args!("Root inode after creation: {:?}", inode));
 
         // 创建 root 目录结构
         let mut root_dir = Directory::new(root_index);
         root_dir
-            .add(root_index, ".", DirEntryType::Directory)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        root_dir
-            .add(root_index, "..", DirEntryType::Directory)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        let dir_bytes = bincode::serialize(&root_dir)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .add_dot_entries(root_index, root_index)
+            .map_err(std::io::Error::other)?;
+        let dir_bytes = bincode::serialize(&root_dir).map_err(std::io::Error::other)?;
 
         // 写入数据块
         self.data_area
             .write_block(root_block, &dir_bytes)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        println!("Root directory written, size: {} bytes", dir_bytes.len());
+            .map_err(std::io::Error::other)?;
+        self.log(format_args!(
+            "Root directory written, size: {} bytes",
+            dir_bytes.len()
+        ));
 
         // 更新 inode size
         let inode = self.inode_table.get_inode_mut(root_index as u64).unwrap();
         inode.size = dir_bytes.len() as u64;
 
-        println!("Root inode final state: {:?}", inode);
+        self.log(format_args!(
+            "Root inode final state: {:?}",
+            self.inode_table.inodes[root_index]
+        ));
 
         // 同步 inode_table 和 super_block 到磁盘
         self.sync()?;
 
+        if let Some(done) = filled {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                FileSystemError::Interrupted(format!(
+                    "filled {} of {} data blocks",
+                    done, self.data_area.total_blocks
+                ))
+                .to_string(),
+            ));
+        }
+        if options.verify {
+            self.verify_format()?;
+        }
+
+        Ok(())
+    }
+
+    /// 回读校验：确认超级块和根目录块已正确落盘
+    pub fn verify_format(&self) -> Result<(), std::io::Error> {
+        let mut block_buf = [0u8; 4096];
+
+        self.disk.read_block(0, &mut block_buf)?;
+        let on_disk: SuperBlock =
+            bincode::deserialize(&block_buf).map_err(std::io::Error::other)?;
+        if on_disk.magic != self.super_block.magic
+            || on_disk.data_block_start != self.super_block.data_block_start
+        {
+            return Err(std::io::Error::other(
+                "Format verification failed: super block read back differently",
+            ));
+        }
+
+        let root_block = self
+            .inode_table
+            .get_inode(0)
+            .map(|inode| inode.direct_blocks[0])
+            .ok_or_else(|| std::io::Error::other("Root inode not found"))?;
+        self.disk
+            .read_block(self.data_area.start_block + root_block, &mut block_buf)?;
+        let root_dir = Directory::load_from_bytes(&block_buf).map_err(|_| {
+            std::io::Error::other("Format verification failed: root directory unreadable")
+        })?;
+        if root_dir.get(".").is_none() || root_dir.get("..").is_none() {
+            return Err(std::io::Error::other(
+                "Format verification failed: root directory is missing '.' or '..'",
+            ));
+        }
+
         Ok(())
     }
 
     /// 创建目录
     pub fn create_dir(&mut self, parent_path: &str, name: &str) -> Result<u64, String> {
-        println!(
+        self.ensure_writable()?;
+        check_name(name)?;
+        self.log(format_args!(
             "--- Creating directory '{}' under '{}' ---",
             name, parent_path
-        );
+        ));
 
         let parent_inode_id = self.find_inode(parent_path)?;
         let parent_inode = self
             .inode_table
             .get_inode(parent_inode_id)
             .ok_or("Parent inode not found")?;
-        println!("Parent inode before adding entry: {:?}", parent_inode);
+        self.log(format_args!(
+            "Parent inode before adding entry: {:?}",
+            parent_inode
+        ));
+
+        // 空间不足时在分配任何资源之前就失败
+        if self.data_bitmap.free_blocks == 0 {
+            return Err(FileSystemError::DiskFull.to_string());
+        }
 
         // 分配inode
         let inode_id = self
             .inode_table
-            .alloc_inode(&mut self.inode_bitmap, InodeType::Directory, 0, 0, 0o755)
-            .ok_or("Failed to allocate inode")?;
-        println!("Allocated inode_id: {}", inode_id);
+            .alloc_inode(
+                &mut self.inode_bitmap,
+                InodeType::Directory,
+                self.current_uid,
+                self.current_gid,
+                0o755,
+            )
+            .ok_or_else(|| FileSystemError::InodeFull.to_string())?;
+        self.log(format_args!("Allocated inode_id: {}", inode_id));
+
+        // 后续任一步失败都回滚：释放已分配的数据块和 inode
+        if let Err(e) = self.init_dir_inode(inode_id, parent_inode_id, parent_path, name) {
+            self.free_file_blocks(inode_id as u64)?;
+            self.inode_table
+                .free_inode(&mut self.inode_bitmap, inode_id as u64);
+            // 没有空闲块（新目录块或父目录的扩展块）时统一报 DiskFull，其他错误原样返回
+            if e == NO_FREE_BLOCKS {
+                return Err(FileSystemError::DiskFull.to_string());
+            }
+            return Err(e);
+        }
+        self.super_block.free_inode -= 1;
+        self.super_block.dirty = true;
 
+        Ok(inode_id as u64)
+    }
+
+    // 辅助方法：为新目录 inode 写入含 . 和 .. 的目录块，并登记到父目录
+    fn init_dir_inode(
+        &mut self,
+        inode_id: usize,
+        parent_inode_id: u64,
+        parent_path: &str,
+        name: &str,
+    ) -> Result<(), String> {
         // 创建目录结构
         let mut new_dir = Directory::new(inode_id);
-        new_dir.add(inode_id, ".", DirEntryType::Directory).unwrap();
-        new_dir
-            .add(inode_id, "..", DirEntryType::Directory)
-            .unwrap();
-        let dir_bytes = bincode::serialize(&new_dir).unwrap();
+        new_dir.add_dot_entries(inode_id, parent_inode_id as usize)?;
+        let dir_bytes = bincode::serialize(&new_dir).map_err(|e| e.to_string())?;
 
-        // 分配数据块
-        let block_id = self
-            .data_bitmap
-            .alloc()
-            .ok_or("Failed to allocate data block")?;
-        self.super_block.free_blocks -= 1;
-        self.data_area.write_block(block_id, &dir_bytes).unwrap();
+        // 分配数据块并写入
+        let block_id = self.alloc_block(&dir_bytes, RESERVED_BLOCK)?;
 
         // 挂到 inode
-        let inode = self.inode_table.get_inode_mut(inode_id as u64).unwrap();
-        inode.add_block(block_id).unwrap();
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id as u64)
+            .ok_or("Directory inode not found")?;
+        if let Err(e) = inode.add_block(block_id) {
+            self.data_bitmap.free(block_id);
+            self.super_block.free_blocks += 1;
+            return Err(e);
+        }
         inode.size = dir_bytes.len() as u64;
         inode.touch();
-        println!("New directory inode: {:?}", inode);
+        self.log(format_args!(
+            "New directory inode: {:?}",
+            self.inode_table.inodes[inode_id]
+        ));
 
         // 更新父目录
-        self.add_directory_entry(parent_path, name, inode_id, DirEntryType::Directory)?;
-        self.super_block.free_inode -= 1;
-        self.super_block.dirty = true;
+        self.add_directory_entry(parent_path, name, inode_id, DirEntryType::Directory)
+    }
 
-        Ok(inode_id as u64)
+    /// 递归创建目录（mkdir -p）：逐级创建缺失的中间目录，已存在时直接返回其 inode
+    pub fn create_dir_all(&mut self, path: &str) -> Result<u64, String> {
+        let mut current_path = String::from("/");
+        let mut current_inode = 0u64;
+
+        for component in path.split('/').filter(|s| !s.is_empty()) {
+            current_inode = match self.lookup_entry(current_inode, component) {
+                Some(id) => {
+                    let id = id as u64;
+                    let is_dir = self
+                        .inode_table
+                        .get_inode(id)
+                        .is_some_and(|inode| inode.inode_type == InodeType::Directory);
+                    if !is_dir {
+                        return Err(FileSystemError::NotADirectory(join_path(
+                            &current_path,
+                            component,
+                        ))
+                        .to_string());
+                    }
+                    id
+                }
+                None => self.create_dir(&current_path, component)?,
+            };
+            current_path = join_path(&current_path, component);
+        }
+        Ok(current_inode)
     }
 
     /// 创建文件  
     pub fn create_file(&mut self, parent_path: &str, name: &str) -> Result<u64, String> {
-        // 0. 检查文件是否已存在
-        let full_path = format!("{}/{}", parent_path, name);
-        if self.find_inode(&full_path).is_ok() {
+        self.ensure_writable()?;
+        check_name(name)?;
+        // 0. 检查文件是否已存在；父目录在分配 inode 之前解析，找不到时不会泄漏。
+        // 父目录不可查找时报权限错误，而不是当成文件不存在
+        let parent_inode_id = self.find_inode(parent_path)?;
+        let parent_inode = self
+            .inode_table
+            .get_inode(parent_inode_id)
+            .ok_or("Parent inode not found")?;
+        if !self.has_access(parent_inode, PERM_EXEC) {
+            return Err(format!("Permission denied: search before {}", name));
+        }
+        if self.lookup_entry(parent_inode_id, name).is_some() {
             return Err("File already exists".to_string());
         }
 
         // 1. 分配 inode
         let inode_id = self
             .inode_table
-            .alloc_inode(&mut self.inode_bitmap, InodeType::File, 0, 0, 0o644)
-            .ok_or("Failed to allocate inode")?;
+            .alloc_inode(
+                &mut self.inode_bitmap,
+                InodeType::File,
+                self.current_uid,
+                self.current_gid,
+                0o644,
+            )
+            .ok_or_else(|| FileSystemError::InodeFull.to_string())?;
 
         let now = current_timestamp();
 
@@ -277,11 +881,17 @@ impl FileSystem {
             // atime 不动
         }
 
-        // 3. 添加目录项
-        self.add_directory_entry(parent_path, name, inode_id, DirEntryType::File)?;
+        // 3. 添加目录项；失败时回滚 inode，父目录无法扩展时报 DiskFull
+        if let Err(e) = self.add_directory_entry(parent_path, name, inode_id, DirEntryType::File) {
+            self.inode_table
+                .free_inode(&mut self.inode_bitmap, inode_id as u64);
+            if e == NO_FREE_BLOCKS {
+                return Err(FileSystemError::DiskFull.to_string());
+            }
+            return Err(e);
+        }
 
         // 4. 更新父目录 inode
-        let parent_inode_id = self.find_inode(parent_path)?;
         if let Some(parent_inode) = self.inode_table.get_inode_mut(parent_inode_id) {
             parent_inode.mtime = now;
             parent_inode.ctime = now;
@@ -294,122 +904,475 @@ impl FileSystem {
         Ok(inode_id as u64)
     }
 
+    /// touch：文件不存在时创建空文件，存在时只把三个时间戳更新为当前时间
+    pub fn touch(&mut self, path: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        match self.find_inode(path) {
+            Ok(inode_id) => {
+                let inode = self
+                    .inode_table
+                    .get_inode_mut(inode_id)
+                    .ok_or("Inode not found")?;
+                inode.touch();
+                self.super_block.dirty = true;
+                Ok(())
+            }
+            Err(_) => {
+                let (parent, name) = split_path(path)?;
+                self.create_file(parent, name).map(|_| ())
+            }
+        }
+    }
+
+    /// 整体替换文件内容：先把新内容写进新分配的块，全部成功后才释放旧块；
+    /// 中途失败（如空间不足）会回收已分配的新块，文件保持原内容不变。
+    /// 因此覆盖写时磁盘需要同时容纳新旧两份数据
     pub fn write_file(&mut self, path: &str, content: &[u8]) -> Result<(), String> {
+        self.ensure_writable()?;
         let inode_id = self.find_inode(path)?;
         let now = current_timestamp();
 
-        // 1. 回收旧数据块
-        self.free_file_blocks(inode_id)?;
-
-        // 2. 写新数据
-        let mut blocks_used = 0;
-        if !content.is_empty() {
-            let block_id = self.data_bitmap.alloc().ok_or("No free data blocks")?;
+        // 1. 把旧块从 inode 上摘下来暂存，先不释放
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Inode not found")?;
+        let old = inode.clone();
+        inode.direct_blocks = [0; DIRECT_PTRS];
+        inode.indirect_block = None;
+        inode.double_indirect_block = None;
+
+        // 2. 按块切分写入新数据；失败时回滚到旧的块指针
+        if let Err(e) = self.write_new_blocks(inode_id, content) {
+            self.free_file_blocks(inode_id)?;
+            *self
+                .inode_table
+                .get_inode_mut(inode_id)
+                .ok_or("Inode not found")? = old;
+            return Err(e);
+        }
 
-            self.data_area.write_block(block_id, content)?;
+        // 3. 新内容已完整挂上，再释放旧块
+        self.release_blocks(&old)?;
 
-            if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
-                inode.add_block(block_id)?;
-                inode.size = content.len() as u64;
+        if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
+            inode.size = content.len() as u64;
+            if !content.is_empty() {
                 inode.mtime = now;
             }
-
-            blocks_used = 1;
         }
 
-        // 3. ctime 不变（只是内容写）
-        self.super_block.free_blocks -= blocks_used;
+        // 4. ctime 不变（只是内容写）
         self.super_block.dirty = true;
 
         Ok(())
     }
 
+    // 辅助方法：为 content 分配新块并依次挂到 inode 上
+    fn write_new_blocks(&mut self, inode_id: u64, content: &[u8]) -> Result<(), String> {
+        let payload = self.data_area.payload_size();
+        let count = content.len().div_ceil(payload);
+        // 直接块放得下时先整段预留连续的块，找不到再逐块分配
+        let mut reserved = match count {
+            2..=DIRECT_PTRS => self
+                .data_bitmap
+                .alloc_contiguous(count)
+                .map(|start| start..start + count as u64),
+            _ => None,
+        };
+        if reserved.is_some() {
+            self.super_block.free_blocks -= count as u64;
+        }
+
+        let mut hint = RESERVED_BLOCK;
+        for chunk in content.chunks(payload) {
+            // 逐块记账：中途失败时已挂上的块也能被正确释放
+            let (block_id, written) = match reserved.as_mut().and_then(Iterator::next) {
+                Some(block_id) => (block_id, self.data_area.write_block(block_id, chunk)),
+                None => (self.alloc_block(chunk, hint)?, Ok(())),
+            };
+            hint = block_id + 1;
+            if let Err(e) = written.and_then(|_| self.attach_block(inode_id, block_id)) {
+                // 还没挂到 inode 上的块（含预留但还没用到的）要单独归还
+                let unused: Vec<u64> = std::iter::once(block_id)
+                    .chain(reserved.take().into_iter().flatten())
+                    .collect();
+                for &block in &unused {
+                    self.data_bitmap.free(block);
+                }
+                self.super_block.free_blocks += unused.len() as u64;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
     pub fn create_or_write_file(
         &mut self,
         parent_path: &str,
         name: &str,
         content: &[u8],
-    ) -> Result<u64, String> {
+    ) -> Result<WriteOutcome, String> {
+        self.ensure_writable()?;
         let full_path = format!("{}/{}", parent_path, name);
 
         match self.find_inode(&full_path) {
             Ok(inode_id) => {
                 self.write_file(&full_path, content)?;
-                Ok(inode_id)
+                Ok(WriteOutcome::Overwrote(inode_id))
             }
             Err(_) => {
                 let inode_id = self.create_file(parent_path, name)?;
                 self.write_file(&full_path, content)?;
-                Ok(inode_id)
+                Ok(WriteOutcome::Created(inode_id))
             }
         }
     }
 
-    /// 列出目录内容  
-    pub fn list_dir(&self, path: &str) -> Result<Vec<DirEntry>, String> {
+    /// 列出目录内容，目录在前、按名字排序（会更新目录的 atime）
+    pub fn list_dir(&mut self, path: &str) -> Result<Vec<DirEntry>, String> {
+        self.list_dir_sorted(path, SortBy::default(), false)
+    }
+
+    /// 按指定方式排序列出目录内容，`reverse` 为 true 时整体倒序；
+    /// 按大小或时间排序时逐项读取 inode，相同时按名字（会更新目录的 atime）
+    pub fn list_dir_sorted(
+        &mut self,
+        path: &str,
+        sort: SortBy,
+        reverse: bool,
+    ) -> Result<Vec<DirEntry>, String> {
         // 获取目录 inode
         let inode_id = self.find_inode(path)?;
-        let inode = self
-            .inode_table
-            .get_inode(inode_id)
-            .ok_or("Inode not found")?;
-
-        if !matches!(inode.inode_type, InodeType::Directory) {
-            return Err("Not a directory".to_string());
+        let mut result = self.dir_entries(inode_id)?;
+        match sort {
+            SortBy::Name => sort_entries(&mut result),
+            SortBy::Type => result.sort_by(|a, b| {
+                a.entry_type
+                    .type_rank()
+                    .cmp(&b.entry_type.type_rank())
+                    .then_with(|| a.name.cmp(&b.name))
+            }),
+            SortBy::Size | SortBy::Mtime => {
+                let mut keyed = Vec::with_capacity(result.len());
+                for entry in result {
+                    let inode = self
+                        .inode_table
+                        .get_inode(entry.inode_index as u64)
+                        .ok_or_else(|| format!("Dangling directory entry: {}", entry.name))?;
+                    let key = if sort == SortBy::Size {
+                        inode.size
+                    } else {
+                        inode.mtime
+                    };
+                    keyed.push((key, entry));
+                }
+                // 大的（新的）在前
+                keyed.sort_by(|(ka, a), (kb, b)| kb.cmp(ka).then_with(|| a.name.cmp(&b.name)));
+                result = keyed.into_iter().map(|(_, entry)| entry).collect();
+            }
         }
+        if reverse {
+            result.reverse();
+        }
+        self.touch_atime(inode_id);
+        Ok(result)
+    }
 
-        // 读取所有 block，把所有目录项收集起来
-        let mut result = Vec::new();
-
-        for &block_id in &inode.direct_blocks {
-            if block_id == 0 {
-                break;
-            }
+    /// 列出目录项并附带各自的 inode（`ls -l` 用）：目录块只读一遍，按 inode 编号直接取，
+    /// 不再按路径逐个重新查找
+    pub fn list_dir_long(
+        &mut self,
+        path: &str,
+        sort: SortBy,
+        reverse: bool,
+    ) -> Result<Vec<(DirEntry, Inode)>, String> {
+        self.list_dir_sorted(path, sort, reverse)?
+            .into_iter()
+            .map(|entry| {
+                let inode = self
+                    .inode_table
+                    .get_inode(entry.inode_index as u64)
+                    .cloned()
+                    .ok_or_else(|| format!("Dangling directory entry: {}", entry.name))?;
+                Ok((entry, inode))
+            })
+            .collect()
+    }
 
-            if let Some(block_data) = self.data_area.read_block(block_id) {
-                let mut dir: Directory =
-                    bincode::deserialize(block_data).map_err(|_| "Corrupted directory block")?;
+    /// 按存储顺序惰性遍历目录项（含 `.` 和 `..`，不排序、不更新 atime），
+    /// 调用方找到需要的条目后即可停止，不必读完整个目录
+    pub fn read_dir(&self, path: &str) -> Result<ReadDir<'_>, String> {
+        ReadDir::new(self, self.find_inode(path)?)
+    }
 
-                // 必须重建 index_map（因为 skip 了）
-                dir.rebuild_index_map();
+    // 辅助方法：读取并清除中断标志
+    fn take_interrupt(&self) -> bool {
+        self.interrupt.swap(false, Ordering::SeqCst)
+    }
 
-                // 追加目录项
-                result.extend(dir.entries);
-            }
+    // 辅助方法：只在 verbose 模式下输出过程信息
+    fn log(&self, args: std::fmt::Arguments) {
+        if self.verbose {
+            // 过程信息写失败不影响操作本身
+            let mut sink = self.log_sink.0.lock().unwrap();
+            let _ = writeln!(sink, "{}", args);
         }
+    }
 
-        result.sort_by(|a, b| {
-            match (&a.entry_type, &b.entry_type) {
-                (DirEntryType::Directory, DirEntryType::File) => std::cmp::Ordering::Less, // 文件夹在前
-                (DirEntryType::File, DirEntryType::Directory) => std::cmp::Ordering::Greater, // 文件在后
-                _ => a.name.cmp(&b.name), // 同类型按名字排序
+    // 辅助方法：记录一次访问；noatime 或只读挂载时跳过
+    fn touch_atime(&mut self, inode_id: u64) {
+        if self.noatime || self.read_only {
+            return;
+        }
+        if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
+            inode.atime = current_timestamp();
+            self.super_block.dirty = true;
+        }
+    }
+
+    // 辅助方法：读取目录 inode 的所有 block，按存储顺序收集目录项
+    fn dir_entries(&self, inode_id: u64) -> Result<Vec<DirEntry>, String> {
+        ReadDir::new(self, inode_id)?.collect()
+    }
+
+    /// 读取目录的每个数据块，原样返回反序列化后的目录项
+    pub fn dir_dump(&self, path: &str) -> Result<Vec<DirBlockDump>, String> {
+        let inode_id = self.find_inode(path)?;
+
+        let mut dumps = Vec::new();
+        for block_id in self.dir_blocks(inode_id)? {
+            let dir = self.load_dir_block(block_id)?;
+            let byte_len = serialized_len(&dir)?;
+
+            dumps.push(DirBlockDump {
+                block_id,
+                byte_len,
+                entries: dir.entries,
+            });
+        }
+        Ok(dumps)
+    }
+
+    /// 先序遍历 `root` 下的所有路径（不含 `root` 本身和 `.`/`..`），`root` 不存在时报错
+    pub fn walk(&self, root: &str) -> Result<Walk<'_>, String> {
+        Walk::new(self, root)
+    }
+
+    /// 深度优先遍历 `path` 下的目录项，按 `list_dir` 的顺序把 (深度, 目录项) 交给 visitor。
+    /// `path` 的直接子项深度为 0，深度达到 `max_depth` 的目录不再展开。
+    pub fn visit_tree(
+        &self,
+        path: &str,
+        max_depth: usize,
+        visitor: &mut dyn FnMut(usize, &DirEntry),
+    ) -> Result<(), String> {
+        let root = self.find_inode(path)?;
+        let mut visited = HashSet::from([root]);
+        self.visit_dir(root, 0, max_depth, &mut visited, visitor)
+    }
+
+    // 辅助方法：visit_tree 的递归部分，visited 防止目录环
+    fn visit_dir(
+        &self,
+        inode_id: u64,
+        depth: usize,
+        max_depth: usize,
+        visited: &mut HashSet<u64>,
+        visitor: &mut dyn FnMut(usize, &DirEntry),
+    ) -> Result<(), String> {
+        let mut entries = self.dir_entries(inode_id)?;
+        sort_entries(&mut entries);
+
+        for entry in entries {
+            if entry.name == "." || entry.name == ".." {
+                continue;
             }
-        });
+            visitor(depth, &entry);
+
+            let child = entry.inode_index as u64;
+            if entry.entry_type == DirEntryType::Directory
+                && depth + 1 < max_depth
+                && visited.insert(child)
+            {
+                self.visit_dir(child, depth + 1, max_depth, visited, visitor)?;
+            }
+        }
+        Ok(())
+    }
 
-        Ok(result)
+    /// 在 `root` 子树中查找名字匹配通配符 `pattern` 的文件和目录，返回排序后的完整路径
+    pub fn find_by_name(&self, root: &str, pattern: &str) -> Result<Vec<String>, String> {
+        let mut matches: Vec<String> = self
+            .walk(root)?
+            .map(|(path, _)| path)
+            .filter(|path| glob_match(pattern, path.rsplit('/').next().unwrap_or("")))
+            .collect();
+        matches.sort();
+        Ok(matches)
+    }
+
+    /// 反查 inode 对应的完整路径（从根目录深度优先搜索目录项）；没有任何目录项引用时返回 None。
+    /// 有硬链接时返回先找到的那一个。
+    pub fn path_of(&self, inode_id: u64) -> Option<String> {
+        if inode_id == 0 {
+            return Some("/".to_string());
+        }
+        let mut visited = HashSet::from([0u64]);
+        let mut stack = vec![(0u64, String::from("/"))];
+        while let Some((dir_id, dir_path)) = stack.pop() {
+            let Ok(entries) = ReadDir::new(self, dir_id) else {
+                continue;
+            };
+            // 找到目标立即返回，后面的目录块不再读取
+            for entry in entries.filter_map(Result::ok) {
+                if entry.name == "." || entry.name == ".." {
+                    continue;
+                }
+                let child = entry.inode_index as u64;
+                let child_path = join_path(&dir_path, &entry.name);
+                if child == inode_id {
+                    return Some(child_path);
+                }
+                if entry.entry_type == DirEntryType::Directory && visited.insert(child) {
+                    stack.push((child, child_path));
+                }
+            }
+        }
+        None
+    }
+
+    /// 比较两个文件系统：列出只在一侧存在的路径，以及内容或元数据不同的路径
+    pub fn diff(&self, other: &FileSystem) -> ImageDiff {
+        // 根目录总是存在，遍历失败时按空树处理
+        let a: BTreeMap<String, InodeType> = self.walk("/").into_iter().flatten().collect();
+        let b: BTreeMap<String, InodeType> = other.walk("/").into_iter().flatten().collect();
+        let mut diff = ImageDiff::default();
+
+        for path in a.keys() {
+            if !b.contains_key(path) {
+                diff.only_in_a.push(path.clone());
+            } else if !self.same_entry(other, path) {
+                diff.changed.push(path.clone());
+            }
+        }
+        diff.only_in_b = b.keys().filter(|p| !a.contains_key(*p)).cloned().collect();
+
+        diff
+    }
+
+    // 辅助方法：比较两侧同一路径的类型、大小、权限、属主以及文件内容
+    fn same_entry(&self, other: &FileSystem, path: &str) -> bool {
+        let (Ok(id_a), Ok(id_b)) = (self.find_inode(path), other.find_inode(path)) else {
+            return false;
+        };
+        let (Some(inode_a), Some(inode_b)) = (
+            self.inode_table.get_inode(id_a),
+            other.inode_table.get_inode(id_b),
+        ) else {
+            return false;
+        };
+
+        if inode_a.inode_type != inode_b.inode_type
+            || inode_a.permissions != inode_b.permissions
+            || inode_a.uid != inode_b.uid
+            || inode_a.gid != inode_b.gid
+        {
+            return false;
+        }
+        if inode_a.inode_type != InodeType::File {
+            return true;
+        }
+
+        inode_a.size == inode_b.size && self.file_content(id_a) == other.file_content(id_b)
+    }
+
+    /// 统计整棵目录树的最大嵌套深度和最长路径
+    pub fn depth_report(&self) -> Result<DepthReport, String> {
+        let mut report = DepthReport {
+            max_depth: 0,
+            longest_path: "/".to_string(),
+        };
+        self.walk_depth(0, "/", 0, &mut report)?;
+        Ok(report)
+    }
+
+    // 辅助方法：有界递归遍历，超过 MAX_WALK_DEPTH 直接报错，同时防止环
+    fn walk_depth(
+        &self,
+        inode_id: u64,
+        path: &str,
+        depth: usize,
+        report: &mut DepthReport,
+    ) -> Result<(), String> {
+        if depth > MAX_WALK_DEPTH {
+            return Err(format!(
+                "Directory nesting exceeds {} levels at {}",
+                MAX_WALK_DEPTH, path
+            ));
+        }
+        report.max_depth = report.max_depth.max(depth);
+
+        for entry in self.dir_entries(inode_id)? {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+
+            let child_path = join_path(path, &entry.name);
+            if child_path.len() > report.longest_path.len() {
+                report.longest_path = child_path.clone();
+            }
+
+            if entry.entry_type == DirEntryType::Directory {
+                self.walk_depth(entry.inode_index as u64, &child_path, depth + 1, report)?;
+            }
+        }
+        Ok(())
     }
 
     /// 同步所有组件到磁盘  
-    pub fn sync(&mut self) -> Result<(), std::io::Error> {
+    pub fn sync(&mut self) -> Result<SyncStats, std::io::Error> {
+        // 只读挂载：没有待写回的修改时什么也不写，有的话拒绝落盘
+        if self.read_only {
+            if self.super_block.dirty || self.data_area.dirty_count() > 0 {
+                return Err(read_only_error());
+            }
+            return Ok(SyncStats {
+                blocks: 0,
+                bytes: 0,
+            });
+        }
+
         // 同步各个组件
-        self.inode_bitmap.sync(&mut self.disk)?;
-        self.data_bitmap.sync(&mut self.disk)?;
-        self.inode_table.sync(&mut self.disk)?;
-        self.data_area.sync(&mut self.disk)?;
+        let mut blocks = self.inode_bitmap.sync(&self.disk)?;
+        blocks += self.data_bitmap.sync(&self.disk)?;
+        blocks += self.inode_table.sync(&self.disk)?;
+        blocks += self.data_area.sync()?;
 
         // 同步超级块
-        let super_block_bytes = bincode::serialize(&self.super_block)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let super_block_bytes =
+            bincode::serialize(&self.super_block).map_err(std::io::Error::other)?;
         let mut block_buf = [0u8; 4096];
         block_buf[..super_block_bytes.len()].copy_from_slice(&super_block_bytes);
         self.disk.write_block(0, &block_buf)?;
+        blocks += 1;
 
         self.super_block.dirty = false;
-        Ok(())
+        Ok(SyncStats {
+            blocks,
+            bytes: blocks * BLOCK_SIZE as u64,
+        })
     }
 
     /// 卸载文件系统  
     pub fn unmount(&mut self) -> Result<(), std::io::Error> {
+        // 卸载视为关闭所有句柄：回收已删除但仍被打开的 inode
+        for inode_id in std::mem::take(&mut self.open_counts).into_keys() {
+            if !self.read_only && self.is_orphan(inode_id) {
+                self.free_inode_and_blocks(inode_id)
+                    .map_err(std::io::Error::other)?;
+            }
+        }
         if self.super_block.dirty {
             self.sync()?;
         }
@@ -425,14 +1388,10 @@ impl FileSystem {
         inode_id: usize,
         entry_type: DirEntryType,
     ) -> Result<(), String> {
+        check_name(name)?;
         let parent_inode_id = self.find_inode(parent_path)?;
-        let parent_inode = self
-            .inode_table
-            .get_inode_mut(parent_inode_id)
-            .ok_or("Parent inode not found")?;
-
-        let block_id = parent_inode.direct_blocks[0];
-        if block_id == 0 {
+        let blocks = self.dir_blocks(parent_inode_id)?;
+        if blocks.is_empty() {
             // 添加更详细的错误信息
             return Err(format!(
                 "Parent directory has no data block. inode_id={}, path={}",
@@ -440,241 +1399,895 @@ impl FileSystem {
             ));
         }
 
-        // 读取并反序列化目录
-        let block_data = self
-            .data_area
-            .read_block(block_id)
-            .ok_or("Failed to read directory block")?;
+        // 名字在所有目录块中都必须唯一
+        if self.lookup_entry(parent_inode_id, name).is_some() {
+            return Err(format!("Entry '{}' already exists", name));
+        }
+
+        // 依次尝试已有的目录块，放得下就写回
+        for block_id in blocks {
+            let mut dir = self.load_dir_block(block_id)?;
+            let old_len = serialized_len(&dir)?;
+            dir.add(inode_id, name, entry_type.clone())?;
+            if serialized_len(&dir)? <= self.data_area.payload_size() as u64 {
+                self.store_dir_block(block_id, &dir)?;
+                return self.resize_dir(parent_inode_id, old_len, serialized_len(&dir)?);
+            }
+        }
+
+        // 所有块都满了：再分配一个目录块
+        let mut dir = Directory::new(parent_inode_id as usize);
+        dir.add(inode_id, name, entry_type)?;
+        let bytes = bincode::serialize(&dir).map_err(|e| e.to_string())?;
+        let block_id = self.alloc_block(&bytes, RESERVED_BLOCK)?;
+        if let Err(e) = self.attach_block(parent_inode_id, block_id) {
+            self.data_bitmap.free(block_id);
+            self.super_block.free_blocks += 1;
+            return Err(e);
+        }
+        self.resize_dir(parent_inode_id, 0, serialized_len(&dir)?)
+    }
 
-        let mut parent_dir: Directory =
-            bincode::deserialize(block_data).map_err(|_| "Failed to deserialize directory")?;
+    // 辅助方法：目录占用的全部数据块（直接块 + 间接块）
+    fn dir_blocks(&self, inode_id: u64) -> Result<Vec<u64>, String> {
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("Inode not found")?;
 
-        // 添加新条目
-        parent_dir.add(inode_id, name, entry_type)?;
+        if !matches!(inode.inode_type, InodeType::Directory) {
+            return Err("Not a directory".to_string());
+        }
+        self.file_blocks(inode)
+    }
 
-        // 序列化并写回
-        let dir_bytes = bincode::serialize(&parent_dir).unwrap();
-        self.data_area.write_block(block_id, &dir_bytes).unwrap();
+    // 辅助方法：每个目录块独立保存一个 Directory（只含部分目录项）
+    fn load_dir_block(&self, block_id: u64) -> Result<Directory, String> {
+        let block_data = self
+            .data_area
+            .read_block(block_id)
+            .map_err(|e| e.to_string())?;
+        Directory::load_from_bytes(&block_data).map_err(|_| "Corrupted directory block".to_string())
+    }
 
-        // 更新父目录inode
-        parent_inode.size = dir_bytes.len() as u64;
-        parent_inode.touch();
+    fn store_dir_block(&mut self, block_id: u64, dir: &Directory) -> Result<(), String> {
+        let dir_bytes = bincode::serialize(dir).map_err(|e| e.to_string())?;
+        self.data_area.write_block(block_id, &dir_bytes)
+    }
 
+    // 辅助方法：某个目录块从 old_len 变为 new_len 字节后更新目录 inode
+    fn resize_dir(&mut self, inode_id: u64, old_len: u64, new_len: u64) -> Result<(), String> {
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Directory inode not found")?;
+        inode.size = (inode.size + new_len).saturating_sub(old_len);
+        inode.touch();
         Ok(())
     }
 
-    /// 删除文件    
+    /// 删除文件：只移除一个名字，link_count 归零时才真正释放 inode 和数据块；
+    /// 文件仍被打开时推迟到最后一个句柄 close 再释放
     pub fn delete_file(&mut self, path: &str, name: &str) -> Result<(), String> {
-        // 1. 查找文件inode
-        let file_inode_id = self.find_inode(&format!("{}/{}", path, name))?;
+        self.ensure_writable()?;
+        // 1. 查找文件inode；目录必须走 delete_dir / remove_dir_recursive
+        let full_path = format!("{}/{}", path, name);
+        let file_inode_id = self.find_inode(&full_path)?;
+        if self.is_dir(&full_path) {
+            return Err(FileSystemError::IsADirectory(full_path).to_string());
+        }
+
+        // 2. 从父目录中移除条目
+        self.remove_directory_entry(path, name)?;
+        self.super_block.dirty = true;
 
-        // 2. 释放文件占用的数据块
         let inode = self
             .inode_table
-            .get_inode(file_inode_id)
+            .get_inode_mut(file_inode_id)
             .ok_or("File inode not found")?;
+        inode.link_count = inode.link_count.saturating_sub(1);
+        if inode.link_count > 0 || self.open_counts.contains_key(&file_inode_id) {
+            inode.ctime = current_timestamp();
+            return Ok(());
+        }
 
-        for &block_id in &inode.direct_blocks {
-            if block_id != 0 {
-                self.data_bitmap.free(block_id);
-                // DataArea 不需要 remove_block，位图已经管理分配
-            }
+        self.free_inode_and_blocks(file_inode_id)
+    }
+
+    // 辅助方法：link_count 已归零，仍占着 inode 等待最后一个句柄关闭
+    fn is_orphan(&self, inode_id: u64) -> bool {
+        self.inode_table
+            .get_inode(inode_id)
+            .is_some_and(|inode| !inode.is_free() && inode.link_count == 0)
+    }
+
+    /// 已分配但 link_count 为 0、且没有句柄打开的 inode（没有任何路径能再找到它们）
+    pub fn orphan_inodes(&self) -> Vec<u64> {
+        (0..self.inode_table.inodes.len() as u64)
+            .filter(|id| self.is_orphan(*id) && !self.open_counts.contains_key(id))
+            .collect()
+    }
+
+    // 辅助方法：回收所有孤儿 inode，返回被回收的编号
+    fn reclaim_orphans(&mut self) -> Result<Vec<u64>, String> {
+        let orphans = self.orphan_inodes();
+        for &inode_id in &orphans {
+            self.free_inode_and_blocks(inode_id)?;
         }
+        Ok(orphans)
+    }
 
-        // 3. 释放inode
-        self.inode_bitmap.free(file_inode_id);
+    // 辅助方法：释放文件的数据块、属性块和 inode 本身
+    fn free_inode_and_blocks(&mut self, inode_id: u64) -> Result<(), String> {
+        // 1. 释放数据块和属性块（free_file_blocks 会同步 free_blocks）
+        self.free_file_blocks(inode_id)?;
+        self.release_xattrs(inode_id);
 
-        // 4. 从父目录中移除条目
-        self.remove_directory_entry(path, name)?;
+        // 2. 释放inode（同时清空 inode 表中的槽位）
+        self.inode_table
+            .free_inode(&mut self.inode_bitmap, inode_id);
 
-        // 5. 更新计数器
+        // 3. 更新计数器
         self.super_block.free_inode += 1;
         self.super_block.dirty = true;
 
         Ok(())
     }
 
+    /// 创建硬链接：新名字指向同一个 inode，并增加 link_count
+    pub fn link(&mut self, existing_path: &str, new_path: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let (existing_parent, existing_name) = split_path(existing_path)?;
+        let (new_parent, new_name) = split_path(new_path)?;
+
+        let inode_id = self.find_inode(existing_path)?;
+        let inode_type = self.stat(existing_parent, existing_name)?.inode_type;
+        if inode_type == InodeType::Directory {
+            return Err(format!(
+                "Hard links to directories are not allowed: {}",
+                existing_path
+            ));
+        }
+        if self.exists(new_path) {
+            return Err(format!("Destination already exists: {}", new_path));
+        }
+
+        self.add_directory_entry(
+            new_parent,
+            new_name,
+            inode_id as usize,
+            DirEntryType::from(&inode_type),
+        )?;
+
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("File inode not found")?;
+        inode.link_count += 1;
+        inode.ctime = current_timestamp();
+        self.super_block.dirty = true;
+
+        Ok(())
+    }
+
     /// 删除目录    
     pub fn delete_dir(&mut self, path: &str, name: &str) -> Result<(), String> {
+        self.ensure_writable()?;
         // 类似delete_file，但需要检查目录是否为空
         let dir_inode_id = self.find_inode(&format!("{}/{}", path, name))?;
 
-        // 检查目录是否为空
-        let entries = self.list_dir(&format!("{}/{}", path, name))?;
-        if entries.len() > 2 {
-            // 包含 . 和 ..
+        // 检查目录是否为空：除 . 和 .. 之外不能有其他目录项，且两个点目录项都必须在
+        let entries = self.dir_entries(dir_inode_id)?;
+        let is_dot = |entry: &DirEntry| entry.name == "." || entry.name == "..";
+        if entries.iter().any(|entry| !is_dot(entry)) {
             return Err("Directory not empty".to_string());
         }
-
-        // 释放目录数据块和inode
-        let inode = self
-            .inode_table
-            .get_inode(dir_inode_id)
-            .ok_or("Directory inode not found")?;
-
-        if inode.direct_blocks[0] != 0 {
-            // 检查是否为 0 而不是 Some
-            let block_id = inode.direct_blocks[0];
-            self.data_bitmap.free(block_id);
-            // DataArea 不需要 remove_block
+        if !entries.iter().any(|e| e.name == ".") || !entries.iter().any(|e| e.name == "..") {
+            return Err(format!("Corrupted directory: missing . or .. in {}", name));
         }
 
-        self.inode_bitmap.free(dir_inode_id);
+        // 先摘掉父目录中的目录项，失败时不会留下指向已释放 inode 的条目
         self.remove_directory_entry(path, name)?;
 
+        // 再释放目录数据块、属性块和inode
+        self.free_file_blocks(dir_inode_id)?;
+        self.release_xattrs(dir_inode_id);
+        self.inode_table
+            .free_inode(&mut self.inode_bitmap, dir_inode_id);
+
         self.super_block.free_inode += 1;
         self.super_block.dirty = true;
 
         Ok(())
     }
 
-    /// 读取文件内容    
-    pub fn read_file(&self, path: &str, name: &str) -> Result<Vec<u8>, String> {
-        let file_inode_id = self.find_inode(&format!("{}/{}", path, name))?;
-        let inode = self
-            .inode_table
-            .get_inode(file_inode_id)
-            .ok_or("File inode not found")?;
+    /// 递归删除目录：深度优先先删文件、清空子目录，最后释放目录本身
+    pub fn remove_dir_recursive(&mut self, path: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let (parent, name) = split_path(path).map_err(|_| "Refusing to remove '/'".to_string())?;
+        let dir_id = self.find_inode(path)?;
+        if self.stat(parent, name)?.inode_type != InodeType::Directory {
+            return Err(format!("Not a directory: {}", path));
+        }
+
+        let mut visited = HashSet::new();
+        let mut removed = 0;
+        let result = self.remove_tree(&join_path(parent, name), dir_id, &mut visited, &mut removed);
+        if self.take_interrupt() {
+            // 已删除的部分先落盘，磁盘状态停在最后一个完整的删除步骤
+            self.sync().map_err(|e| e.to_string())?;
+            return Err(FileSystemError::Interrupted(format!(
+                "removed {} entries under {}",
+                removed, path
+            ))
+            .to_string());
+        }
+        result?;
+        self.delete_dir(parent, name)
+    }
 
-        // 读取文件数据块
-        let block_id = inode.direct_blocks[0];
-        if block_id != 0 {
-            // 改为检查是否为 0，而不是使用 Some
-            if let Some(data) = self.data_area.read_block(block_id) {
-                return Ok(data[..inode.size as usize].to_vec());
+    // 清空目录内容（不删除目录本身）；visited 防止将来符号链接等造成环，
+    // removed 累计已删除的条目数，中断时在下一个条目之前停下
+    fn remove_tree(
+        &mut self,
+        path: &str,
+        dir_id: u64,
+        visited: &mut HashSet<u64>,
+        removed: &mut u64,
+    ) -> Result<(), String> {
+        if !visited.insert(dir_id) {
+            return Err(format!("Directory cycle detected at {}", path));
+        }
+
+        for entry in self.dir_entries(dir_id)? {
+            if entry.name == "." || entry.name == ".." {
+                continue;
             }
+            if self.interrupt.load(Ordering::SeqCst) {
+                return Err("Interrupted".to_string());
+            }
+            match entry.entry_type {
+                DirEntryType::Directory => {
+                    let child = join_path(path, &entry.name);
+                    self.remove_tree(&child, entry.inode_index as u64, visited, removed)?;
+                    self.delete_dir(path, &entry.name)?;
+                }
+                _ => self.delete_file(path, &entry.name)?,
+            }
+            *removed += 1;
         }
+        Ok(())
+    }
 
-        Ok(Vec::new())
+    /// 读取文件内容（会更新 atime）
+    pub fn read_file(&mut self, path: &str, name: &str) -> Result<Vec<u8>, String> {
+        self.read_path(&format!("{}/{}", path, name))
     }
 
-    /// 获取文件状态信息  
-    pub fn stat(&self, path: &str, name: &str) -> Result<Inode, String> {
-        let inode_id = self.find_inode(&format!("{}/{}", path, name))?;
+    /// 按完整路径读取文件内容（不以 `/` 开头时同样从根目录解析）
+    pub fn read_path(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        let file_inode_id = self.find_inode(path)?;
+        let content = self.file_content(file_inode_id)?;
+        self.touch_atime(file_inode_id);
+        Ok(content)
+    }
+
+    // 辅助方法：只读地取出文件全部内容，不记录访问
+    fn file_content(&self, inode_id: u64) -> Result<Vec<u8>, String> {
         let inode = self
             .inode_table
             .get_inode(inode_id)
             .ok_or("File inode not found")?;
 
-        Ok(inode.clone())
+        // 按顺序拼接所有数据块，再截断到文件实际大小
+        let mut content = Vec::with_capacity(inode.size as usize);
+        for block_id in self.file_blocks(inode)? {
+            let data = self
+                .data_area
+                .read_block(block_id)
+                .map_err(|e| e.to_string())?;
+            content.extend_from_slice(&data[..self.data_area.payload_size()]);
+        }
+        content.truncate(inode.size as usize);
+
+        Ok(content)
     }
 
-    // 辅助方法：从目录中移除条目
-    fn remove_directory_entry(&mut self, parent_path: &str, name: &str) -> Result<(), String> {
-        let parent_inode_id = self.find_inode(parent_path)?;
-        let parent_inode = self
+    /// 读取 `[start, start+len)` 范围内的字节，只访问与该范围重叠的数据块；超出文件末尾的部分被截掉
+    pub fn read_range(&self, path: &str, start: u64, len: u64) -> Result<Vec<u8>, String> {
+        let inode_id = self.find_inode(path)?;
+        let inode = self
             .inode_table
-            .get_inode_mut(parent_inode_id)
-            .ok_or("Parent inode not found")?;
-
-        let block_id = parent_inode.direct_blocks[0];
-        if block_id == 0 {
-            return Err("Parent directory has no data block".to_string());
+            .get_inode(inode_id)
+            .ok_or("File inode not found")?;
+        if inode.inode_type != InodeType::File {
+            return Err(FileSystemError::IsADirectory(path.to_string()).to_string());
         }
 
-        let block_data = self
-            .data_area
-            .read_block(block_id)
-            .ok_or("Failed to read directory block")?;
-
-        let mut parent_dir: Directory =
-            bincode::deserialize(block_data).map_err(|_| "Failed to deserialize directory")?;
-
-        // 关键：重建 index_map
-        parent_dir.rebuild_index_map();
-
-        // 删除条目
-        parent_dir
-            .remove(name)
-            .ok_or("Entry not found in directory")?;
-
-        let dir_bytes = bincode::serialize(&parent_dir).map_err(|e| e.to_string())?;
-        self.data_area
-            .write_block(block_id, &dir_bytes)
-            .map_err(|e| e.to_string())?;
-
-        parent_inode.size = dir_bytes.len() as u64;
-        parent_inode.touch();
-
-        Ok(())
+        let len = len.min(inode.size.saturating_sub(start));
+        let mut buf = vec![0u8; len as usize];
+        let n = self.read_chunk(inode_id, start, &mut buf)?;
+        buf.truncate(n);
+        Ok(buf)
     }
 
-    pub fn find_inode(&self, path: &str) -> Result<u64, String> {
-        println!("🔍 find_inode called with path: {:?}", path);
-
-        if path == "/" {
-            return Ok(0);
+    /// 类似 `wc`：逐块统计文件的 (行数, 单词数, 字节数)，不把整个文件读入内存
+    pub fn count_stats(&self, path: &str) -> Result<(usize, usize, usize), String> {
+        let inode_id = self.find_inode(path)?;
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("File inode not found")?;
+        if inode.inode_type != InodeType::File {
+            return Err(FileSystemError::IsADirectory(path.to_string()).to_string());
         }
 
-        let normalized_path = path.trim_start_matches('/').trim();
-        if normalized_path.is_empty() {
-            return Ok(0);
+        let (mut lines, mut words, mut bytes) = (0, 0, 0);
+        let mut in_word = false; // 单词可能跨块，状态在块之间延续
+        let mut buf = vec![0u8; self.data_area.payload_size()];
+        loop {
+            let n = self.read_chunk(inode_id, bytes as u64, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            for &b in &buf[..n] {
+                if b == b'\n' {
+                    lines += 1;
+                }
+                if b.is_ascii_whitespace() {
+                    in_word = false;
+                } else if !in_word {
+                    in_word = true;
+                    words += 1;
+                }
+            }
+            bytes += n;
         }
+        Ok((lines, words, bytes))
+    }
 
-        let components: Vec<&str> = normalized_path
-            .split('/')
-            .filter(|s| !s.is_empty())
+    // 辅助方法：按文件偏移顺序返回 inode 引用的所有数据块（直接块 + 一级间接块）
+    fn file_blocks(&self, inode: &Inode) -> Result<Vec<u64>, String> {
+        let mut blocks: Vec<u64> = inode
+            .direct_blocks
+            .iter()
+            .copied()
+            .take_while(|&b| b != 0)
             .collect();
-        println!("Debug: path components = {:?}", components);
 
-        let mut current_inode = 0u64; // 从根目录开始
+        if let Some(indirect) = inode.indirect_block {
+            blocks.extend(
+                self.read_ptr_block(indirect)?
+                    .into_iter()
+                    .take_while(|&b| b != 0),
+            );
+        }
+        Ok(blocks)
+    }
 
-        for component in components {
-            println!("Debug: resolving component: {}", component);
-            let inode = self
-                .inode_table
-                .get_inode(current_inode)
-                .ok_or("Inode not found")?;
+    /// inode 实际占用的块数：数据块加上间接指针块和属性块本身
+    pub fn block_count(&self, inode: &Inode) -> Result<u64, String> {
+        let mut count = inode.direct_blocks.iter().filter(|&&b| b != 0).count() as u64;
+        if let Some(indirect) = inode.indirect_block {
+            let ptrs = self.read_ptr_block(indirect)?;
+            count += 1 + ptrs.iter().filter(|&&b| b != 0).count() as u64;
+        }
+        if inode.double_indirect_block.is_some() {
+            count += 1;
+        }
+        if inode.xattr_block.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
 
-            if !matches!(inode.inode_type, InodeType::Directory) {
-                return Err("Path component is not a directory".to_string());
-            }
+    // 辅助方法：把数据块挂到文件末尾，直接指针用完时自动分配一级间接块
+    fn attach_block(&mut self, inode_id: u64, block_id: u64) -> Result<(), String> {
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Inode not found")?;
+        if inode.add_block(block_id).is_ok() {
+            return Ok(());
+        }
 
-            let block_id = inode.direct_blocks[0];
-            if block_id == 0 {
-                return Err("Directory has no data block".to_string());
+        let indirect = match inode.indirect_block {
+            Some(indirect) => indirect,
+            None => {
+                let indirect = self.alloc_block(&[], block_id + 1)?;
+                if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
+                    inode.indirect_block = Some(indirect);
+                }
+                indirect
             }
+        };
 
-            let block_data = self
-                .data_area
-                .read_block(block_id)
-                .ok_or("Failed to read directory block")?;
-
-            let mut directory = Directory::load_from_bytes(block_data)
-                .map_err(|_| "Failed to deserialize directory")?;
+        let mut ptrs = self.read_ptr_block(indirect)?;
+        let slot = ptrs
+            .iter()
+            .position(|&p| p == 0)
+            .ok_or("File too large: indirect block is full")?;
+        ptrs[slot] = block_id;
+        self.data_area.write_block(indirect, &encode_ptrs(&ptrs))
+    }
 
-            if let Some(inode_index) = directory.find(component) {
-                println!(
-                    "Debug: component '{}' resolved to inode {}",
-                    component, inode_index
-                );
-                current_inode = inode_index as u64;
-            } else {
-                println!(
-                    "❌ component '{}' not found in current directory",
-                    component
-                );
-                return Err(format!("Path component not found: {}", component));
-            }
+    // 辅助方法：分配一个数据块并立即整块写入 contents（不足部分补 0）。
+    // 新块都先经过这里再挂到 inode 上，之前被释放的块里的旧内容不会出现在新文件中；
+    // hint 通常是文件上一个块之后的位置，没有要求时传 RESERVED_BLOCK
+    fn alloc_block(&mut self, contents: &[u8], hint: u64) -> Result<u64, String> {
+        let block_id = self.data_bitmap.alloc_near(hint).ok_or(NO_FREE_BLOCKS)?;
+        if let Err(e) = self.data_area.write_block(block_id, contents) {
+            self.data_bitmap.free(block_id);
+            return Err(e);
         }
+        self.super_block.free_blocks -= 1;
+        Ok(block_id)
+    }
 
-        println!("✅ find_inode resolved to inode {}", current_inode);
-        Ok(current_inode)
+    // 辅助方法：读取间接块中的块号数组
+    fn read_ptr_block(&self, block_id: u64) -> Result<Vec<u64>, String> {
+        let data = self
+            .data_area
+            .read_block(block_id)
+            .map_err(|e| e.to_string())?;
+        Ok(decode_ptrs(&data[..self.data_area.payload_size()]))
     }
 
-    pub fn open(&mut self, path: &str, flags: OpenFlags) -> Result<FileHandle, String> {
-        let inode_id = match self.find_inode(path) {
-            Ok(id) => {
-                // 文件存在
-                if flags.contains(OpenFlags::TRUNC) && flags.contains(OpenFlags::WRITE) {
-                    self.truncate_file(id)?;
-                }
-                id
-            }
-            Err(_) => {
-                // 文件不存在
+    /// 读取符号链接保存的目标路径（不解析）
+    pub fn read_link(&self, path: &str) -> Result<String, String> {
+        let inode_id = self.find_inode(path)?;
+        let is_symlink = self
+            .inode_table
+            .get_inode(inode_id)
+            .is_some_and(|inode| inode.inode_type == InodeType::Symlink);
+        if !is_symlink {
+            return Err(format!("Not a symbolic link: {}", path));
+        }
+        let target = self.file_content(inode_id)?;
+        String::from_utf8(target).map_err(|_| format!("Corrupted link target: {}", path))
+    }
+
+    /// 获取文件状态信息  
+    pub fn stat(&self, path: &str, name: &str) -> Result<Inode, String> {
+        self.stat_path(&format!("{}/{}", path, name))
+    }
+
+    /// 按完整路径获取 inode 信息
+    pub fn stat_path(&self, path: &str) -> Result<Inode, String> {
+        let inode_id = self.find_inode(path)?;
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("File inode not found")?;
+
+        Ok(inode.clone())
+    }
+
+    /// 修改文件或目录的权限位（只接受 0o777 以内的模式）
+    pub fn chmod(&mut self, path: &str, mode: u16) -> Result<(), String> {
+        self.ensure_writable()?;
+        if mode > 0o777 {
+            return Err(format!("Invalid mode: {:o}", mode));
+        }
+        let inode_id = self.find_inode(path)?;
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Inode not found")?;
+
+        inode.permissions = mode;
+        inode.ctime = current_timestamp(); // 元数据变化只更新 ctime
+        self.super_block.dirty = true;
+
+        Ok(())
+    }
+
+    /// 读取文件或目录的全部元数据
+    pub fn get_attr(&self, path: &str) -> Result<FileAttr, String> {
+        let inode_id = self.find_inode(path)?;
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("Inode not found")?;
+        Ok(FileAttr {
+            inode_id,
+            inode_type: inode.inode_type.clone(),
+            size: inode.size,
+            blocks: self.block_count(inode)?,
+            permissions: inode.permissions,
+            uid: inode.uid,
+            gid: inode.gid,
+            link_count: inode.link_count,
+            atime: inode.atime,
+            mtime: inode.mtime,
+            ctime: inode.ctime,
+        })
+    }
+
+    /// 直接设置访问和修改时间（毫秒，如导入时保留宿主机时间戳），ctime 更新为当前时间
+    pub fn set_times(&mut self, path: &str, atime: u64, mtime: u64) -> Result<(), String> {
+        self.ensure_writable()?;
+        let inode_id = self.find_inode(path)?;
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Inode not found")?;
+
+        inode.atime = atime;
+        inode.mtime = mtime;
+        inode.ctime = current_timestamp();
+        self.super_block.dirty = true;
+
+        Ok(())
+    }
+
+    /// 修改文件或目录的属主和属组
+    pub fn chown(&mut self, path: &str, uid: u32, gid: u32) -> Result<(), String> {
+        self.ensure_writable()?;
+        let inode_id = self.find_inode(path)?;
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Inode not found")?;
+
+        inode.uid = uid;
+        inode.gid = gid;
+        inode.ctime = current_timestamp();
+        self.super_block.dirty = true;
+
+        Ok(())
+    }
+
+    /// 复制文件：目标获得独立的 inode 和数据块，沿用源文件的权限与属主，时间戳为新的。
+    /// 空间不足时返回 DiskFull / InodeFull，并回滚已分配的资源。
+    pub fn copy_file(&mut self, src: &str, dst: &str) -> Result<u64, String> {
+        self.ensure_writable()?;
+        let (src_parent, src_name) = split_path(src)?;
+        let (dst_parent, dst_name) = split_path(dst)?;
+
+        let src_inode = self.stat(src_parent, src_name)?;
+        if src_inode.inode_type != InodeType::File {
+            return Err(format!("Not a regular file: {}", src));
+        }
+        if self.exists(dst) {
+            return Err(format!("Destination already exists: {}", dst));
+        }
+
+        // 先估算所需资源，尽量在动手之前就失败
+        if self.inode_bitmap.free_inodes == 0 {
+            return Err(FileSystemError::InodeFull.to_string());
+        }
+        if self.data_bitmap.free_blocks
+            < blocks_needed(src_inode.size, self.data_area.payload_size())
+        {
+            return Err(FileSystemError::DiskFull.to_string());
+        }
+
+        let content = self.file_content(self.find_inode(src)?)?;
+        let dst_id = self.create_file(dst_parent, dst_name)?;
+        if let Err(e) = self.write_file(dst, &content) {
+            // 回滚：删除目标文件会同时释放已写入的数据块和 inode；
+            // 只有块不够时才报 DiskFull，I/O、校验等错误原样返回
+            self.delete_file(dst_parent, dst_name)?;
+            if e == NO_FREE_BLOCKS {
+                return Err(FileSystemError::DiskFull.to_string());
+            }
+            return Err(e);
+        }
+
+        if let Some(inode) = self.inode_table.get_inode_mut(dst_id) {
+            inode.permissions = src_inode.permissions;
+            inode.uid = src_inode.uid;
+            inode.gid = src_inode.gid;
+        }
+        Ok(dst_id)
+    }
+
+    /// 校验复制结果：重新读取源文件和目标文件并逐字节比较
+    pub fn verify_copy(&self, src: &str, dst: &str) -> Result<(), String> {
+        let src_content = self.file_content(self.find_inode(src)?)?;
+        let dst_content = self.file_content(self.find_inode(dst)?)?;
+
+        if src_content.len() != dst_content.len() {
+            return Err(format!(
+                "Copy verification failed: {} has {} bytes, {} has {} bytes",
+                src,
+                src_content.len(),
+                dst,
+                dst_content.len()
+            ));
+        }
+        if let Some(pos) = src_content
+            .iter()
+            .zip(&dst_content)
+            .position(|(a, b)| a != b)
+        {
+            return Err(format!(
+                "Copy verification failed: {} and {} differ at byte {}",
+                src, dst, pos
+            ));
+        }
+        Ok(())
+    }
+
+    /// 重命名/移动：只搬动目录项，新旧路径指向同一个 inode，不复制数据
+    pub fn rename(&mut self, old_path: &str, new_path: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let (old_parent, old_name) = split_path(old_path)?;
+        let (new_parent, new_name) = split_path(new_path)?;
+
+        let inode_id = self.find_inode(old_path)?;
+        let entry_type = DirEntryType::from(&self.stat(old_parent, old_name)?.inode_type);
+
+        if self.exists(new_path) {
+            if self.find_inode(new_path)? == inode_id {
+                return Ok(()); // 原地重命名为自己
+            }
+            return Err(format!("Destination already exists: {}", new_path));
+        }
+
+        // 目录不能移动到自己的子树下
+        let old_prefix = join_path(old_path.trim_end_matches('/'), "");
+        if entry_type == DirEntryType::Directory
+            && join_path(new_parent, "").starts_with(&old_prefix)
+        {
+            return Err(format!("Cannot move {} into itself", old_path));
+        }
+
+        let new_parent_id = self.find_inode(new_parent)?;
+        if self
+            .inode_table
+            .get_inode(new_parent_id)
+            .is_none_or(|inode| inode.inode_type != InodeType::Directory)
+        {
+            return Err(format!("Not a directory: {}", new_parent));
+        }
+
+        // 同一目录下直接改名，inode 本身不动；新名字放不下原块时再走先删后加
+        if self.find_inode(old_parent)? == new_parent_id
+            && self.rename_directory_entry(new_parent_id, old_name, new_name)?
+        {
+            self.super_block.dirty = true;
+            return Ok(());
+        }
+
+        // 跨目录时先删后加，失败时把原条目加回去
+        self.remove_directory_entry(old_parent, old_name)?;
+        if let Err(e) =
+            self.add_directory_entry(new_parent, new_name, inode_id as usize, entry_type.clone())
+        {
+            self.add_directory_entry(old_parent, old_name, inode_id as usize, entry_type)?;
+            return Err(e);
+        }
+
+        if entry_type == DirEntryType::Directory {
+            self.set_parent_link(inode_id, new_parent_id)?;
+        }
+        if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
+            inode.ctime = current_timestamp();
+        }
+        self.super_block.dirty = true;
+
+        Ok(())
+    }
+
+    // 辅助方法：把目录的 ".." 指向新的父目录（"." 和 ".." 总在第一个目录块）
+    fn set_parent_link(&mut self, dir_inode_id: u64, parent_inode_id: u64) -> Result<(), String> {
+        let block_id = *self
+            .dir_blocks(dir_inode_id)?
+            .first()
+            .ok_or("Directory has no data block")?;
+        let mut dir = self.load_dir_block(block_id)?;
+
+        let idx = *dir
+            .index_map
+            .get("..")
+            .ok_or("Directory has no '..' entry")?;
+        dir.entries[idx].inode_index = parent_inode_id as usize;
+        self.store_dir_block(block_id, &dir)
+    }
+
+    // 辅助方法：在条目所在的目录块里原地改名；改名后超出块容量时返回 false 且不做修改
+    fn rename_directory_entry(
+        &mut self,
+        dir_inode_id: u64,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<bool, String> {
+        check_name(new_name)?;
+        for block_id in self.dir_blocks(dir_inode_id)? {
+            let mut dir = self.load_dir_block(block_id)?;
+            if dir.get(old_name).is_none() {
+                continue;
+            }
+            let old_len = serialized_len(&dir)?;
+            dir.rename(old_name, new_name)?;
+            let new_len = serialized_len(&dir)?;
+            if new_len > self.data_area.payload_size() as u64 {
+                return Ok(false);
+            }
+            self.store_dir_block(block_id, &dir)?;
+            self.resize_dir(dir_inode_id, old_len, new_len)?;
+            return Ok(true);
+        }
+        Err("Entry not found in directory".to_string())
+    }
+
+    // 辅助方法：从目录中移除条目
+    fn remove_directory_entry(&mut self, parent_path: &str, name: &str) -> Result<(), String> {
+        let parent_inode_id = self.find_inode(parent_path)?;
+        let blocks = self.dir_blocks(parent_inode_id)?;
+        if blocks.is_empty() {
+            return Err("Parent directory has no data block".to_string());
+        }
+
+        for block_id in blocks {
+            let mut dir = self.load_dir_block(block_id)?;
+            let old_len = serialized_len(&dir)?;
+            if dir.remove(name).is_some() {
+                self.store_dir_block(block_id, &dir)?;
+                self.resize_dir(parent_inode_id, old_len, serialized_len(&dir)?)?;
+                return self.compact_dir(parent_inode_id);
+            }
+        }
+        Err("Entry not found in directory".to_string())
+    }
+
+    // 辅助方法：多块目录删除条目后重新装箱，释放用不到的尾部目录块并收缩 size
+    fn compact_dir(&mut self, inode_id: u64) -> Result<(), String> {
+        let blocks = self.dir_blocks(inode_id)?;
+        if blocks.len() < 2 {
+            return Ok(());
+        }
+        let parts = blocks
+            .iter()
+            .map(|&block_id| self.load_dir_block(block_id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let packed = Directory::compact(parts, self.data_area.payload_size() as u64)?;
+        if packed.len() == blocks.len() {
+            return Ok(());
+        }
+
+        let mut size = 0;
+        for (&block_id, dir) in blocks.iter().zip(&packed) {
+            self.store_dir_block(block_id, dir)?;
+            size += serialized_len(dir)?;
+        }
+
+        // 清空块映射后只挂回保留的块；尾部块和旧的间接块一并释放
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Directory inode not found")?;
+        let mut released = blocks[packed.len()..].to_vec();
+        released.extend(inode.indirect_block.take());
+        inode.direct_blocks = [0; DIRECT_PTRS];
+        inode.size = size;
+        for &block_id in &released {
+            self.data_bitmap.free(block_id);
+        }
+        self.super_block.free_blocks += released.len() as u64;
+        for &block_id in &blocks[..packed.len()] {
+            self.attach_block(inode_id, block_id)?;
+        }
+        self.super_block.dirty = true;
+        Ok(())
+    }
+
+    /// 判断路径是否存在：逐级查找，任一分量缺失立即返回 false。
+    /// 与 find_inode 一样要求沿途目录有 x（search）权限，没有权限时也返回 false
+    pub fn exists(&self, path: &str) -> bool {
+        let mut current_inode = 0u64; // 从根目录开始
+
+        for component in path.split('/').filter(|s| !s.is_empty()) {
+            let searchable = self
+                .inode_table
+                .get_inode(current_inode)
+                .is_some_and(|inode| self.has_access(inode, PERM_EXEC));
+            if !searchable {
+                return false;
+            }
+            match self.lookup_entry(current_inode, component) {
+                Some(inode_index) => current_inode = inode_index as u64,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// 路径是否指向目录；与 find_inode 一样不跟随符号链接，找不到时返回 false
+    pub fn is_dir(&self, path: &str) -> bool {
+        self.path_type(path) == Some(InodeType::Directory)
+    }
+
+    /// 路径是否指向普通文件；符号链接本身不算普通文件
+    pub fn is_file(&self, path: &str) -> bool {
+        self.path_type(path) == Some(InodeType::File)
+    }
+
+    // 辅助方法：查找路径对应 inode 的类型
+    fn path_type(&self, path: &str) -> Option<InodeType> {
+        let inode_id = self.find_inode(path).ok()?;
+        self.inode_table
+            .get_inode(inode_id)
+            .map(|inode| inode.inode_type.clone())
+    }
+
+    // 辅助方法：在目录的数据块中查找单个名字，命中即返回，不再读取后续块
+    fn lookup_entry(&self, dir_inode_id: u64, name: &str) -> Option<usize> {
+        let inode = self.inode_table.get_inode(dir_inode_id)?;
+        if inode.inode_type != InodeType::Directory {
+            return None;
+        }
+
+        for block_id in self.file_blocks(inode).ok()? {
+            let directory = self.load_dir_block(block_id).ok()?;
+            if let Some(entry) = directory.get(name) {
+                return Some(entry.inode_index);
+            }
+        }
+        None
+    }
+
+    pub fn find_inode(&self, path: &str) -> Result<u64, String> {
+        if path == "/" {
+            return Ok(0);
+        }
+
+        let normalized_path = path.trim_start_matches('/').trim();
+        if normalized_path.is_empty() {
+            return Ok(0);
+        }
+
+        let components: Vec<&str> = normalized_path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let mut current_inode = 0u64; // 从根目录开始
+
+        for component in components {
+            let inode = self
+                .inode_table
+                .get_inode(current_inode)
+                .ok_or("Inode not found")?;
+
+            if !matches!(inode.inode_type, InodeType::Directory) {
+                return Err("Path component is not a directory".to_string());
+            }
+
+            if inode.direct_blocks[0] == 0 {
+                return Err("Directory has no data block".to_string());
+            }
+
+            // 进入目录查找子项需要该目录的 x（search）权限
+            if !self.has_access(inode, PERM_EXEC) {
+                return Err(format!("Permission denied: search before {}", component));
+            }
+
+            match self.lookup_entry(current_inode, component) {
+                Some(inode_index) => current_inode = inode_index as u64,
+                None => return Err(format!("Path component not found: {}", component)),
+            }
+        }
+
+        Ok(current_inode)
+    }
+
+    pub fn open(&mut self, path: &str, flags: OpenFlags) -> Result<FileHandle, String> {
+        // 不带 WRITE 的 TRUNC 以前会被悄悄忽略，现在明确拒绝
+        if flags.contains(OpenFlags::TRUNC) && !flags.contains(OpenFlags::WRITE) {
+            return Err("TRUNC requires WRITE".to_string());
+        }
+        if flags.intersects(OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNC) {
+            self.ensure_writable()?;
+        }
+        let (inode_id, existed) = match self.find_inode(path) {
+            Ok(id) => (id, true),
+            Err(_) => {
+                // 文件不存在
                 if flags.contains(OpenFlags::CREATE) {
-                    self.create_file_from_path(path)?
+                    (self.create_file_from_path(path)?, false)
                 } else {
                     return Err("File not found".to_string());
                 }
@@ -692,7 +2305,16 @@ impl FileSystem {
         }
 
         // 权限检查（简化版）
-        self.check_open_permissions(&inode, &flags)?;
+        self.check_open_permissions(inode, &flags)?;
+
+        // 所有检查都通过后才截断，失败的 open 不会清掉已有内容
+        if existed && flags.contains(OpenFlags::TRUNC) {
+            self.truncate_file(inode_id)?;
+        }
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("Inode not found")?;
 
         // offset 初始化
         let offset = if flags.contains(OpenFlags::APPEND) {
@@ -701,6 +2323,7 @@ impl FileSystem {
             0
         };
 
+        *self.open_counts.entry(inode_id).or_insert(0) += 1;
         Ok(FileHandle {
             inode_id,
             offset,
@@ -708,62 +2331,244 @@ impl FileSystem {
         })
     }
 
-    fn check_open_permissions(&self, inode: &Inode, flags: &OpenFlags) -> Result<(), String> {
-        if flags.contains(OpenFlags::READ) && inode.permissions & 0o400 == 0 {
-            return Err("Permission denied: read".into());
+    /// 关闭句柄；文件已被删除且这是最后一个句柄时，释放它的 inode 和数据块
+    pub fn close(&mut self, fh: FileHandle) -> Result<(), String> {
+        let Some(count) = self.open_counts.get_mut(&fh.inode_id) else {
+            return Err("File handle is not open".to_string());
+        };
+        *count -= 1;
+        if *count > 0 {
+            return Ok(());
+        }
+        self.open_counts.remove(&fh.inode_id);
+        if self.is_orphan(fh.inode_id) {
+            self.free_inode_and_blocks(fh.inode_id)?;
         }
+        Ok(())
+    }
 
-        if flags.contains(OpenFlags::WRITE) && inode.permissions & 0o200 == 0 {
-            return Err("Permission denied: write".into());
+    /// 从 `fh.offset` 开始读取最多 `buf.len()` 字节并推进 offset；到达 EOF 返回 0
+    pub fn read_at(&mut self, fh: &mut FileHandle, buf: &mut [u8]) -> Result<usize, String> {
+        if !fh.flags.contains(OpenFlags::READ) {
+            return Err("File not opened for reading".into());
         }
 
-        Ok(())
+        let len = self.read_chunk(fh.inode_id, fh.offset, buf)?;
+        fh.offset += len as u64;
+        if len > 0 {
+            self.touch_atime(fh.inode_id);
+        }
+        Ok(len)
     }
 
-    pub fn free_file_blocks(&mut self, inode_id: u64) -> Result<(), String> {
+    // 辅助方法：从 offset 处读取最多 buf.len() 字节，不记录访问；到达 EOF 返回 0
+    fn read_chunk(&self, inode_id: u64, offset: u64, buf: &mut [u8]) -> Result<usize, String> {
         let inode = self
             .inode_table
-            .get_inode_mut(inode_id)
-            .ok_or("Inode not found")?;
-
-        let mut freed = 0;
+            .get_inode(inode_id)
+            .ok_or("File inode not found")?;
+        if offset >= inode.size {
+            return Ok(0);
+        }
 
-        // 1. 释放 direct blocks
-        for block in inode.direct_blocks.iter_mut() {
-            if *block != 0 {
-                self.data_bitmap.free(*block);
-                *block = 0;
-                freed += 1;
+        let len = buf.len().min((inode.size - offset) as usize);
+        let blocks = self.file_blocks(inode)?;
+        let payload = self.data_area.payload_size();
+
+        let mut done = 0;
+        while done < len {
+            let pos = offset as usize + done;
+            let (index, start) = (pos / payload, pos % payload);
+            let chunk = (payload - start).min(len - done);
+            match blocks.get(index) {
+                Some(&block_id) => {
+                    let data = self
+                        .data_area
+                        .read_block(block_id)
+                        .map_err(|e| e.to_string())?;
+                    buf[done..done + chunk].copy_from_slice(&data[start..start + chunk]);
+                }
+                // 没有分配数据块的部分按 0 读出
+                None => buf[done..done + chunk].fill(0),
             }
+            done += chunk;
         }
+        Ok(len)
+    }
 
-        // 2. 释放 indirect block（注意：你现在只是“单个块”）
-        if let Some(block_id) = inode.indirect_block.take() {
-            self.data_bitmap.free(block_id);
-            freed += 1;
+    /// 从 `fh.offset` 开始写入 `data`，不截断后面的内容；需要时分配新块扩展文件
+    pub fn write_at(&mut self, fh: &mut FileHandle, data: &[u8]) -> Result<usize, String> {
+        self.ensure_writable()?;
+        if !fh.flags.contains(OpenFlags::WRITE) {
+            return Err("File not opened for writing".into());
         }
 
-        // 3. double indirect（你目前还没用到，可以先占位）
-        if let Some(block_id) = inode.double_indirect_block.take() {
-            self.data_bitmap.free(block_id);
-            freed += 1;
+        let inode = self
+            .inode_table
+            .get_inode(fh.inode_id)
+            .ok_or("File inode not found")?;
+        // APPEND 语义：每次写之前都移动到文件末尾
+        if fh.flags.contains(OpenFlags::APPEND) {
+            fh.offset = inode.size;
+        }
+        let old_size = inode.size;
+        let mut blocks = self.file_blocks(inode)?;
+        let payload = self.data_area.payload_size();
+
+        // 写入点在 EOF 之后时，空洞必须读出 0：先清掉旧末尾块里 size 之后的残留字节，
+        // 空洞中缺的块在下面分配时填 0
+        let tail = (old_size % payload as u64) as usize;
+        if fh.offset > old_size && !data.is_empty() && tail != 0 {
+            if let Some(&block_id) = blocks.get((old_size / payload as u64) as usize) {
+                let mut block = self
+                    .data_area
+                    .read_block(block_id)
+                    .map_err(|e| e.to_string())?;
+                block[tail..payload].fill(0);
+                self.data_area.write_block(block_id, &block[..payload])?;
+            }
         }
 
-        // 4. 更新 inode
-        inode.size = 0;
+        let mut done = 0;
+        while done < data.len() {
+            let pos = fh.offset as usize + done;
+            let (index, start) = (pos / payload, pos % payload);
+            let chunk = (payload - start).min(data.len() - done);
+
+            // 写到文件末尾之后：补齐中间缺的块（新块内容为 0）
+            while blocks.len() <= index {
+                let hint = blocks.last().map_or(RESERVED_BLOCK, |&last| last + 1);
+                let block_id = self.alloc_block(&[], hint)?;
+                self.attach_block(fh.inode_id, block_id)?;
+                blocks.push(block_id);
+            }
 
-        // 注意：mtime 在 write_file 里更新
-        // ctime 不变（内容变化不算元数据变化）
+            let block_id = blocks[index];
+            let mut block = self
+                .data_area
+                .read_block(block_id)
+                .map_err(|e| e.to_string())?
+                .to_vec();
+            block[start..start + chunk].copy_from_slice(&data[done..done + chunk]);
+            self.data_area.write_block(block_id, &block[..payload])?;
+            done += chunk;
+        }
 
-        // 5. 更新超级块
-        self.super_block.free_blocks += freed;
+        fh.offset += data.len() as u64;
+        if let Some(inode) = self.inode_table.get_inode_mut(fh.inode_id) {
+            inode.size = old_size.max(fh.offset);
+            inode.mtime = current_timestamp();
+        }
         self.super_block.dirty = true;
-
-        Ok(())
+        Ok(data.len())
     }
 
-    pub fn truncate_file(&mut self, inode_id: u64) -> Result<(), String> {
-        self.free_file_blocks(inode_id)?;
+    /// 移动文件句柄的 offset，语义同 `std::io::SeekFrom`；允许越过 EOF，返回新的绝对位置
+    pub fn seek(&self, fh: &mut FileHandle, pos: SeekFrom) -> Result<u64, String> {
+        let size = self
+            .inode_table
+            .get_inode(fh.inode_id)
+            .ok_or("File inode not found")?
+            .size;
+
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => fh.offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => size.checked_add_signed(delta),
+        };
+        fh.offset = target.ok_or("Invalid seek: resulting offset is negative")?;
+        Ok(fh.offset)
+    }
+
+    fn check_open_permissions(&self, inode: &Inode, flags: &OpenFlags) -> Result<(), String> {
+        if flags.contains(OpenFlags::READ) && !self.has_access(inode, PERM_READ) {
+            return Err("Permission denied: read".into());
+        }
+
+        if flags.contains(OpenFlags::WRITE) && !self.has_access(inode, PERM_WRITE) {
+            return Err("Permission denied: write".into());
+        }
+
+        Ok(())
+    }
+
+    /// 设置当前身份：之后新建的文件归其所有，权限检查也按其 uid/gid 进行
+    pub fn set_current_user(&mut self, uid: u32, gid: u32) {
+        self.current_uid = uid;
+        self.current_gid = gid;
+    }
+
+    // 辅助方法：按当前用户是属主、同组还是其他人，选出对应的 rwx 三位再检查
+    fn has_access(&self, inode: &Inode, wanted: u16) -> bool {
+        let shift = if inode.uid == self.current_uid {
+            6
+        } else if inode.gid == self.current_gid {
+            3
+        } else {
+            0
+        };
+        (inode.permissions >> shift) & wanted == wanted
+    }
+
+    pub fn free_file_blocks(&mut self, inode_id: u64) -> Result<(), String> {
+        self.ensure_writable()?;
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("Inode not found")?
+            .clone();
+        self.release_blocks(&inode)?;
+
+        // 更新 inode：清空块指针和大小
+        // 注意：mtime 在 write_file 里更新
+        // ctime 不变（内容变化不算元数据变化）
+        let inode = self
+            .inode_table
+            .get_inode_mut(inode_id)
+            .ok_or("Inode not found")?;
+        inode.direct_blocks = [0; DIRECT_PTRS];
+        inode.indirect_block = None;
+        inode.double_indirect_block = None;
+        inode.size = 0;
+
+        Ok(())
+    }
+
+    // 辅助方法：在位图中释放 inode 引用的所有块并更新超级块，不修改 inode 本身
+    fn release_blocks(&mut self, inode: &Inode) -> Result<(), String> {
+        // 1. direct blocks
+        let mut blocks: Vec<u64> = inode
+            .direct_blocks
+            .iter()
+            .copied()
+            .filter(|&b| b != 0)
+            .collect();
+
+        // 2. indirect block 引用的数据块，以及间接块本身
+        if let Some(indirect) = inode.indirect_block {
+            blocks.extend(
+                self.read_ptr_block(indirect)?
+                    .into_iter()
+                    .filter(|&b| b != 0),
+            );
+            blocks.push(indirect);
+        }
+
+        // 3. double indirect（你目前还没用到，可以先占位）
+        blocks.extend(inode.double_indirect_block);
+
+        for &block_id in &blocks {
+            self.data_bitmap.free(block_id);
+        }
+        self.super_block.free_blocks += blocks.len() as u64;
+        self.super_block.dirty = true;
+
+        Ok(())
+    }
+
+    pub fn truncate_file(&mut self, inode_id: u64) -> Result<(), String> {
+        self.ensure_writable()?;
+        self.free_file_blocks(inode_id)?;
 
         if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
             inode.size = 0;
@@ -772,8 +2577,2034 @@ impl FileSystem {
         Ok(())
     }
 
+    /// 把文件截断或扩展到 new_size 字节：缩小时释放多余的块，扩大时分配填 0 的新块。
+    /// 同时更新 mtime 和 ctime
+    pub fn truncate_to(&mut self, path: &str, new_size: u64) -> Result<(), String> {
+        self.ensure_writable()?;
+        let inode_id = self.find_inode(path)?;
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("File inode not found")?;
+        if inode.inode_type != InodeType::File {
+            return Err(format!("Not a regular file: {}", path));
+        }
+        let old_size = inode.size;
+        let blocks = self.file_blocks(inode)?;
+        let payload = self.data_area.payload_size();
+        let wanted = new_size.div_ceil(payload as u64) as usize;
+
+        if wanted < blocks.len() {
+            // 缩小：释放末尾多余的块和不再需要的间接块，再把保留的块重新挂回去
+            let mut freed = blocks[wanted..].to_vec();
+            let inode = self
+                .inode_table
+                .get_inode_mut(inode_id)
+                .ok_or("File inode not found")?;
+            freed.extend(inode.indirect_block.take());
+            inode.direct_blocks = [0; DIRECT_PTRS];
+            for &block_id in &freed {
+                self.data_bitmap.free(block_id);
+            }
+            self.super_block.free_blocks += freed.len() as u64;
+            for &block_id in &blocks[..wanted] {
+                self.attach_block(inode_id, block_id)?;
+            }
+        } else if new_size > old_size {
+            let max_blocks = DIRECT_PTRS + payload / 8;
+            if wanted > max_blocks {
+                return Err(format!("File too large: {} bytes", new_size));
+            }
+            let needed = blocks_needed(new_size, payload) - blocks_needed(old_size, payload);
+            if self.data_bitmap.free_blocks < needed {
+                return Err(FileSystemError::DiskFull.to_string());
+            }
+
+            // 扩大：旧末尾块里 size 之后的残留字节清零，再补上填 0 的新块
+            let tail = (old_size % payload as u64) as usize;
+            if let Some(&block_id) = blocks
+                .get(old_size as usize / payload)
+                .filter(|_| tail != 0)
+            {
+                let mut block = self
+                    .data_area
+                    .read_block(block_id)
+                    .map_err(|e| e.to_string())?;
+                block[tail..payload].fill(0);
+                self.data_area.write_block(block_id, &block[..payload])?;
+            }
+            let mut hint = blocks.last().map_or(RESERVED_BLOCK, |&last| last + 1);
+            for _ in blocks.len()..wanted {
+                let block_id = self.alloc_block(&[], hint)?;
+                self.attach_block(inode_id, block_id)?;
+                hint = block_id + 1;
+            }
+        }
+
+        let now = current_timestamp();
+        if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
+            inode.size = new_size;
+            inode.mtime = now;
+            inode.ctime = now;
+        }
+        self.super_block.dirty = true;
+        Ok(())
+    }
+
     fn create_file_from_path(&mut self, path: &str) -> Result<u64, String> {
         let (parent, name) = split_path(path)?;
         self.create_file(parent, name)
     }
 }
+
+pub(crate) fn read_only_error() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::PermissionDenied,
+        "file system is mounted read-only",
+    )
+}
+
+// 存放 size 字节的文件需要的数据块数（含一级间接块），payload 为每块有效字节数
+fn blocks_needed(size: u64, payload: usize) -> u64 {
+    let data = size.div_ceil(payload as u64);
+    if data > DIRECT_PTRS as u64 {
+        data + 1
+    } else {
+        data
+    }
+}
+
+// 目录块序列化后的字节数
+fn serialized_len(dir: &Directory) -> Result<u64, String> {
+    bincode::serialized_size(dir).map_err(|e| e.to_string())
+}
+
+// 名字不合法时返回 InvalidPath，在分配任何资源之前调用
+fn check_name(name: &str) -> Result<(), String> {
+    directory::validate_name(name).map_err(|reason| {
+        FileSystemError::InvalidPath(format!("{} ({})", name, reason)).to_string()
+    })
+}
+
+// 目录项统一排序：目录在前，文件和符号链接在后，同一类按名字排序
+fn sort_entries(entries: &mut [DirEntry]) {
+    entries.sort_by(|a, b| {
+        a.entry_type
+            .sort_rank()
+            .cmp(&b.entry_type.sort_rank())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::fs::inode_table::{INODES_PER_BLOCK, INODE_SLOT_SIZE};
+
+    // 每个测试一块独立的内存磁盘，已格式化
+    pub(crate) fn test_fs() -> FileSystem {
+        FileSystem::new_in_memory().unwrap()
+    }
+
+    #[test]
+    fn test_exists() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "a").unwrap();
+        fs.create_dir("/a", "b").unwrap();
+        fs.create_or_write_file("/a/b", "c.txt", b"hello").unwrap();
+
+        assert!(fs.exists("/"));
+        assert!(fs.exists("/a"));
+        assert!(fs.exists("/a/b/c.txt"));
+        assert!(!fs.exists("/a/x"));
+        assert!(!fs.exists("/missing/a/b/c"));
+        // 文件不能作为中间路径分量
+        assert!(!fs.exists("/a/b/c.txt/d"));
+
+        assert!(fs.is_dir("/") && fs.is_dir("/a/b"));
+        assert!(!fs.is_dir("/a/b/c.txt") && !fs.is_dir("/a/x"));
+        assert!(fs.is_file("/a/b/c.txt"));
+        assert!(!fs.is_file("/a") && !fs.is_file("/a/x"));
+    }
+
+    #[test]
+    fn test_depth_report() {
+        let mut fs = test_fs();
+        assert_eq!(fs.depth_report().unwrap().max_depth, 0);
+
+        fs.create_dir("/", "a").unwrap();
+        fs.create_dir("/a", "b").unwrap();
+        fs.create_dir("/a/b", "c").unwrap();
+        fs.create_dir("/", "x").unwrap();
+        fs.create_or_write_file("/a/b/c", "file.txt", b"deep")
+            .unwrap();
+
+        let report = fs.depth_report().unwrap();
+        assert_eq!(report.max_depth, 3);
+        assert_eq!(report.longest_path, "/a/b/c/file.txt");
+    }
+
+    #[test]
+    fn test_copy_and_verify() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "src.txt", b"important data")
+            .unwrap();
+
+        fs.copy_file("/src.txt", "/dst.txt").unwrap();
+        assert!(fs.verify_copy("/src.txt", "/dst.txt").is_ok());
+        assert_eq!(fs.read_file("/", "dst.txt").unwrap(), b"important data");
+
+        // 目标已存在时拒绝覆盖
+        assert!(fs.copy_file("/src.txt", "/dst.txt").is_err());
+
+        // 模拟写入出错：目标内容与源不一致
+        fs.write_file("/dst.txt", b"important dat!").unwrap();
+        let err = fs.verify_copy("/src.txt", "/dst.txt").unwrap_err();
+        assert!(err.contains("differ at byte 13"));
+
+        fs.write_file("/dst.txt", b"short").unwrap();
+        assert!(fs.verify_copy("/src.txt", "/dst.txt").is_err());
+    }
+
+    #[test]
+    fn test_walk_preorder() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "a").unwrap();
+        fs.create_dir("/a", "b").unwrap();
+        fs.create_or_write_file("/a/b", "f2", b"2").unwrap();
+        fs.create_or_write_file("/a", "f1", b"1").unwrap();
+        fs.create_or_write_file("/", "z.txt", b"z").unwrap();
+        fs.create_dir("/", "c").unwrap();
+
+        let paths: Vec<(String, InodeType)> = fs.walk("/").unwrap().collect();
+        let expected = vec![
+            ("/a", InodeType::Directory),
+            ("/a/b", InodeType::Directory),
+            ("/a/b/f2", InodeType::File),
+            ("/a/f1", InodeType::File),
+            ("/c", InodeType::Directory),
+            ("/z.txt", InodeType::File),
+        ];
+        assert_eq!(
+            paths,
+            expected
+                .into_iter()
+                .map(|(p, t)| (p.to_string(), t))
+                .collect::<Vec<_>>()
+        );
+
+        // 子树遍历只包含该目录下的路径
+        let sub: Vec<String> = fs.walk("/a").unwrap().map(|(p, _)| p).collect();
+        assert_eq!(sub, vec!["/a/b", "/a/b/f2", "/a/f1"]);
+
+        // 不存在的根目录是错误，不是空遍历
+        assert!(fs.walk("/missing").is_err());
+    }
+
+    #[test]
+    fn test_diff_images() {
+        let mut a = test_fs();
+        let mut b = test_fs();
+        for fs in [&mut a, &mut b] {
+            fs.create_dir("/", "docs").unwrap();
+            fs.create_or_write_file("/docs", "same.txt", b"same")
+                .unwrap();
+            fs.create_or_write_file("/docs", "edit.txt", b"before")
+                .unwrap();
+        }
+        assert!(a.diff(&b).is_empty());
+
+        b.create_or_write_file("/docs", "added.txt", b"new")
+            .unwrap();
+        b.write_file("/docs/edit.txt", b"after!").unwrap();
+
+        let diff = a.diff(&b);
+        assert!(diff.only_in_a.is_empty());
+        assert_eq!(diff.only_in_b, vec!["/docs/added.txt"]);
+        assert_eq!(diff.changed, vec!["/docs/edit.txt"]);
+    }
+
+    #[test]
+    fn test_format_custom_sizes() {
+        let mut fs = test_fs();
+        fs.format_with(&FormatOptions {
+            total_inodes: 8192,
+            total_blocks: 8192,
+            ..FormatOptions::default()
+        })
+        .unwrap();
+        let stats = fs.statfs();
+        assert_eq!((stats.total_inodes, stats.total_blocks), (8192, 8192));
+        fs.create_or_write_file("/", "a.txt", b"hi").unwrap();
+        fs.sync().unwrap();
+        fs.mount().unwrap();
+        assert_eq!(fs.super_block.total_inodes, 8192);
+        assert_eq!(fs.read_path("/a.txt").unwrap(), b"hi");
+
+        // 放不下元数据或超出磁盘的布局被拒绝，且不破坏现有文件系统
+        for (inodes, blocks) in [(8192, 10), (0, 8192), (4096, BLOCK_COUNT as u64 + 1)] {
+            let err = fs
+                .format_with(&FormatOptions {
+                    total_inodes: inodes,
+                    total_blocks: blocks,
+                    ..FormatOptions::default()
+                })
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        assert!(fs.exists("/a.txt"));
+    }
+
+    #[test]
+    fn test_format_fill_and_verify() {
+        let mut fs = test_fs();
+        fs.format_with(&FormatOptions {
+            fill_byte: Some(0xAA),
+            ..FormatOptions::default()
+        })
+        .unwrap();
+
+        // 未使用的数据块应为填充字节
+        let mut block_buf = [0u8; 4096];
+        fs.disk
+            .read_block(fs.data_area.start_block + 10, &mut block_buf)
+            .unwrap();
+        assert!(block_buf.iter().all(|&b| b == 0xAA));
+        assert!(fs.exists("/"));
+
+        // 模拟根目录块写坏：回读校验必须失败
+        let root_block = fs.inode_table.get_inode(0).unwrap().direct_blocks[0];
+        fs.disk
+            .write_block(fs.data_area.start_block + root_block, &[0u8; 4096])
+            .unwrap();
+        assert!(fs.verify_format().is_err());
+    }
+
+    #[test]
+    fn test_dir_dump_insertion_order() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "d").unwrap();
+        fs.create_or_write_file("/d", "b", b"").unwrap();
+        fs.create_or_write_file("/d", "a", b"").unwrap();
+
+        let dumps = fs.dir_dump("/d").unwrap();
+        assert_eq!(dumps.len(), 1);
+        let names: Vec<&str> = dumps[0].entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec![".", "..", "b", "a"]);
+        assert!(dumps[0].byte_len > 0);
+
+        assert!(fs.dir_dump("/d/a").is_err());
+    }
+
+    #[test]
+    fn test_multi_block_round_trip() {
+        let mut fs = test_fs();
+        let content: Vec<u8> = (0..9000u32).map(|i| (i % 251) as u8).collect();
+        fs.create_or_write_file("/", "big.bin", &content).unwrap();
+
+        let inode = fs.stat("/", "big.bin").unwrap();
+        assert_eq!(inode.size, 9000);
+        assert_eq!(fs.block_count(&inode).unwrap(), 3);
+        assert_eq!(fs.read_file("/", "big.bin").unwrap(), content);
+
+        // 尾块不足 4096 字节也要原样返回
+        fs.write_file("/big.bin", &content[..4097]).unwrap();
+        assert_eq!(fs.read_file("/", "big.bin").unwrap(), &content[..4097]);
+    }
+
+    #[test]
+    fn test_indirect_block_round_trip() {
+        let mut fs = test_fs();
+        fs.create_file("/", "medium.bin").unwrap();
+        let free_before = fs.data_bitmap.free_blocks;
+
+        // 约 100KB：12 个直接块 + 13 个间接块引用的数据块
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 256) as u8).collect();
+        fs.write_file("/medium.bin", &content).unwrap();
+
+        let inode = fs.stat("/", "medium.bin").unwrap();
+        assert!(inode.indirect_block.is_some());
+        assert_eq!(fs.read_file("/", "medium.bin").unwrap(), content);
+        // 25 个数据块 + 1 个间接块
+        assert_eq!(fs.data_bitmap.free_blocks, free_before - 26);
+        assert_eq!(fs.block_count(&inode).unwrap(), 26);
+        assert_eq!(
+            fs.disk_usage("/medium.bin").unwrap(),
+            26 * BLOCK_SIZE as u64
+        );
+
+        // 覆盖写为小文件后，间接块及其数据块全部释放
+        fs.write_file("/medium.bin", b"x").unwrap();
+        assert!(fs.stat("/", "medium.bin").unwrap().indirect_block.is_none());
+        assert_eq!(fs.data_bitmap.free_blocks, free_before - 1);
+        assert_eq!(fs.read_file("/", "medium.bin").unwrap(), b"x");
+    }
+
+    #[test]
+    fn test_footprint_matches_structures() {
+        let mut fs = test_fs();
+        assert!(fs.mount_time.is_none());
+        fs.mount().unwrap();
+        assert!(fs.mount_time.is_some());
+
+        // 数据区不再整体驻留内存，挂载后没有缓存块
+        let footprint = fs.footprint();
+        let data_blocks = fs.super_block.total_blocks - fs.super_block.data_block_start;
+        assert_eq!(footprint.data_area, 0);
+        assert_eq!(
+            footprint.inode_bitmap,
+            fs.super_block.total_inodes.div_ceil(8) as usize
+        );
+        assert_eq!(footprint.data_bitmap, data_blocks.div_ceil(8) as usize);
+        assert!(
+            footprint.inode_table
+                >= fs.super_block.total_inodes as usize * std::mem::size_of::<Inode>()
+        );
+        assert_eq!(
+            footprint.total(),
+            footprint.data_area
+                + footprint.inode_table
+                + footprint.inode_bitmap
+                + footprint.data_bitmap
+        );
+    }
+
+    #[test]
+    fn test_incremental_inode_table_sync() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"hello").unwrap();
+        fs.sync().unwrap();
+        assert_eq!(fs.inode_table.sync(&fs.disk).unwrap(), 0);
+
+        // 只改动一个 inode 时只重写它所在的那个块
+        fs.chmod("/a.txt", 0o600).unwrap();
+        assert_eq!(fs.inode_table.sync(&fs.disk).unwrap(), 1);
+
+        // 落在不同块里的两个 inode 各写一次
+        let far = INODES_PER_BLOCK * 3;
+        fs.inode_table.inodes[far as usize] = Inode::new(InodeType::File, 0, 0, 0o644);
+        fs.inode_table.mark_dirty(far);
+        fs.chmod("/a.txt", 0o644).unwrap();
+        assert_eq!(fs.inode_table.sync(&fs.disk).unwrap(), 2);
+
+        fs.mount().unwrap();
+        assert_eq!(
+            fs.inode_table.inodes.len() as u64,
+            fs.super_block.total_inodes
+        );
+        assert_eq!(fs.inode_table.allocated_inodes, 3);
+        assert_eq!(fs.stat_path("/a.txt").unwrap().permissions, 0o644);
+        assert_eq!(fs.read_file("/", "a.txt").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_full_inode_table_fits_its_region() {
+        let mut fs = test_fs();
+        // 定长 id 让每个 inode 的序列化大小固定，不随 UUID 文本长度变化
+        assert_eq!(Inode::max_serialized_size(), 189);
+        assert!(Inode::max_serialized_size() <= INODE_SLOT_SIZE as u64);
+
+        // 所有 inode 都已分配时，整张表仍不能越过数据区起点
+        for id in 0..fs.inode_table.inodes.len() {
+            fs.inode_table.inodes[id] = Inode {
+                indirect_block: Some(1),
+                double_indirect_block: Some(1),
+                ..Inode::new(InodeType::File, 0, 0, 0o644)
+            };
+            fs.inode_table.mark_dirty(id as u64);
+        }
+        let blocks = fs.inode_table.sync(&fs.disk).unwrap();
+        let region = fs.super_block.data_block_start - fs.super_block.inode_table_start;
+        assert!(blocks <= region);
+
+        // 超出预留区域时拒绝写入
+        fs.inode_table.total_inodes = 1;
+        fs.inode_table.mark_dirty(INODES_PER_BLOCK);
+        assert!(fs.inode_table.sync(&fs.disk).is_err());
+    }
+
+    #[test]
+    fn test_delete_frees_inode_slot() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"hello").unwrap();
+        fs.create_dir("/", "d").unwrap();
+        let file_id = fs.find_inode("/a.txt").unwrap();
+        let dir_id = fs.find_inode("/d").unwrap();
+        assert_eq!(fs.inode_table.allocated_inodes, 3);
+
+        fs.delete_file("/", "a.txt").unwrap();
+        fs.delete_dir("/", "d").unwrap();
+        assert_eq!(fs.inode_table.inodes[file_id as usize], Inode::empty());
+        assert_eq!(fs.inode_table.inodes[dir_id as usize], Inode::empty());
+        assert_eq!(fs.inode_table.allocated_inodes, 1);
+    }
+
+    #[test]
+    fn test_delete_returns_free_blocks() {
+        let mut fs = test_fs();
+        let before = fs.super_block.free_blocks;
+
+        let content = vec![7u8; BLOCK_SIZE * 3 + 1];
+        fs.create_or_write_file("/", "big.bin", &content).unwrap();
+        fs.create_dir("/", "d").unwrap();
+        assert_eq!(fs.super_block.free_blocks, before - 5);
+
+        fs.delete_file("/", "big.bin").unwrap();
+        fs.delete_dir("/", "d").unwrap();
+        assert_eq!(fs.super_block.free_blocks, before);
+    }
+
+    #[test]
+    fn test_mount_rejects_bad_super_block() {
+        let mut fs = test_fs();
+        fs.sync().unwrap();
+
+        let mut bad = SuperBlock {
+            magic: 0x1234,
+            ..SuperBlock::default()
+        };
+        let mut block = [0u8; BLOCK_SIZE];
+        let bytes = bincode::serialize(&bad).unwrap();
+        block[..bytes.len()].copy_from_slice(&bytes);
+        fs.disk.write_block(0, &block).unwrap();
+
+        let err = fs.mount().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("magic"));
+
+        bad.magic = super_block::MAGIC;
+        bad.block_size = 512;
+        let bytes = bincode::serialize(&bad).unwrap();
+        block[..bytes.len()].copy_from_slice(&bytes);
+        fs.disk.write_block(0, &block).unwrap();
+        assert!(fs.mount().unwrap_err().to_string().contains("block size"));
+
+        // 区域重叠的布局同样拒绝挂载
+        bad.block_size = BLOCK_SIZE as u64;
+        bad.inode_table_start = bad.data_block_start + 1;
+        let bytes = bincode::serialize(&bad).unwrap();
+        block[..bytes.len()].copy_from_slice(&bytes);
+        fs.disk.write_block(0, &block).unwrap();
+        assert!(fs.mount().unwrap_err().to_string().contains("overlapping"));
+
+        // 全零块同样不是合法的超级块
+        fs.disk.write_block(0, &[0u8; BLOCK_SIZE]).unwrap();
+        assert!(fs.mount().is_err());
+    }
+
+    #[test]
+    fn test_chmod() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "notes.txt", b"hi").unwrap();
+
+        fs.chmod("/notes.txt", 0o600).unwrap();
+        assert_eq!(fs.stat("/", "notes.txt").unwrap().permissions, 0o600);
+        assert!(fs.super_block.dirty);
+
+        assert!(fs.chmod("/notes.txt", 0o1777).is_err());
+        assert!(fs.chmod("/missing", 0o600).is_err());
+        assert_eq!(fs.stat("/", "notes.txt").unwrap().permissions, 0o600);
+    }
+
+    #[test]
+    fn test_rename_and_move() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "a").unwrap();
+        fs.create_dir("/", "b").unwrap();
+        fs.create_or_write_file("/a", "x.txt", b"data").unwrap();
+        let file_id = fs.find_inode("/a/x.txt").unwrap();
+
+        // 同一目录内改名
+        fs.rename("/a/x.txt", "/a/y.txt").unwrap();
+        assert!(!fs.exists("/a/x.txt"));
+        assert_eq!(fs.find_inode("/a/y.txt").unwrap(), file_id);
+
+        // 跨目录移动，不复制数据
+        fs.rename("/a/y.txt", "/b/y.txt").unwrap();
+        assert_eq!(fs.find_inode("/b/y.txt").unwrap(), file_id);
+        assert_eq!(fs.read_file("/b", "y.txt").unwrap(), b"data");
+
+        // 目标已存在时拒绝
+        fs.create_or_write_file("/b", "z.txt", b"").unwrap();
+        assert!(fs.rename("/b/y.txt", "/b/z.txt").is_err());
+        assert!(fs.exists("/b/y.txt"));
+
+        // 移动目录后 ".." 指向新父目录
+        let b_id = fs.find_inode("/b").unwrap();
+        fs.rename("/a", "/b/a").unwrap();
+        let a_id = fs.find_inode("/b/a").unwrap();
+        let dotdot = fs
+            .dir_entries(a_id)
+            .unwrap()
+            .into_iter()
+            .find(|e| e.name == "..")
+            .unwrap();
+        assert_eq!(dotdot.inode_index as u64, b_id);
+
+        assert!(fs.rename("/b", "/b/a/b").is_err());
+    }
+
+    #[test]
+    fn test_delete_dir_ignores_only_dot_entries() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "d").unwrap();
+        fs.create_file("/d", "a").unwrap();
+        fs.create_file("/d", "b").unwrap();
+
+        // 抹掉点目录项后只剩两个普通文件，条目数恰好为 2，仍然不是空目录
+        let dir_id = fs.find_inode("/d").unwrap();
+        let block = fs.dir_blocks(dir_id).unwrap()[0];
+        let mut dir = fs.load_dir_block(block).unwrap();
+        dir.remove(".");
+        dir.remove("..");
+        fs.store_dir_block(block, &dir).unwrap();
+        assert_eq!(fs.delete_dir("/", "d").unwrap_err(), "Directory not empty");
+        assert!(fs.exists("/d/a"));
+
+        // 空但缺少点目录项的目录视为损坏
+        fs.create_dir("/", "e").unwrap();
+        let dir_id = fs.find_inode("/e").unwrap();
+        let block = fs.dir_blocks(dir_id).unwrap()[0];
+        let mut dir = fs.load_dir_block(block).unwrap();
+        dir.remove("..");
+        fs.store_dir_block(block, &dir).unwrap();
+        assert!(fs.delete_dir("/", "e").unwrap_err().contains("Corrupted"));
+
+        fs.create_dir("/", "empty").unwrap();
+        fs.delete_dir("/", "empty").unwrap();
+        assert!(!fs.exists("/empty"));
+    }
+
+    #[test]
+    fn test_remove_dir_recursive() {
+        let mut fs = test_fs();
+        let free_blocks = fs.super_block.free_blocks;
+        let free_inodes = fs.super_block.free_inode;
+
+        fs.create_dir("/", "a").unwrap();
+        fs.create_dir("/a", "b").unwrap();
+        fs.create_dir("/a/b", "c").unwrap();
+        fs.create_or_write_file("/a", "x.txt", b"x").unwrap();
+        fs.create_or_write_file("/a/b/c", "y.txt", &vec![1u8; BLOCK_SIZE * 2])
+            .unwrap();
+
+        fs.remove_dir_recursive("/a").unwrap();
+        assert!(!fs.exists("/a"));
+        assert_eq!(fs.list_dir("/").unwrap().len(), 2);
+        assert_eq!(fs.super_block.free_blocks, free_blocks);
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+        assert_eq!(fs.inode_table.allocated_inodes, 1);
+
+        assert!(fs.remove_dir_recursive("/").is_err());
+    }
+
+    #[test]
+    fn test_create_dir_all() {
+        let mut fs = test_fs();
+        let id = fs.create_dir_all("/a/b/c").unwrap();
+        assert_eq!(fs.find_inode("/a/b/c").unwrap(), id);
+
+        // 已存在时静默成功
+        assert_eq!(fs.create_dir_all("/a/b/c").unwrap(), id);
+        fs.create_dir_all("/a/b/d").unwrap();
+        assert_eq!(fs.list_dir("/a/b").unwrap().len(), 4);
+
+        fs.create_or_write_file("/a", "f", b"").unwrap();
+        let err = fs.create_dir_all("/a/f/g").unwrap_err();
+        assert!(err.contains("Expected a directory"), "{}", err);
+    }
+
+    #[test]
+    fn test_directory_spans_multiple_blocks() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "many").unwrap();
+        for i in 0..500 {
+            fs.create_file("/many", &format!("file_{:03}.txt", i))
+                .unwrap();
+        }
+
+        let dir_id = fs.find_inode("/many").unwrap();
+        assert!(fs.dir_dump("/many").unwrap().len() > 1);
+        assert!(fs.inode_table.get_inode(dir_id).unwrap().size > BLOCK_SIZE as u64);
+
+        let names: Vec<String> = fs
+            .list_dir("/many")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .filter(|n| n != "." && n != "..")
+            .collect();
+        assert_eq!(names.len(), 500);
+        assert_eq!(names[0], "file_000.txt");
+        assert_eq!(names[499], "file_499.txt");
+
+        // read_dir 按存储顺序跨块产出全部条目
+        let stored: Vec<String> = fs
+            .read_dir("/many")
+            .unwrap()
+            .map(|e| e.unwrap().name)
+            .collect();
+        assert_eq!(stored.len(), 502);
+        assert_eq!(stored[..3], [".", "..", "file_000.txt"]);
+        assert_eq!(stored[501], "file_499.txt");
+        assert!(fs.read_dir("/many/file_000.txt").is_err());
+
+        // 后面块里的条目同样可以查找、删除
+        assert!(fs.exists("/many/file_499.txt"));
+        fs.delete_file("/many", "file_499.txt").unwrap();
+        assert!(!fs.exists("/many/file_499.txt"));
+        assert!(fs.create_file("/many", "file_000.txt").is_err());
+    }
+
+    #[test]
+    fn test_hard_links() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "d").unwrap();
+        fs.create_or_write_file("/", "a.txt", b"shared").unwrap();
+        let id = fs.find_inode("/a.txt").unwrap();
+
+        fs.link("/a.txt", "/b.txt").unwrap();
+        fs.link("/a.txt", "/d/c.txt").unwrap();
+        assert_eq!(fs.find_inode("/d/c.txt").unwrap(), id);
+        assert_eq!(fs.stat("/", "a.txt").unwrap().link_count, 3);
+        assert!(fs.link("/d", "/e").is_err());
+
+        fs.delete_file("/", "a.txt").unwrap();
+        assert_eq!(fs.read_file("/", "b.txt").unwrap(), b"shared");
+        assert_eq!(fs.stat("/", "b.txt").unwrap().link_count, 2);
+
+        fs.delete_file("/", "b.txt").unwrap();
+        assert_eq!(fs.read_file("/d", "c.txt").unwrap(), b"shared");
+
+        // 最后一个名字删除后 inode 才被释放
+        fs.delete_file("/d", "c.txt").unwrap();
+        assert_eq!(fs.inode_table.inodes[id as usize], Inode::empty());
+    }
+
+    #[test]
+    fn test_read_at_in_chunks() {
+        let mut fs = test_fs();
+        let content: Vec<u8> = (0..BLOCK_SIZE * 2 + 250).map(|i| (i % 251) as u8).collect();
+        fs.create_or_write_file("/", "data.bin", &content).unwrap();
+
+        let mut fh = fs.open("/data.bin", OpenFlags::READ).unwrap();
+        let mut read_back = Vec::new();
+        let mut buf = [0u8; 100];
+        loop {
+            let n = fs.read_at(&mut fh, &mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            read_back.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(read_back, content);
+        assert_eq!(fh.offset, content.len() as u64);
+
+        let mut fh = fs.open("/data.bin", OpenFlags::WRITE).unwrap();
+        assert!(fs.read_at(&mut fh, &mut buf).is_err());
+    }
+
+    #[test]
+    fn test_write_at_append_and_overwrite() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "log.txt", b"head:").unwrap();
+
+        let mut fh = fs
+            .open("/log.txt", OpenFlags::WRITE | OpenFlags::APPEND)
+            .unwrap();
+        fs.write_at(&mut fh, b"first,").unwrap();
+        let tail = vec![b'x'; BLOCK_SIZE];
+        fs.write_at(&mut fh, &tail).unwrap();
+
+        let mut expected = b"head:first,".to_vec();
+        expected.extend_from_slice(&tail);
+        assert_eq!(fs.read_file("/", "log.txt").unwrap(), expected);
+
+        // 非 APPEND 写入只覆盖对应区间，不截断
+        let mut fh = fs.open("/log.txt", OpenFlags::WRITE).unwrap();
+        fs.write_at(&mut fh, b"HEAD").unwrap();
+        expected[..4].copy_from_slice(b"HEAD");
+        assert_eq!(fs.read_file("/", "log.txt").unwrap(), expected);
+
+        let mut fh = fs.open("/log.txt", OpenFlags::READ).unwrap();
+        assert!(fs.write_at(&mut fh, b"nope").is_err());
+    }
+
+    #[test]
+    fn test_seek_then_read() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "abc.txt", b"0123456789")
+            .unwrap();
+        let mut fh = fs.open("/abc.txt", OpenFlags::READ).unwrap();
+        let mut buf = [0u8; 3];
+
+        assert_eq!(fs.seek(&mut fh, SeekFrom::Start(4)).unwrap(), 4);
+        fs.read_at(&mut fh, &mut buf).unwrap();
+        assert_eq!(&buf, b"456");
+
+        assert_eq!(fs.seek(&mut fh, SeekFrom::Current(-5)).unwrap(), 2);
+        assert_eq!(fs.seek(&mut fh, SeekFrom::End(-3)).unwrap(), 7);
+        fs.read_at(&mut fh, &mut buf).unwrap();
+        assert_eq!(&buf, b"789");
+
+        // 越过 EOF 合法，读出 0 字节；负位置报错且 offset 不变
+        assert_eq!(fs.seek(&mut fh, SeekFrom::End(5)).unwrap(), 15);
+        assert_eq!(fs.read_at(&mut fh, &mut buf).unwrap(), 0);
+        assert!(fs.seek(&mut fh, SeekFrom::Current(-100)).is_err());
+        assert_eq!(fh.offset, 15);
+
+        // 在 EOF 之后写入，中间补 0
+        let mut fh = fs.open("/abc.txt", OpenFlags::WRITE).unwrap();
+        fs.seek(&mut fh, SeekFrom::End(2)).unwrap();
+        fs.write_at(&mut fh, b"!").unwrap();
+        assert_eq!(fs.read_file("/", "abc.txt").unwrap(), b"0123456789\0\0!");
+    }
+
+    #[test]
+    fn test_statfs_tracks_allocations() {
+        let mut fs = test_fs();
+        let before = fs.statfs();
+        assert_eq!(before.block_size, BLOCK_SIZE as u64);
+        // 刚格式化：只有元数据块、预留的 0 号块和根目录块被占用
+        assert_eq!(before.used_blocks(), before.metadata_blocks + 2);
+        assert_eq!(before.used_inodes(), 1);
+
+        fs.create_or_write_file("/", "big.bin", &vec![1u8; BLOCK_SIZE * 3])
+            .unwrap();
+        let after = fs.statfs();
+        assert_eq!(after.used_blocks(), before.used_blocks() + 3);
+        assert_eq!(after.used_inodes(), 2);
+        assert_eq!(
+            after.used_blocks() - after.metadata_blocks,
+            fs.data_bitmap.total_blocks - fs.data_bitmap.free_blocks
+        );
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let mut fs = test_fs();
+        let block = BLOCK_SIZE as u64;
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create_or_write_file("/a", "one.bin", &vec![1u8; BLOCK_SIZE + 1])
+            .unwrap();
+        fs.create_or_write_file("/a/b", "small.txt", b"hi").unwrap();
+        fs.create_or_write_file("/a/b", "empty", b"").unwrap();
+
+        assert_eq!(fs.disk_usage("/a/one.bin").unwrap(), 2 * block);
+        assert_eq!(fs.disk_usage("/a/b").unwrap(), 2 * block);
+        // a 和 b 各占一个目录块
+        assert_eq!(fs.disk_usage("/a").unwrap(), 5 * block);
+
+        // 硬链接不重复计算
+        fs.link("/a/one.bin", "/a/b/again.bin").unwrap();
+        assert_eq!(fs.disk_usage("/a").unwrap(), 5 * block);
+        assert!(fs.disk_usage("/missing").is_err());
+    }
+
+    #[test]
+    fn test_data_area_reads_through_disk() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"on demand").unwrap();
+        assert!(fs.footprint().data_area > 0);
+        assert!(fs.data_area.dirty_count() > 0);
+
+        fs.sync().unwrap();
+        assert_eq!(fs.data_area.dirty_count(), 0);
+        assert!(fs.footprint().data_area <= data_area::READ_CACHE_BLOCKS * BLOCK_SIZE);
+        assert_eq!(fs.read_file("/", "a.txt").unwrap(), b"on demand");
+
+        // 超过写回上限时自动刷盘，缓存不会无限增长
+        let limit = data_area::WRITE_BACK_LIMIT as u64;
+        for i in 0..limit + 10 {
+            fs.data_area.write_block(1000 + i, &[i as u8]).unwrap();
+        }
+        assert!(
+            fs.footprint().data_area
+                <= (data_area::WRITE_BACK_LIMIT + data_area::READ_CACHE_BLOCKS) * BLOCK_SIZE
+        );
+
+        fs.sync().unwrap();
+        fs.mount().unwrap();
+        assert_eq!(
+            fs.data_area.read_block(1000 + limit + 5).unwrap()[0],
+            (limit + 5) as u8
+        );
+        assert_eq!(fs.read_file("/", "a.txt").unwrap(), b"on demand");
+    }
+
+    #[test]
+    fn test_read_updates_atime() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "d").unwrap();
+        fs.create_or_write_file("/d", "a.txt", b"x").unwrap();
+        let file_id = fs.find_inode("/d/a.txt").unwrap() as usize;
+        let dir_id = fs.find_inode("/d").unwrap() as usize;
+
+        fs.inode_table.inodes[file_id].atime = 0;
+        fs.inode_table.inodes[dir_id].atime = 0;
+        fs.super_block.dirty = false;
+
+        fs.read_file("/d", "a.txt").unwrap();
+        assert!(fs.stat("/d", "a.txt").unwrap().atime > 0);
+        assert!(fs.super_block.dirty);
+        fs.list_dir("/d").unwrap();
+        assert!(fs.inode_table.inodes[dir_id].atime > 0);
+
+        // noatime 下读取不改变访问时间
+        fs.noatime = true;
+        fs.inode_table.inodes[file_id].atime = 0;
+        fs.read_file("/d", "a.txt").unwrap();
+        assert_eq!(fs.stat("/d", "a.txt").unwrap().atime, 0);
+    }
+
+    #[test]
+    fn test_copy_keeps_owner_and_rolls_back() {
+        let mut fs = test_fs();
+        let content = vec![5u8; BLOCK_SIZE * 2];
+        fs.create_or_write_file("/", "src.bin", &content).unwrap();
+        fs.chmod("/src.bin", 0o600).unwrap();
+        let src_id = fs.find_inode("/src.bin").unwrap() as usize;
+        fs.inode_table.inodes[src_id].uid = 42;
+        fs.inode_table.inodes[src_id].gid = 7;
+
+        let dst_id = fs.copy_file("/src.bin", "/dst.bin").unwrap();
+        let dst = fs.stat("/", "dst.bin").unwrap();
+        assert_eq!((dst.permissions, dst.uid, dst.gid), (0o600, 42, 7));
+        assert_ne!(dst.id, fs.inode_table.inodes[src_id].id);
+        assert_ne!(
+            dst.direct_blocks[0],
+            fs.inode_table.inodes[src_id].direct_blocks[0]
+        );
+        assert_eq!(fs.read_file("/", "dst.bin").unwrap(), content);
+        assert_ne!(dst_id as usize, src_id);
+
+        // 只剩一个空闲块：复制两块的文件应失败且不留下痕迹
+        while fs.data_bitmap.free_blocks > 1 {
+            fs.data_bitmap.alloc().unwrap();
+        }
+        let free_inodes = fs.super_block.free_inode;
+        let err = fs.copy_file("/src.bin", "/full.bin").unwrap_err();
+        assert_eq!(err, FileSystemError::DiskFull.to_string());
+        assert!(!fs.exists("/full.bin"));
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+
+        while fs.inode_bitmap.alloc().is_some() {}
+        let err = fs.copy_file("/src.bin", "/noinode.bin").unwrap_err();
+        assert_eq!(err, FileSystemError::InodeFull.to_string());
+    }
+
+    #[test]
+    fn test_copy_rolls_back_after_partial_write() {
+        let mut fs = small_fs();
+        let content = vec![5u8; 2 * fs.data_area.payload_size()];
+        fs.create_or_write_file("/", "src.bin", &content).unwrap();
+
+        // 预检查按两块放行，但目标目录项先扩出一块，写内容时就不够了
+        fill_dir_block(&mut fs, "/p");
+        fill_disk(&mut fs, 2);
+        let free_inodes = fs.super_block.free_inode;
+
+        let err = fs.copy_file("/src.bin", "/p/d0000").unwrap_err();
+        assert_eq!(err, FileSystemError::DiskFull.to_string());
+        assert!(!fs.exists("/p/d0000"));
+        assert_eq!(fs.super_block.free_blocks, 2);
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+        assert!(fs.fsck(false).is_clean());
+    }
+
+    #[test]
+    fn test_touch() {
+        let mut fs = test_fs();
+        fs.touch("/new.txt").unwrap();
+        assert_eq!(fs.stat("/", "new.txt").unwrap().size, 0);
+
+        fs.create_or_write_file("/", "old.txt", b"keep me").unwrap();
+        let id = fs.find_inode("/old.txt").unwrap() as usize;
+        let blocks = fs.inode_table.inodes[id].direct_blocks;
+        fs.inode_table.inodes[id].mtime = 0;
+        fs.inode_table.inodes[id].ctime = 0;
+
+        fs.touch("/old.txt").unwrap();
+        let inode = fs.stat("/", "old.txt").unwrap();
+        assert!(inode.mtime > 0 && inode.ctime > 0);
+        assert_eq!(inode.direct_blocks, blocks);
+        assert_eq!(fs.read_file("/", "old.txt").unwrap(), b"keep me");
+        assert!(fs.touch("/missing/x").is_err());
+    }
+
+    #[test]
+    fn test_path_variants() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "docs").unwrap();
+        fs.create_or_write_file("/docs", "a.txt", b"hello").unwrap();
+
+        assert_eq!(fs.read_path("/docs/a.txt").unwrap(), b"hello");
+        assert_eq!(fs.read_path("docs/a.txt").unwrap(), b"hello");
+        assert_eq!(
+            fs.stat_path("/docs/a.txt").unwrap().id,
+            fs.stat("/docs", "a.txt").unwrap().id
+        );
+        assert_eq!(fs.stat_path("/").unwrap().inode_type, InodeType::Directory);
+        assert!(fs.stat_path("/docs/missing").is_err());
+    }
+
+    #[test]
+    fn test_visit_tree() {
+        let mut fs = test_fs();
+        fs.create_dir_all("/a/b/c").unwrap();
+        fs.create_or_write_file("/", "z.txt", b"").unwrap();
+        fs.create_or_write_file("/a", "f.txt", b"").unwrap();
+
+        let mut seen = Vec::new();
+        fs.visit_tree("/", MAX_WALK_DEPTH, &mut |depth, entry| {
+            seen.push((depth, entry.name.clone()))
+        })
+        .unwrap();
+        let expected = [(0, "a"), (1, "b"), (2, "c"), (1, "f.txt"), (0, "z.txt")];
+        let expected: Vec<_> = expected.iter().map(|(d, n)| (*d, n.to_string())).collect();
+        assert_eq!(seen, expected);
+
+        // 限制深度后不再展开更深的目录
+        let mut count = 0;
+        fs.visit_tree("/", 2, &mut |_, _| count += 1).unwrap();
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn test_write_outcome() {
+        let mut fs = test_fs();
+        let created = fs.create_or_write_file("/", "a.txt", b"one").unwrap();
+        assert!(matches!(created, WriteOutcome::Created(_)));
+
+        let overwrote = fs.create_or_write_file("/", "a.txt", b"two").unwrap();
+        assert_eq!(overwrote, WriteOutcome::Overwrote(created.inode_id()));
+        assert_eq!(fs.read_file("/", "a.txt").unwrap(), b"two");
+    }
+
+    #[test]
+    fn test_create_rejects_invalid_names() {
+        let mut fs = test_fs();
+        let free_inodes = fs.super_block.free_inode;
+        let long = "n".repeat(directory::MAX_NAME_LEN + 1);
+
+        for bad in ["", ".", "..", "a/b", "x\0y", long.as_str()] {
+            let err = fs.create_file("/", bad).unwrap_err();
+            assert!(err.starts_with("Invalid path"), "{}", err);
+            assert!(fs.create_dir("/", bad).is_err());
+        }
+        // 被拒绝的名字不会占用 inode
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+        assert_eq!(fs.list_dir("/").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let mut fs = test_fs();
+        fs.checksums = true;
+        fs.format().unwrap();
+        assert_eq!(fs.data_area.payload_size(), BLOCK_SIZE - 4);
+
+        // 跨越有效载荷边界的内容也能原样读回
+        let content: Vec<u8> = (0..BLOCK_SIZE * 2).map(|i| (i % 251) as u8).collect();
+        fs.create_or_write_file("/", "data.bin", &content).unwrap();
+        fs.sync().unwrap();
+        fs.mount().unwrap();
+        assert_eq!(fs.read_path("/data.bin").unwrap(), content);
+
+        // 直接在磁盘上翻转一个字节，重新挂载后读取应报告损坏
+        let block = fs.stat_path("/data.bin").unwrap().direct_blocks[0];
+        let mut buf = [0u8; BLOCK_SIZE];
+        let on_disk = fs.data_area.start_block + block;
+        fs.disk.read_block(on_disk, &mut buf).unwrap();
+        buf[10] ^= 0xFF;
+        fs.disk.write_block(on_disk, &buf).unwrap();
+        fs.mount().unwrap();
+
+        let err = fs.read_path("/data.bin").unwrap_err();
+        assert!(err.contains("corrupted"), "{}", err);
+    }
+
+    #[test]
+    fn test_count_stats() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"hello world\nsecond line\n  x")
+            .unwrap();
+        assert_eq!(fs.count_stats("/a.txt").unwrap(), (2, 5, 27));
+
+        // 单词恰好跨越块边界时只计一次
+        let mut content = vec![b' '; BLOCK_SIZE - 2];
+        content.extend_from_slice(b"word\n");
+        fs.create_or_write_file("/", "b.txt", &content).unwrap();
+        let (lines, words, bytes) = fs.count_stats("/b.txt").unwrap();
+        assert_eq!((lines, words), (1, 1));
+        assert_eq!(bytes as u64, fs.stat_path("/b.txt").unwrap().size);
+        assert!(fs.count_stats("/").is_err());
+    }
+
+    #[test]
+    fn test_resolve_paths() {
+        let fs = test_fs();
+        assert_eq!(fs.resolve("/a/b", "c"), "/a/b/c");
+        assert_eq!(fs.resolve("/a/b", "../c//d/."), "/a/c/d");
+        assert_eq!(fs.resolve("/a/b", "/x/../y"), "/y");
+        assert_eq!(fs.resolve("/", "../.."), "/");
+    }
+
+    #[test]
+    fn test_read_only_mount() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "docs").unwrap();
+        fs.create_or_write_file("/docs", "a.txt", b"keep").unwrap();
+        fs.sync().unwrap();
+        fs.mount_readonly().unwrap();
+
+        let denied = |r: Result<(), String>| assert!(r.unwrap_err().contains("read-only"));
+        denied(fs.create_file("/", "new.txt").map(|_| ()));
+        denied(fs.create_dir("/", "d").map(|_| ()));
+        denied(fs.write_file("/docs/a.txt", b"changed"));
+        denied(fs.delete_file("/docs", "a.txt"));
+        denied(fs.delete_dir("/", "docs"));
+        denied(fs.chmod("/docs/a.txt", 0o600));
+        denied(fs.rename("/docs/a.txt", "/b.txt"));
+        denied(fs.open("/docs/a.txt", OpenFlags::WRITE).map(|_| ()));
+        denied(
+            fs.open("/new.txt", OpenFlags::READ | OpenFlags::CREATE)
+                .map(|_| ()),
+        );
+
+        // 读取照常进行，且不会因为 atime 把文件系统标脏
+        assert_eq!(fs.read_path("/docs/a.txt").unwrap(), b"keep");
+        assert!(fs.open("/docs/a.txt", OpenFlags::READ).is_ok());
+        assert!(!fs.super_block.dirty);
+        assert_eq!(fs.sync().unwrap().blocks, 0);
+        fs.super_block.dirty = true;
+        assert_eq!(
+            fs.sync().unwrap_err().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+        assert!(fs.format().is_err());
+
+        fs.read_only = false;
+        fs.create_file("/", "new.txt").unwrap();
+    }
+
+    #[test]
+    fn test_permission_triads() {
+        let mut fs = test_fs();
+        fs.set_current_user(1000, 100);
+        fs.create_dir("/", "home").unwrap();
+        fs.create_or_write_file("/home", "notes.txt", b"mine")
+            .unwrap();
+        fs.chmod("/home/notes.txt", 0o640).unwrap();
+
+        // 属主可读写，同组只读，其他人不可读
+        assert!(fs
+            .open("/home/notes.txt", OpenFlags::READ | OpenFlags::WRITE)
+            .is_ok());
+        fs.set_current_user(2000, 100);
+        assert!(fs.open("/home/notes.txt", OpenFlags::READ).is_ok());
+        assert!(fs.open("/home/notes.txt", OpenFlags::WRITE).is_err());
+        fs.set_current_user(2000, 200);
+        assert_eq!(
+            fs.open("/home/notes.txt", OpenFlags::READ).unwrap_err(),
+            "Permission denied: read"
+        );
+
+        // 目录缺少 x 时不能查找其中的条目，属主本人也一样
+        fs.set_current_user(1000, 100);
+        fs.chmod("/home", 0o600).unwrap();
+        assert!(fs
+            .find_inode("/home/notes.txt")
+            .unwrap_err()
+            .contains("search"));
+        assert!(fs.find_inode("/home").is_ok());
+        // exists 和 create_file 遵守同样的规则，不把权限错误当成不存在
+        assert!(!fs.exists("/home/notes.txt"));
+        assert!(fs
+            .create_file("/home", "notes.txt")
+            .unwrap_err()
+            .contains("search"));
+        fs.chmod("/home", 0o701).unwrap();
+        fs.set_current_user(2000, 200);
+        assert!(fs.find_inode("/home/notes.txt").is_ok());
+    }
+
+    #[test]
+    fn test_rejected_open_does_not_truncate() {
+        let mut fs = test_fs();
+        fs.set_current_user(1000, 100);
+        fs.create_dir("/", "d").unwrap();
+        fs.create_or_write_file("/d", "a.txt", b"keep me").unwrap();
+        fs.chmod("/d/a.txt", 0o644).unwrap();
+
+        // 没有写权限时 TRUNC 不生效，内容保持不变
+        fs.set_current_user(2000, 200);
+        let flags = OpenFlags::WRITE | OpenFlags::TRUNC;
+        assert_eq!(
+            fs.open("/d/a.txt", flags).unwrap_err(),
+            "Permission denied: write"
+        );
+        fs.set_current_user(1000, 100);
+        assert_eq!(fs.read_path("/d/a.txt").unwrap(), b"keep me");
+
+        // 目录不能被 open 截断，其中的条目仍然可以查找
+        assert!(fs.open("/d", OpenFlags::WRITE | OpenFlags::TRUNC).is_err());
+        assert_eq!(fs.read_path("/d/a.txt").unwrap(), b"keep me");
+        assert!(fs.fsck(false).is_clean());
+    }
+
+    #[test]
+    fn test_chown_and_current_owner() {
+        let mut fs = test_fs();
+        fs.current_uid = 1000;
+        fs.current_gid = 100;
+        fs.create_dir("/", "home").unwrap();
+        fs.create_file("/home", "a.txt").unwrap();
+        let file = fs.stat_path("/home/a.txt").unwrap();
+        assert_eq!((file.uid, file.gid), (1000, 100));
+        assert_eq!(fs.stat_path("/home").unwrap().uid, 1000);
+
+        let id = fs.find_inode("/home/a.txt").unwrap() as usize;
+        fs.inode_table.inodes[id].ctime = 0;
+        fs.chown("/home/a.txt", 0, 0).unwrap();
+        let file = fs.stat_path("/home/a.txt").unwrap();
+        assert_eq!((file.uid, file.gid), (0, 0));
+        assert!(file.ctime > 0);
+        assert!(fs.chown("/missing", 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_find_by_name() {
+        let mut fs = test_fs();
+        fs.create_dir_all("/src/notes").unwrap();
+        fs.create_or_write_file("/src", "main.rs", b"").unwrap();
+        fs.create_or_write_file("/src/notes", "a1.txt", b"")
+            .unwrap();
+        fs.create_or_write_file("/src/notes", "b22.txt", b"")
+            .unwrap();
+        fs.create_or_write_file("/", "top.txt", b"").unwrap();
+
+        assert_eq!(
+            fs.find_by_name("/", "*.txt").unwrap(),
+            ["/src/notes/a1.txt", "/src/notes/b22.txt", "/top.txt"]
+        );
+        assert_eq!(
+            fs.find_by_name("/src", "?1.*").unwrap(),
+            ["/src/notes/a1.txt"]
+        );
+        assert_eq!(fs.find_by_name("/", "no*s").unwrap(), ["/src/notes"]);
+        assert_eq!(fs.find_by_name("/", "*").unwrap().len(), 6);
+        assert!(fs.find_by_name("/src", "*.md").unwrap().is_empty());
+        assert!(fs.find_by_name("/missing", "*").is_err());
+    }
+
+    #[test]
+    fn test_read_range() {
+        let mut fs = test_fs();
+        let content: Vec<u8> = (0..BLOCK_SIZE * 3).map(|i| (i % 255) as u8).collect();
+        fs.create_or_write_file("/", "big.bin", &content).unwrap();
+
+        let start = BLOCK_SIZE as u64 - 10;
+        assert_eq!(
+            fs.read_range("/big.bin", start, 20).unwrap(),
+            &content[start as usize..start as usize + 20]
+        );
+        // 越过 EOF 的部分被截断，完全在 EOF 之后则为空
+        let tail = fs
+            .read_range("/big.bin", content.len() as u64 - 5, 100)
+            .unwrap();
+        assert_eq!(tail, &content[content.len() - 5..]);
+        assert!(fs.read_range("/big.bin", 1 << 20, 10).unwrap().is_empty());
+        assert!(fs.read_range("/", 0, 10).is_err());
+    }
+
+    #[test]
+    fn test_sync_reports_written_blocks() {
+        let mut fs = test_fs();
+        fs.sync().unwrap();
+        fs.create_or_write_file("/", "a.bin", &vec![7u8; BLOCK_SIZE * 3])
+            .unwrap();
+        assert!(fs.super_block.dirty);
+
+        let first = fs.sync().unwrap();
+        assert!(!fs.super_block.dirty);
+        assert_eq!(first.bytes, first.blocks * BLOCK_SIZE as u64);
+        // 第二次没有脏数据块和脏 inode：少写 3 个文件块、1 个根目录块和 1 个 inode 表块
+        let second = fs.sync().unwrap();
+        assert_eq!(first.blocks - second.blocks, 5);
+    }
+
+    #[test]
+    fn test_sparse_write_reads_zeros() {
+        let mut fs = test_fs();
+        fs.create_file("/", "hole.bin").unwrap();
+        let mut fh = fs
+            .open("/hole.bin", OpenFlags::READ | OpenFlags::WRITE)
+            .unwrap();
+
+        fs.seek(&mut fh, SeekFrom::Start(10000)).unwrap();
+        fs.write_at(&mut fh, b"X").unwrap();
+        assert_eq!(fs.stat_path("/hole.bin").unwrap().size, 10001);
+
+        let content = fs.read_path("/hole.bin").unwrap();
+        assert_eq!(content.len(), 10001);
+        assert!(content[..10000].iter().all(|&b| b == 0));
+        assert_eq!(content[10000], b'X');
+
+        // 旧末尾块中 size 之后的残留字节不会出现在空洞里
+        fs.create_or_write_file("/", "stale.bin", &[0xFF; BLOCK_SIZE])
+            .unwrap();
+        let id = fs.find_inode("/stale.bin").unwrap() as usize;
+        fs.inode_table.inodes[id].size = 5;
+        let mut fh = fs.open("/stale.bin", OpenFlags::WRITE).unwrap();
+        fs.seek(&mut fh, SeekFrom::Start(100)).unwrap();
+        fs.write_at(&mut fh, b"Y").unwrap();
+        let content = fs.read_path("/stale.bin").unwrap();
+        assert_eq!(&content[..5], &[0xFF; 5]);
+        assert!(content[5..100].iter().all(|&b| b == 0));
+        assert_eq!(content[100], b'Y');
+    }
+
+    #[test]
+    fn test_path_of() {
+        let mut fs = test_fs();
+        let dir = fs.create_dir_all("/a/b").unwrap();
+        let file = fs
+            .create_or_write_file("/a/b", "f.txt", b"x")
+            .unwrap()
+            .inode_id();
+
+        assert_eq!(fs.path_of(0).as_deref(), Some("/"));
+        assert_eq!(fs.path_of(dir).as_deref(), Some("/a/b"));
+        assert_eq!(fs.path_of(file).as_deref(), Some("/a/b/f.txt"));
+
+        fs.delete_file("/a/b", "f.txt").unwrap();
+        assert_eq!(fs.path_of(file), None);
+    }
+
+    #[test]
+    fn test_block_ids_are_bitmap_relative() {
+        let mut fs = test_fs();
+        assert!(fs.fsck(false).is_clean());
+        let root_block = fs.stat_path("/").unwrap().direct_blocks[0];
+        assert_ne!(root_block, RESERVED_BLOCK);
+        assert!(fs.data_bitmap.is_used(root_block));
+
+        // 写入、删除后再分配到同一个逻辑块，根目录不受影响
+        fs.create_or_write_file("/", "a.txt", b"first").unwrap();
+        let block = fs.stat_path("/a.txt").unwrap().direct_blocks[0];
+        fs.delete_file("/", "a.txt").unwrap();
+        assert!(!fs.data_bitmap.is_used(block));
+
+        fs.create_or_write_file("/", "b.txt", b"second").unwrap();
+        assert_eq!(fs.stat_path("/b.txt").unwrap().direct_blocks[0], block);
+        assert_eq!(fs.read_path("/b.txt").unwrap(), b"second");
+        assert_eq!(fs.list_dir("/").unwrap().len(), 3);
+
+        // 直接从磁盘按 start_block + 逻辑块号读出的正是文件内容
+        let mut buf = [0u8; BLOCK_SIZE];
+        fs.sync().unwrap();
+        fs.disk
+            .read_block(fs.data_area.start_block + block, &mut buf)
+            .unwrap();
+        assert_eq!(&buf[..6], b"second");
+        assert!(fs.fsck(false).is_clean());
+    }
+
+    #[test]
+    fn test_write_file_keeps_old_content_on_failure() {
+        let mut fs = test_fs();
+        let payload = fs.data_area.payload_size();
+        let old: Vec<u8> = (0..payload * 2).map(|i| (i % 199) as u8).collect();
+        fs.create_or_write_file("/", "f.bin", &old).unwrap();
+
+        // 只剩一个空闲块：三块的新内容写到一半就会失败
+        while fs.data_bitmap.free_blocks > 1 {
+            fs.data_bitmap.alloc().unwrap();
+        }
+        let (bitmap_free, sb_free) = (fs.data_bitmap.free_blocks, fs.super_block.free_blocks);
+        let err = fs
+            .write_file("/f.bin", &vec![7u8; payload * 3])
+            .unwrap_err();
+        assert_eq!(err, "No free data blocks");
+
+        // 旧内容完好，新分配的块全部归还
+        assert_eq!(fs.read_path("/f.bin").unwrap(), old);
+        assert_eq!(fs.data_bitmap.free_blocks, bitmap_free);
+        assert_eq!(fs.super_block.free_blocks, sb_free);
+
+        // 放得下时正常替换，旧块随后被释放
+        fs.write_file("/f.bin", b"tiny").unwrap();
+        assert_eq!(fs.read_path("/f.bin").unwrap(), b"tiny");
+        assert_eq!(fs.data_bitmap.free_blocks, bitmap_free + 1);
+        assert_eq!(fs.super_block.free_blocks, sb_free + 1);
+    }
+
+    #[test]
+    fn test_library_use_is_quiet_by_default() {
+        // 与测试共享缓冲区的输出目标
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut fs = test_fs();
+        let captured = Captured::default();
+        fs.set_log_sink(captured.clone());
+        assert!(!fs.verbose);
+
+        // 静默模式下过程信息不输出，也不影响结果
+        fs.create_dir("/", "d").unwrap();
+        assert!(captured.0.lock().unwrap().is_empty());
+
+        fs.verbose = true;
+        fs.create_dir("/d", "e").unwrap();
+        assert!(fs.exists("/d/e"));
+        let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("Allocated inode_id"), "{}", log);
+    }
+
+    #[test]
+    fn test_directory_compaction_frees_trailing_block() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "many").unwrap();
+        let dir_id = fs.find_inode("/many").unwrap();
+        let mut count = 0;
+        while fs.dir_blocks(dir_id).unwrap().len() < 2 {
+            fs.create_file("/many", &format!("file_{:04}", count))
+                .unwrap();
+            count += 1;
+        }
+        let second = fs.dir_blocks(dir_id).unwrap()[1];
+        let free_before = fs.super_block.free_blocks;
+
+        // 删掉大部分条目后剩下的都能装进第一个块
+        for i in 0..count - 5 {
+            fs.delete_file("/many", &format!("file_{:04}", i)).unwrap();
+        }
+        assert_eq!(fs.dir_blocks(dir_id).unwrap().len(), 1);
+        assert!(!fs.data_bitmap.is_used(second));
+        assert_eq!(fs.super_block.free_blocks, free_before + 1);
+
+        let names: Vec<String> = fs
+            .list_dir("/many")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names.len(), 2 + 5);
+        assert!(names.contains(&format!("file_{:04}", count - 1)));
+        let entries_len: u64 = fs
+            .dir_blocks(dir_id)
+            .unwrap()
+            .iter()
+            .map(|&b| serialized_len(&fs.load_dir_block(b).unwrap()).unwrap())
+            .sum();
+        assert_eq!(fs.stat_path("/many").unwrap().size, entries_len);
+        assert!(fs.fsck(false).is_clean());
+    }
+
+    #[test]
+    fn test_rename_within_directory_keeps_inode() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "old.txt", b"data").unwrap();
+        let id = fs.find_inode("/old.txt").unwrap();
+        let before = fs.stat_path("/old.txt").unwrap();
+
+        fs.rename("/old.txt", "/new.txt").unwrap();
+        assert_eq!(fs.find_inode("/new.txt").unwrap(), id);
+        assert!(!fs.exists("/old.txt"));
+        // 只改了父目录块，文件 inode 原样保留
+        assert_eq!(fs.stat_path("/new.txt").unwrap(), before);
+        assert_eq!(fs.read_path("/new.txt").unwrap(), b"data");
+    }
+
+    #[test]
+    fn test_interrupted_recursive_delete_syncs() {
+        let mut fs = test_fs();
+        fs.create_dir_all("/tree/sub").unwrap();
+        fs.create_or_write_file("/tree", "a.txt", b"a").unwrap();
+
+        fs.interrupt.store(true, Ordering::SeqCst);
+        let err = fs.remove_dir_recursive("/tree").unwrap_err();
+        assert!(err.contains("Interrupted") && err.contains("removed 0"));
+        assert!(!fs.interrupt.load(Ordering::SeqCst));
+        assert!(!fs.super_block.dirty);
+        assert!(fs.exists("/tree/a.txt"));
+
+        // 标志清除后可以重新执行完
+        fs.remove_dir_recursive("/tree").unwrap();
+        assert!(!fs.exists("/tree"));
+    }
+
+    #[test]
+    fn test_interrupted_format_leaves_usable_fs() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "old.txt", b"old").unwrap();
+        fs.interrupt.store(true, Ordering::SeqCst);
+        let options = FormatOptions {
+            fill_byte: Some(0xAA),
+            ..FormatOptions::default()
+        };
+        let err = fs.format_with(&options).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert!(err.to_string().contains("filled 0 of"));
+
+        fs.mount().unwrap();
+        assert!(!fs.exists("/old.txt"));
+        fs.create_or_write_file("/", "new.txt", b"new").unwrap();
+        assert!(fs.fsck(false).is_clean());
+    }
+
+    #[test]
+    fn test_set_times_and_get_attr() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"hello").unwrap();
+        fs.set_times("/a.txt", 1_000, 2_000).unwrap();
+
+        let attr = fs.get_attr("/a.txt").unwrap();
+        assert_eq!((attr.atime, attr.mtime), (1_000, 2_000));
+        assert!(attr.ctime >= 2_000);
+        assert_eq!(attr.inode_id, fs.find_inode("/a.txt").unwrap());
+        assert_eq!(attr.inode_type, InodeType::File);
+        assert_eq!((attr.size, attr.blocks, attr.link_count), (5, 1, 1));
+        assert_eq!(fs.get_attr("/").unwrap().inode_type, InodeType::Directory);
+        assert!(fs.set_times("/missing", 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_reused_block_does_not_expose_old_bytes() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "secret.txt", &[b'S'; BLOCK_SIZE])
+            .unwrap();
+        let old_block = fs.stat_path("/secret.txt").unwrap().direct_blocks[0];
+        fs.delete_file("/", "secret.txt").unwrap();
+
+        // 新的短文件拿到同一个块，块中 size 之后全是 0
+        fs.create_or_write_file("/", "short.txt", b"hi").unwrap();
+        assert_eq!(
+            fs.stat_path("/short.txt").unwrap().direct_blocks[0],
+            old_block
+        );
+        let raw = fs.data_area.read_block(old_block).unwrap();
+        assert_eq!(&raw[..2], b"hi");
+        assert!(raw[2..].iter().all(|&b| b == 0));
+
+        // 越过 EOF 写入后，中间的空洞同样读出 0
+        fs.delete_file("/", "short.txt").unwrap();
+        fs.create_or_write_file("/", "secret.txt", &[b'S'; BLOCK_SIZE])
+            .unwrap();
+        fs.delete_file("/", "secret.txt").unwrap();
+        fs.create_file("/", "sparse.bin").unwrap();
+        let mut fh = fs.open("/sparse.bin", OpenFlags::WRITE).unwrap();
+        fs.seek(&mut fh, SeekFrom::Start(100)).unwrap();
+        fs.write_at(&mut fh, b"X").unwrap();
+        let content = fs.read_path("/sparse.bin").unwrap();
+        assert!(content[..100].iter().all(|&b| b == 0));
+        assert_eq!(content[100], b'X');
+    }
+
+    #[test]
+    fn test_symlink_entries_in_listing() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "dir").unwrap();
+        fs.create_or_write_file("/", "b.txt", b"b").unwrap();
+        // 还没有创建符号链接的接口，直接拼出一个链接 inode
+        let id = fs
+            .inode_table
+            .alloc_inode(&mut fs.inode_bitmap, InodeType::Symlink, 0, 0, 0o777)
+            .unwrap();
+        fs.super_block.free_inode -= 1;
+        fs.write_new_blocks(id as u64, b"/b.txt").unwrap();
+        fs.inode_table.get_inode_mut(id as u64).unwrap().size = 6;
+        fs.add_directory_entry("/", "a.lnk", id, DirEntryType::Symlink)
+            .unwrap();
+
+        let entries = fs.list_dir("/").unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, [".", "..", "dir", "a.lnk", "b.txt"]);
+        assert_eq!(entries[3].entry_type, DirEntryType::Symlink);
+        assert_eq!(fs.read_link("/a.lnk").unwrap(), "/b.txt");
+        assert_eq!(
+            fs.stat_path("/a.lnk").unwrap().inode_type,
+            InodeType::Symlink
+        );
+        assert!(fs.read_link("/b.txt").is_err());
+        // 不跟随链接：链接本身既不是目录也不是普通文件
+        assert!(fs.exists("/a.lnk"));
+        assert!(!fs.is_file("/a.lnk") && !fs.is_dir("/a.lnk"));
+
+        // 改名和硬链接保留链接类型
+        fs.rename("/a.lnk", "/dir/c.lnk").unwrap();
+        fs.link("/dir/c.lnk", "/d.lnk").unwrap();
+        let entry = fs
+            .list_dir("/")
+            .unwrap()
+            .into_iter()
+            .find(|e| e.name == "d.lnk")
+            .unwrap();
+        assert_eq!(entry.entry_type, DirEntryType::Symlink);
+    }
+
+    #[test]
+    fn test_mount_rejects_short_image() {
+        let path =
+            std::env::temp_dir().join(format!("minifs_mount_short_{}.img", std::process::id()));
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let mut fs = FileSystem::new(FileDisk::new(path.to_str().unwrap(), &tx).unwrap());
+        fs.format().unwrap();
+        fs.mount().unwrap();
+
+        // 把镜像截掉一半，超级块仍然完好
+        let half = crate::disk::DISK_SIZE / 2;
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(half)
+            .unwrap();
+        let err = fs.mount().unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err
+            .to_string()
+            .contains(&format!("has {} blocks", half / BLOCK_SIZE as u64)));
+    }
+
+    #[test]
+    fn test_delete_file_rejects_directory() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "d").unwrap();
+        let err = fs.delete_file("/", "d").unwrap_err();
+        assert!(err.contains("Expected a file"));
+        assert!(fs.exists("/d"));
+    }
+
+    #[test]
+    fn test_file_blocks_stay_contiguous() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "big.bin", &vec![1u8; BLOCK_SIZE * 4])
+            .unwrap();
+        let blocks = fs.file_blocks(&fs.stat_path("/big.bin").unwrap()).unwrap();
+        assert!(blocks.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", blocks);
+
+        // 追加时其他文件占掉了紧邻的块，新块也接在文件自己的末尾之后，而不是填前面的空洞
+        fs.create_or_write_file("/", "hole.bin", b"h").unwrap();
+        fs.create_or_write_file("/", "next.bin", b"n").unwrap();
+        fs.delete_file("/", "hole.bin").unwrap();
+        let mut fh = fs.open("/next.bin", OpenFlags::WRITE).unwrap();
+        fs.seek(&mut fh, SeekFrom::Start(BLOCK_SIZE as u64))
+            .unwrap();
+        fs.write_at(&mut fh, b"more").unwrap();
+        let next = fs.file_blocks(&fs.stat_path("/next.bin").unwrap()).unwrap();
+        assert_eq!(next[1], next[0] + 1);
+
+        // hint 之后没有空闲块时回到从头扫描
+        let last = fs.data_bitmap.total_blocks - 1;
+        let first_free = fs.data_bitmap.alloc_near(last).unwrap();
+        assert_eq!(first_free, last);
+        assert!(fs.data_bitmap.alloc_near(last).unwrap() < last);
+    }
+
+    #[test]
+    fn test_write_file_places_file_in_one_run() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.bin", b"a").unwrap();
+        fs.create_or_write_file("/", "b.bin", b"b").unwrap();
+        let hole = fs.stat_path("/a.bin").unwrap().direct_blocks[0];
+        fs.delete_file("/", "a.bin").unwrap();
+        let free = fs.super_block.free_blocks;
+
+        // 前面一块的空洞放不下整个文件，于是整体放到后面的连续空闲段
+        fs.create_or_write_file("/", "c.bin", &vec![7u8; BLOCK_SIZE * 3])
+            .unwrap();
+        let blocks = fs.file_blocks(&fs.stat_path("/c.bin").unwrap()).unwrap();
+        assert!(!blocks.contains(&hole));
+        assert!(blocks.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", blocks);
+        assert_eq!(fs.super_block.free_blocks, free - 3);
+        assert_eq!(fs.read_path("/c.bin").unwrap(), vec![7u8; BLOCK_SIZE * 3]);
+    }
+
+    #[test]
+    fn test_unlink_while_open() {
+        let mut fs = test_fs();
+        let content = vec![7u8; 3 * BLOCK_SIZE];
+        let free_blocks = fs.super_block.free_blocks;
+        let free_inodes = fs.super_block.free_inode;
+        fs.create_or_write_file("/", "a.bin", &content).unwrap();
+
+        let mut fh = fs.open("/a.bin", OpenFlags::READ).unwrap();
+        let mut other = fs.open("/a.bin", OpenFlags::READ).unwrap();
+        fs.delete_file("/", "a.bin").unwrap();
+
+        // 目录项立即消失，但已打开的句柄仍能读到完整内容
+        assert!(!fs.exists("/a.bin"));
+        let mut buf = vec![0u8; content.len()];
+        assert_eq!(fs.read_at(&mut fh, &mut buf).unwrap(), content.len());
+        assert_eq!(buf, content);
+        assert!(fs.super_block.free_blocks < free_blocks);
+
+        // 最后一个句柄关闭后才释放
+        fs.close(fh).unwrap();
+        assert_eq!(fs.read_at(&mut other, &mut buf[..1]).unwrap(), 1);
+        fs.close(other).unwrap();
+        assert_eq!(fs.super_block.free_blocks, free_blocks);
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+        assert!(fs.fsck(false).is_clean());
+
+        // 未删除的文件关闭时保持不变；卸载时回收仍打开的已删除文件
+        fs.create_or_write_file("/", "b.txt", b"b").unwrap();
+        let fh = fs.open("/b.txt", OpenFlags::READ).unwrap();
+        fs.close(fh).unwrap();
+        assert_eq!(fs.read_path("/b.txt").unwrap(), b"b");
+        let _fh = fs.open("/b.txt", OpenFlags::READ).unwrap();
+        fs.delete_file("/", "b.txt").unwrap();
+        fs.unmount().unwrap();
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+    }
+
+    #[test]
+    fn test_orphans_reclaimed_after_crash() {
+        let mut fs = test_fs();
+        let free_inodes = fs.super_block.free_inode;
+        fs.create_or_write_file("/", "a.bin", &[1u8; 3 * BLOCK_SIZE])
+            .unwrap();
+        let _fh = fs.open("/a.bin", OpenFlags::READ).unwrap();
+        fs.delete_file("/", "a.bin").unwrap();
+        // 已删除但仍打开的 inode 随 sync 落盘，之后没有 unmount 就“崩溃”
+        fs.sync().unwrap();
+        assert!(fs.fsck(false).orphan_inodes.is_empty());
+
+        // 只读挂载不回收，fsck 能报告出来
+        let mut inspect = FileSystem::new(Arc::clone(&fs.disk));
+        inspect.mount_readonly().unwrap();
+        let report = inspect.fsck(false);
+        assert_eq!(report.orphan_inodes.len(), 1);
+        assert!(!report.is_clean());
+
+        // 读写挂载时直接回收
+        let mut remounted = FileSystem::new(Arc::clone(&fs.disk));
+        remounted.mount().unwrap();
+        assert_eq!(remounted.super_block.free_inode, free_inodes);
+        assert!(remounted.fsck(false).is_clean());
+    }
+
+    #[test]
+    fn test_millisecond_timestamps() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"a").unwrap();
+        let before = fs.get_attr("/a.txt").unwrap().mtime;
+        // 毫秒精度：2000 年之后的毫秒时间戳远大于秒级时间戳
+        assert!(before > 946_684_800_000);
+        std::thread::sleep(Duration::from_millis(5));
+        fs.write_file("/a.txt", b"b").unwrap();
+        assert!(fs.get_attr("/a.txt").unwrap().mtime > before);
+    }
+
+    #[test]
+    fn test_mount_upgrades_second_timestamps() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "old.txt", b"old").unwrap();
+        let id = fs.find_inode("/old.txt").unwrap();
+
+        // 模拟老镜像：没有版本号，时间戳以秒为单位
+        let inode = fs.inode_table.get_inode_mut(id).unwrap();
+        inode.atime = 1_600_000_000;
+        inode.mtime = 1_700_000_000;
+        inode.ctime = 1_700_000_000;
+        fs.super_block.version = 0;
+        fs.sync().unwrap();
+
+        fs.mount().unwrap();
+        let attr = fs.get_attr("/old.txt").unwrap();
+        assert_eq!(attr.atime, 1_600_000_000_000);
+        assert_eq!(attr.mtime, 1_700_000_000_000);
+        assert_eq!(fs.super_block.version, VERSION_MILLIS_TIMESTAMPS);
+
+        // 写回后再次挂载不会重复换算
+        fs.sync().unwrap();
+        fs.mount().unwrap();
+        assert_eq!(fs.get_attr("/old.txt").unwrap().mtime, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_migrate_and_reject_newer_versions() {
+        let mut fs = test_fs();
+        assert_eq!(fs.super_block.version, CURRENT_VERSION);
+        assert_eq!(fs.migrate().unwrap(), None);
+
+        // 老镜像：migrate 把升级结果写回磁盘并报告原版本
+        fs.super_block.version = 0;
+        fs.sync().unwrap();
+        fs.mount().unwrap();
+        assert_eq!(fs.read_super_block().unwrap().version, 0);
+        assert_eq!(fs.migrate().unwrap(), Some(0));
+        assert_eq!(fs.read_super_block().unwrap().version, CURRENT_VERSION);
+        assert_eq!(fs.migrate().unwrap(), None);
+
+        // 比当前程序新的版本拒绝挂载
+        fs.super_block.version = CURRENT_VERSION + 1;
+        fs.sync().unwrap();
+        let err = fs.mount().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("Unsupported image format version"));
+    }
+
+    #[test]
+    fn test_truncate_to_shrink_and_grow() {
+        let mut fs = test_fs();
+        let payload = fs.data_area.payload_size();
+        let content: Vec<u8> = (0..15 * payload).map(|i| (i % 199) as u8).collect();
+        let free = fs.super_block.free_blocks;
+        fs.create_or_write_file("/", "f.bin", &content).unwrap();
+
+        // 缩小到直接指针以内：超出的数据块和间接块都被释放
+        let new_size = payload as u64 + 10;
+        fs.truncate_to("/f.bin", new_size).unwrap();
+        assert_eq!(
+            fs.read_path("/f.bin").unwrap(),
+            &content[..new_size as usize]
+        );
+        let inode = fs.stat_path("/f.bin").unwrap();
+        assert_eq!(inode.indirect_block, None);
+        assert_eq!(fs.super_block.free_blocks, free - 2);
+        assert!(fs.fsck(false).is_clean());
+
+        // 扩大：原有内容不变，新增部分（包括旧末尾块的剩余部分）读出 0
+        fs.truncate_to("/f.bin", 14 * payload as u64).unwrap();
+        let data = fs.read_path("/f.bin").unwrap();
+        assert_eq!(data.len(), 14 * payload);
+        assert_eq!(&data[..new_size as usize], &content[..new_size as usize]);
+        assert!(data[new_size as usize..].iter().all(|&b| b == 0));
+        assert_eq!(
+            fs.block_count(&fs.stat_path("/f.bin").unwrap()).unwrap(),
+            15
+        );
+        assert!(fs.fsck(false).is_clean());
+
+        fs.truncate_to("/f.bin", 0).unwrap();
+        assert_eq!(fs.super_block.free_blocks, free);
+        assert!(fs.truncate_to("/", 0).is_err());
+    }
+
+    #[test]
+    fn test_open_trunc_requires_write() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"keep me").unwrap();
+
+        // 单独的 TRUNC 报错，且不动文件内容
+        let err = fs.open("/a.txt", OpenFlags::TRUNC).unwrap_err();
+        assert!(err.contains("TRUNC requires WRITE"));
+        assert!(fs
+            .open("/a.txt", OpenFlags::READ | OpenFlags::TRUNC)
+            .is_err());
+        assert_eq!(fs.read_path("/a.txt").unwrap(), b"keep me");
+
+        let fh = fs
+            .open("/a.txt", OpenFlags::WRITE | OpenFlags::TRUNC)
+            .unwrap();
+        assert_eq!(fs.stat_path("/a.txt").unwrap().size, 0);
+        fs.close(fh).unwrap();
+
+        // TRUNC|WRITE 在权限或类型检查失败时也不截断
+        fs.create_or_write_file("/", "ro.txt", b"read only")
+            .unwrap();
+        fs.chmod("/ro.txt", 0o444).unwrap();
+        assert!(fs
+            .open("/ro.txt", OpenFlags::WRITE | OpenFlags::TRUNC)
+            .is_err());
+        assert_eq!(fs.read_path("/ro.txt").unwrap(), b"read only");
+
+        fs.create_dir("/", "d").unwrap();
+        fs.create_or_write_file("/d", "b.txt", b"inside").unwrap();
+        assert!(fs.open("/d", OpenFlags::WRITE | OpenFlags::TRUNC).is_err());
+        assert_eq!(fs.read_path("/d/b.txt").unwrap(), b"inside");
+    }
+
+    #[test]
+    fn test_create_dir_failures_do_not_leak() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "a").unwrap();
+        let (free_inodes, free_blocks) = (fs.inode_bitmap.free_inodes, fs.data_bitmap.free_blocks);
+
+        // 重名时已分配的 inode 和目录块被回滚
+        assert!(fs.create_dir("/", "a").is_err());
+        assert_eq!(fs.inode_bitmap.free_inodes, free_inodes);
+        assert_eq!(fs.data_bitmap.free_blocks, free_blocks);
+        assert!(fs.fsck(false).is_clean());
+    }
+
+    // 小镜像，方便用真实写入把磁盘填满
+    fn small_fs() -> FileSystem {
+        let mut fs = test_fs();
+        fs.format_with(&FormatOptions {
+            total_inodes: 512,
+            total_blocks: 1024,
+            ..FormatOptions::default()
+        })
+        .unwrap();
+        fs
+    }
+
+    // 新建 dir 并让它的目录块恰好装满：再加一个 5 字符的名字就要扩出新块
+    fn fill_dir_block(fs: &mut FileSystem, dir: &str) {
+        let (parent, name) = split_path(dir).unwrap();
+        fs.create_dir(parent, name).unwrap();
+        let id = fs.find_inode(dir).unwrap();
+        let mut count = 0;
+        while fs.dir_blocks(id).unwrap().len() < 2 {
+            fs.create_file(dir, &format!("f{:04}", count)).unwrap();
+            count += 1;
+        }
+        fs.delete_file(dir, &format!("f{:04}", count - 1)).unwrap();
+        assert_eq!(fs.dir_blocks(id).unwrap().len(), 1);
+    }
+
+    // 用真实的文件写入把磁盘填到只剩 free 个空闲块
+    fn fill_disk(fs: &mut FileSystem, free: u64) {
+        let payload = fs.data_area.payload_size();
+        let mut i = 0;
+        while fs.data_bitmap.free_blocks > free {
+            let room = fs.data_bitmap.free_blocks - free;
+            let mut n = room.min(256);
+            while blocks_needed(n * payload as u64, payload) > room {
+                n -= 1;
+            }
+            let content = vec![1u8; n as usize * payload];
+            fs.create_or_write_file("/", &format!("fill{}", i), &content)
+                .unwrap();
+            i += 1;
+        }
+        assert_eq!(fs.super_block.free_blocks, free);
+    }
+
+    #[test]
+    fn test_create_dir_rolls_back_when_parent_cannot_grow() {
+        let mut fs = small_fs();
+
+        fill_dir_block(&mut fs, "/p");
+        fill_disk(&mut fs, 1);
+        let free_inodes = fs.super_block.free_inode;
+
+        // 新目录块用掉最后一块后，父目录扩块失败：新目录块和 inode 都要还回去
+        let err = fs.create_dir("/p", "d0000").unwrap_err();
+        assert_eq!(err, FileSystemError::DiskFull.to_string());
+        assert_eq!(fs.super_block.free_blocks, 1);
+        assert_eq!(fs.data_bitmap.free_blocks, 1);
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+        assert!(!fs.exists("/p/d0000"));
+        assert!(fs.fsck(false).is_clean());
+
+        // 没有空闲块时在分配之前就失败
+        fs.create_or_write_file("/", "last", b"x").unwrap();
+        let err = fs.create_dir("/", "b").unwrap_err();
+        assert_eq!(err, FileSystemError::DiskFull.to_string());
+        assert_eq!(fs.super_block.free_inode, free_inodes - 1);
+    }
+
+    #[test]
+    fn test_create_file_failures_do_not_leak() {
+        let mut fs = small_fs();
+        let free_inodes = fs.super_block.free_inode;
+
+        // 父目录不存在或不可查找时，在分配 inode 之前就失败
+        for _ in 0..3 {
+            assert!(fs.create_file("/nope", "x").is_err());
+        }
+        fs.create_dir_all("/locked/sub").unwrap();
+        fs.chmod("/locked", 0o600).unwrap();
+        assert!(fs.create_file("/locked/sub", "x").is_err());
+        assert_eq!(fs.super_block.free_inode, free_inodes - 2);
+        assert!(fs.fsck(false).is_clean());
+
+        // 父目录扩块失败时 inode 被回滚，并报 DiskFull
+        fill_dir_block(&mut fs, "/p");
+        fill_disk(&mut fs, 0);
+        let free_inodes = fs.super_block.free_inode;
+        let err = fs.create_file("/p", "f9999").unwrap_err();
+        assert_eq!(err, FileSystemError::DiskFull.to_string());
+        assert_eq!(fs.super_block.free_inode, free_inodes);
+        assert_eq!(fs.inode_bitmap.free_inodes, free_inodes);
+        assert!(fs.fsck(false).is_clean());
+    }
+
+    #[test]
+    fn test_dump_block() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"Hello, block!")
+            .unwrap();
+        fs.sync().unwrap();
+
+        let inode = fs.stat_path("/a.txt").unwrap();
+        let block = fs.data_area.start_block + fs.file_blocks(&inode).unwrap()[0];
+        let raw = fs.dump_block(block).unwrap();
+        assert_eq!(raw.len(), BLOCK_SIZE);
+        assert_eq!(&raw[..13], b"Hello, block!");
+
+        assert!(!fs.has_unsynced_changes());
+
+        // 只改 inode 表也算未写回
+        fs.chmod("/a.txt", 0o600).unwrap();
+        assert!(fs.has_unsynced_changes());
+        fs.sync().unwrap();
+        assert!(!fs.has_unsynced_changes());
+
+        assert_eq!(fs.block_region(0), "superblock");
+        assert_eq!(
+            fs.block_region(fs.super_block.inode_table_start),
+            "inode table"
+        );
+        assert_eq!(fs.block_region(block), "data area");
+        assert!(fs.dump_block(fs.super_block.total_blocks).is_none());
+    }
+
+    #[test]
+    fn test_list_dir_sorted() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "b.txt", b"bb").unwrap();
+        fs.create_or_write_file("/", "a.txt", b"aaaa").unwrap();
+        fs.create_dir("/", "z").unwrap();
+        let id = fs
+            .inode_table
+            .alloc_inode(&mut fs.inode_bitmap, InodeType::Symlink, 0, 0, 0o777)
+            .unwrap();
+        fs.super_block.free_inode -= 1;
+        fs.add_directory_entry("/", "0.lnk", id, DirEntryType::Symlink)
+            .unwrap();
+        fs.set_times("/b.txt", 0, 5_000).unwrap();
+        fs.set_times("/a.txt", 0, 1_000).unwrap();
+
+        let mut names = |sort, reverse| -> Vec<String> {
+            fs.list_dir_sorted("/", sort, reverse)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.name)
+                .filter(|n| !n.starts_with('.'))
+                .collect()
+        };
+        assert_eq!(names(SortBy::Name, false), ["z", "0.lnk", "a.txt", "b.txt"]);
+        assert_eq!(names(SortBy::Name, true), ["b.txt", "a.txt", "0.lnk", "z"]);
+        assert_eq!(names(SortBy::Type, false), ["z", "a.txt", "b.txt", "0.lnk"]);
+        // 目录和链接的大小、时间与文件无关，只比较两个文件的先后
+        let files = |names: Vec<String>| -> Vec<String> {
+            names.into_iter().filter(|n| n.ends_with(".txt")).collect()
+        };
+        assert_eq!(files(names(SortBy::Size, false)), ["a.txt", "b.txt"]);
+        assert_eq!(files(names(SortBy::Mtime, false)), ["b.txt", "a.txt"]);
+        // 倒序后最旧的排最前
+        assert_eq!(names(SortBy::Mtime, true)[..2], ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_open_missing_image() {
+        let err = FileSystem::open_image("/nonexistent/minifs.img").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,38 @@
+use std::vec::IntoIter;
+
+use crate::fs::{directory::DirEntry, FileSystem};
+
+/// 单个目录的目录项迭代器：按存储顺序逐块读取，同一时刻只持有一个目录块的条目。
+/// 与 `std::fs::ReadDir` 一样，读取某个目录块失败时产出一次错误
+pub struct ReadDir<'a> {
+    fs: &'a FileSystem,
+    blocks: IntoIter<u64>,       // 尚未读取的目录块
+    current: IntoIter<DirEntry>, // 当前块中剩余的目录项
+}
+
+impl<'a> ReadDir<'a> {
+    pub(crate) fn new(fs: &'a FileSystem, inode_id: u64) -> Result<Self, String> {
+        Ok(Self {
+            fs,
+            blocks: fs.dir_blocks(inode_id)?.into_iter(),
+            current: Vec::new().into_iter(),
+        })
+    }
+}
+
+impl Iterator for ReadDir<'_> {
+    type Item = Result<DirEntry, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.next() {
+                return Some(Ok(entry));
+            }
+            let block_id = self.blocks.next()?;
+            match self.fs.load_dir_block(block_id) {
+                Ok(dir) => self.current = dir.entries.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fs::File,
+    io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    disk::BLOCK_SIZE,
+    fs::{read_only_error, super_block::SuperBlock, FileSystem},
+};
+
+// 快照文件放在镜像旁边，命名为 `<镜像文件名>.snap.<label>`
+const SNAPSHOT_INFIX: &str = ".snap.";
+
+impl FileSystem {
+    /// 先 sync，再把整个镜像（超级块记录的全部块）逐块拷到旁边的快照文件，
+    /// 同名快照会被覆盖。只有打开的是镜像文件（image_path 已知）时可用
+    pub fn snapshot(&mut self, label: &str) -> Result<PathBuf> {
+        let target = self.snapshot_path(label)?;
+        self.sync()?;
+
+        let mut out = File::create(&target)?;
+        let mut buf = [0u8; BLOCK_SIZE];
+        for block in 0..self.super_block.total_blocks {
+            self.disk.read_block(block, &mut buf)?;
+            out.write_all(&buf)?;
+        }
+        out.sync_all()?;
+        self.log(format_args!("Snapshot written to {}", target.display()));
+        Ok(target)
+    }
+
+    /// 用快照整体覆盖当前镜像并重新挂载，未 sync 的修改全部丢弃。
+    /// 还有未关闭的句柄时拒绝，避免句柄指向回滚后已不存在的 inode；
+    /// 写入前先按 mount 的标准校验快照的超级块，坏快照不会覆盖掉镜像
+    pub fn restore(&mut self, label: &str) -> Result<()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        if !self.open_counts.is_empty() {
+            return Err(Error::new(
+                ErrorKind::ResourceBusy,
+                format!(
+                    "{} file(s) still open, close them before restoring",
+                    self.open_counts.len()
+                ),
+            ));
+        }
+        let source = self.snapshot_path(label)?;
+        let mut snap = File::open(&source)
+            .map_err(|e| Error::new(e.kind(), format!("Snapshot not found: {} ({})", label, e)))?;
+
+        // 先检查大小，不完整的快照不能覆盖到镜像上
+        let len = snap.metadata()?.len();
+        let blocks = len / BLOCK_SIZE as u64;
+        if len % BLOCK_SIZE as u64 != 0 || blocks == 0 || blocks > self.disk.block_count()? {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Snapshot {} has an invalid size of {} bytes", label, len),
+            ));
+        }
+
+        // 超级块必须能通过校验，且快照里有它声明的全部块
+        let mut buf = [0u8; BLOCK_SIZE];
+        snap.read_exact(&mut buf)?;
+        let invalid = |reason: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Snapshot {} is not a usable image: {}", label, reason),
+            )
+        };
+        let super_block: SuperBlock =
+            bincode::deserialize(&buf).map_err(|e| invalid(e.to_string()))?;
+        super_block.validate().map_err(|e| invalid(e.to_string()))?;
+        if super_block.total_blocks > blocks {
+            return Err(invalid(format!(
+                "it has {} blocks but the super block expects {}",
+                blocks, super_block.total_blocks
+            )));
+        }
+
+        snap.seek(SeekFrom::Start(0))?;
+        for block in 0..blocks {
+            snap.read_exact(&mut buf)?;
+            self.disk.write_block(block, &buf)?;
+        }
+        self.log(format_args!(
+            "Restored {} blocks from {}",
+            blocks,
+            source.display()
+        ));
+        self.mount()
+    }
+
+    /// 列出当前镜像的所有快照标签（按名字排序）
+    pub fn snapshots(&self) -> Result<Vec<String>> {
+        let (dir, prefix) = self.snapshot_prefix()?;
+        let mut labels = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            if let Some(label) = name.to_str().and_then(|n| n.strip_prefix(&prefix)) {
+                if !label.is_empty() {
+                    labels.push(label.to_string());
+                }
+            }
+        }
+        labels.sort();
+        Ok(labels)
+    }
+
+    // 辅助方法：快照所在目录和文件名前缀；内存磁盘没有镜像路径，无法快照
+    fn snapshot_prefix(&self) -> Result<(PathBuf, String)> {
+        let image = self.image_path.as_deref().ok_or_else(|| {
+            Error::new(ErrorKind::Unsupported, "snapshots need a disk image file")
+        })?;
+        let image = Path::new(image);
+        let dir = match image.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = image
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid disk image path"))?;
+        Ok((dir, format!("{}{}", name, SNAPSHOT_INFIX)))
+    }
+
+    // 辅助方法：标签对应的快照文件路径，标签只能是单个文件名片段
+    fn snapshot_path(&self, label: &str) -> Result<PathBuf> {
+        let valid = !label.is_empty()
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            && label != "."
+            && label != "..";
+        if !valid {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid snapshot label: {:?}", label),
+            ));
+        }
+        let (dir, prefix) = self.snapshot_prefix()?;
+        Ok(dir.join(format!("{}{}", prefix, label)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fs::{tests::test_fs, FileSystem, OpenFlags};
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("minifs_snapshot_{}.img", std::process::id()));
+        let image = path.to_str().unwrap().to_string();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let mut fs = FileSystem::with_image_file(&image, &tx).unwrap();
+        fs.format().unwrap();
+        fs.mount().unwrap();
+
+        fs.create_or_write_file("/", "a.txt", b"before").unwrap();
+        let snap = fs.snapshot("v1").unwrap();
+        assert_eq!(
+            snap,
+            dir.join(format!(
+                "minifs_snapshot_{}.img.snap.v1",
+                std::process::id()
+            ))
+        );
+        assert_eq!(fs.snapshots().unwrap(), ["v1"]);
+
+        fs.write_file("/a.txt", b"after").unwrap();
+        fs.create_dir("/", "later").unwrap();
+
+        // 有句柄未关闭时拒绝回滚
+        let fh = fs.open("/a.txt", OpenFlags::READ).unwrap();
+        let err = fs.restore("v1").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ResourceBusy);
+        fs.close(fh).unwrap();
+
+        fs.restore("v1").unwrap();
+        assert_eq!(fs.read_path("/a.txt").unwrap(), b"before");
+        assert!(!fs.exists("/later"));
+        assert!(fs.fsck(false).is_clean());
+
+        assert!(fs.restore("missing").is_err());
+
+        // 不是镜像的快照在写入前就被拒绝，当前镜像保持可用
+        let bogus = dir.join(format!(
+            "minifs_snapshot_{}.img.snap.bogus",
+            std::process::id()
+        ));
+        std::fs::write(&bogus, vec![0xABu8; 4 * crate::disk::BLOCK_SIZE]).unwrap();
+        let err = fs.restore("bogus").unwrap_err();
+        std::fs::remove_file(&bogus).unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        fs.mount().unwrap();
+        assert_eq!(fs.read_path("/a.txt").unwrap(), b"before");
+        assert!(fs.snapshot("../escape").is_err());
+
+        std::fs::remove_file(&snap).unwrap();
+        drop(fs);
+        std::fs::remove_file(&path).unwrap();
+
+        // 内存磁盘没有镜像文件
+        assert!(test_fs().snapshot("v1").is_err());
+    }
+}
@@ -1,5 +1,21 @@
+use crate::{
+    disk::types::{BLOCK_COUNT, BLOCK_SIZE},
+    fs::{error::FileSystemError, inode_table::InodeTable},
+};
 use serde::{Deserialize, Serialize};
 
+/// MiniFS 超级块魔数
+pub const MAGIC: u64 = 0xDEADBEEF;
+
+/// inode 时间戳改为毫秒的格式版本；更早的镜像没有版本字段，读出来是 0，时间戳以秒为单位
+pub const VERSION_MILLIS_TIMESTAMPS: u32 = 1;
+
+/// 新格式化的镜像使用的格式版本
+pub const CURRENT_VERSION: u32 = VERSION_MILLIS_TIMESTAMPS;
+
+/// 默认 inode 数量
+pub const DEFAULT_INODES: u64 = 4096;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SuperBlock {
     pub fs_type: String, // 文件系统标识
@@ -20,32 +36,55 @@ pub struct SuperBlock {
     pub dirty: bool,     // 是否有未写回的修改
     /** 其他元信息 */
     pub magic: u64, //魔数，用于识别文件系统
+    pub version: u32,    // 磁盘格式版本，放在最后，老镜像的补零区读出来就是 0
 }
 
 impl SuperBlock {
-    pub fn new(total_inodes: u64) -> Self {
-        let block_size: u64 = 4096; // 4KB
-        let total_blocks = 64 * 1024 * 1024 / block_size; // 64MB / 4KB = 16384 块
+    /// 按 inode 数和总块数计算各区域布局；总块数不能超过磁盘容量，
+    /// 且扣除元数据区后至少要留下预留块和根目录块
+    pub fn new(total_inodes: u64, total_blocks: u64) -> Result<Self, FileSystemError> {
+        let block_size = BLOCK_SIZE as u64; // 4KB
+
+        if total_inodes == 0 {
+            return Err(FileSystemError::InvalidLayout(
+                "at least one inode is required for the root directory".into(),
+            ));
+        }
+        if total_blocks > BLOCK_COUNT as u64 {
+            return Err(FileSystemError::InvalidLayout(format!(
+                "{} blocks exceed the disk capacity of {} blocks",
+                total_blocks, BLOCK_COUNT
+            )));
+        }
 
         let superblock_size = 1; // 超级块占 1 块
 
         // inode 位图占用的块数 = ceil(total_inodes / 8 / block_size)
-        let inode_bitmap_size = (total_inodes + 8 * block_size - 1) / (8 * block_size);
+        let inode_bitmap_size = total_inodes.div_ceil(8 * block_size);
         // 数据块位图占用的块数 = ceil(total_blocks / 8 / block_size)
-        let block_bitmap_size = (total_blocks + 8 * block_size - 1) / (8 * block_size);
+        let block_bitmap_size = total_blocks.div_ceil(8 * block_size);
 
-        let inode_table_size = (total_inodes * 128 + block_size - 1) / block_size; // 每个 inode 128B
+        // 按 inode 实际序列化大小的上限预留，而不是假设的 128B
+        let inode_table_size = InodeTable::region_blocks(total_inodes);
 
         let inode_bitmap_start = superblock_size;
         let block_bitmap_start = inode_bitmap_start + inode_bitmap_size;
         let inode_table_start = block_bitmap_start + block_bitmap_size;
         let data_block_start = inode_table_start + inode_table_size;
 
-        Self {
+        // 数据区至少要放下预留的 0 号块和根目录块
+        if total_blocks < data_block_start + 2 {
+            return Err(FileSystemError::InvalidLayout(format!(
+                "{} blocks leave no room for data: metadata alone needs {} blocks",
+                total_blocks, data_block_start
+            )));
+        }
+
+        Ok(Self {
             fs_type: "MiNiFS".to_string(),
             block_size,
             total_blocks,
-            free_blocks: total_blocks,
+            free_blocks: total_blocks - data_block_start, // 元数据区的块永远不可分配
             data_block_start,
             total_inodes,
             free_inode: total_inodes,
@@ -54,7 +93,61 @@ impl SuperBlock {
             block_bitmap_start,
             mounted: false,
             dirty: false,
-            magic: 0xDEADBEEF,
+            magic: MAGIC,
+            version: CURRENT_VERSION,
+        })
+    }
+
+    /// 挂载前检查超级块是否可信，避免按垃圾数据计算布局偏移
+    pub fn validate(&self) -> Result<(), FileSystemError> {
+        if self.magic != MAGIC {
+            return Err(FileSystemError::Corrupted(format!(
+                "bad super block magic {:#x}, expected {:#x} (not a MiniFS image?)",
+                self.magic, MAGIC
+            )));
+        }
+        // 更老的版本由 mount 升级；更新的版本布局未知，不能按当前格式解读
+        if self.version > CURRENT_VERSION {
+            return Err(FileSystemError::UnsupportedVersion(self.version));
+        }
+        if self.block_size != BLOCK_SIZE as u64 {
+            return Err(FileSystemError::Corrupted(format!(
+                "unsupported block size {}, expected {}",
+                self.block_size, BLOCK_SIZE
+            )));
+        }
+        if self.total_blocks > BLOCK_COUNT as u64 {
+            return Err(FileSystemError::Corrupted(format!(
+                "super block reports {} blocks, but the disk only has {}",
+                self.total_blocks, BLOCK_COUNT
+            )));
         }
+        // 各区域必须按顺序排列且互不重叠，数据区位于磁盘范围内
+        let regions = [
+            0,
+            self.inode_bitmap_start,
+            self.block_bitmap_start,
+            self.inode_table_start,
+            self.data_block_start,
+            self.total_blocks,
+        ];
+        if regions.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(FileSystemError::Corrupted(format!(
+                "overlapping layout: inode bitmap {}, block bitmap {}, inode table {}, data {}, total {}",
+                self.inode_bitmap_start,
+                self.block_bitmap_start,
+                self.inode_table_start,
+                self.data_block_start,
+                self.total_blocks
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SuperBlock {
+    /// 默认布局：DEFAULT_INODES 个 inode，占满整块磁盘
+    fn default() -> Self {
+        Self::new(DEFAULT_INODES, BLOCK_COUNT as u64).expect("default layout fits the disk")
     }
 }
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+
+use crate::{
+    fs::{inode_table::InodeType, sort_entries, FileSystem},
+    utils::join_path,
+};
+
+/// 子树先序遍历迭代器，按 `list_dir` 的顺序产出 (完整路径, inode 类型)
+pub struct Walk<'a> {
+    fs: &'a FileSystem,
+    stack: Vec<(String, usize)>, // 待访问的 (路径, inode 编号)
+    visited: HashSet<usize>,     // 已展开过的目录 inode，防止环
+}
+
+impl<'a> Walk<'a> {
+    /// `root` 不存在时返回错误，而不是产出一个空遍历
+    pub fn new(fs: &'a FileSystem, root: &str) -> Result<Self, String> {
+        let root_inode = fs.find_inode(root)?;
+        let mut walk = Self {
+            fs,
+            stack: Vec::new(),
+            visited: HashSet::new(),
+        };
+        walk.expand(root, root_inode as usize);
+        Ok(walk)
+    }
+
+    // 把目录的子项逆序压栈，出栈时即为正序
+    fn expand(&mut self, path: &str, inode_index: usize) {
+        if !self.visited.insert(inode_index) {
+            return;
+        }
+        let Ok(mut entries) = self.fs.dir_entries(inode_index as u64) else {
+            return;
+        };
+        sort_entries(&mut entries);
+
+        for entry in entries.into_iter().rev() {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            self.stack
+                .push((join_path(path, &entry.name), entry.inode_index));
+        }
+    }
+}
+
+impl Iterator for Walk<'_> {
+    type Item = (String, InodeType);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, inode_index) = self.stack.pop()?;
+        let inode_type = self
+            .fs
+            .inode_table
+            .get_inode(inode_index as u64)
+            .map(|inode| inode.inode_type.clone())
+            .unwrap_or(InodeType::File);
+
+        if inode_type == InodeType::Directory {
+            self.expand(&path, inode_index);
+        }
+        Some((path, inode_type))
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    fs::{data_block_bitmap::RESERVED_BLOCK, error::FileSystemError, FileSystem},
+    utils::current_timestamp,
+};
+
+/// 单个 inode 全部扩展属性（键与值字节数之和）的上限
+pub const XATTR_MAX_BYTES: usize = 1024;
+
+// inode 槽位是定长的，放不下 1KB 的属性表；
+// 属性表序列化后单独存进一个数据块，inode 里只记 xattr_block 块号（槽位多占 9 字节）
+type Xattrs = BTreeMap<String, Vec<u8>>;
+
+impl FileSystem {
+    /// 设置扩展属性（已存在则覆盖），总大小超过 XATTR_MAX_BYTES 时报错
+    pub fn set_xattr(&mut self, path: &str, key: &str, value: &[u8]) -> Result<(), String> {
+        self.ensure_writable()?;
+        if key.is_empty() {
+            return Err("Empty xattr name".to_string());
+        }
+        let inode_id = self.find_inode(path)?;
+        let mut xattrs = self.load_xattrs(inode_id)?;
+        xattrs.insert(key.to_string(), value.to_vec());
+
+        let total: usize = xattrs.iter().map(|(k, v)| k.len() + v.len()).sum();
+        if total > XATTR_MAX_BYTES {
+            return Err(format!(
+                "Extended attributes too large: {} bytes (limit {})",
+                total, XATTR_MAX_BYTES
+            ));
+        }
+        self.store_xattrs(inode_id, &xattrs)
+    }
+
+    /// 读取扩展属性，不存在时返回 None
+    pub fn get_xattr(&self, path: &str, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let inode_id = self.find_inode(path)?;
+        Ok(self.load_xattrs(inode_id)?.remove(key))
+    }
+
+    /// 按名字排序列出所有扩展属性名
+    pub fn list_xattr(&self, path: &str) -> Result<Vec<String>, String> {
+        let inode_id = self.find_inode(path)?;
+        Ok(self.load_xattrs(inode_id)?.into_keys().collect())
+    }
+
+    /// 删除扩展属性，属性不存在时报错；删光后释放属性块
+    pub fn remove_xattr(&mut self, path: &str, key: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let inode_id = self.find_inode(path)?;
+        let mut xattrs = self.load_xattrs(inode_id)?;
+        if xattrs.remove(key).is_none() {
+            return Err(format!("No such attribute: {}", key));
+        }
+        self.store_xattrs(inode_id, &xattrs)
+    }
+
+    /// 释放 inode 的属性块，在 inode 被回收前调用
+    pub(super) fn release_xattrs(&mut self, inode_id: u64) {
+        let Some(inode) = self.inode_table.get_inode_mut(inode_id) else {
+            return;
+        };
+        if let Some(block) = inode.xattr_block.take() {
+            self.data_bitmap.free(block);
+            self.super_block.free_blocks += 1;
+            self.super_block.dirty = true;
+        }
+    }
+
+    fn load_xattrs(&self, inode_id: u64) -> Result<Xattrs, String> {
+        let inode = self
+            .inode_table
+            .get_inode(inode_id)
+            .ok_or("Inode not found")?;
+        let Some(block) = inode.xattr_block else {
+            return Ok(Xattrs::new());
+        };
+        let data = self
+            .data_area
+            .read_block(block)
+            .map_err(|e| e.to_string())?;
+        bincode::deserialize(&data[..self.data_area.payload_size()]).map_err(|e| {
+            FileSystemError::Corrupted(format!("xattr block {}: {}", block, e)).to_string()
+        })
+    }
+
+    // 写回属性表：空表释放属性块，否则复用已有块或新分配一个
+    fn store_xattrs(&mut self, inode_id: u64, xattrs: &Xattrs) -> Result<(), String> {
+        if xattrs.is_empty() {
+            self.release_xattrs(inode_id);
+        } else {
+            let bytes = bincode::serialize(xattrs).map_err(|e| e.to_string())?;
+            let existing = self
+                .inode_table
+                .get_inode(inode_id)
+                .ok_or("Inode not found")?
+                .xattr_block;
+            match existing {
+                Some(block) => self.data_area.write_block(block, &bytes)?,
+                None => {
+                    let block = self.alloc_block(&bytes, RESERVED_BLOCK)?;
+                    if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
+                        inode.xattr_block = Some(block);
+                    }
+                }
+            }
+        }
+
+        if let Some(inode) = self.inode_table.get_inode_mut(inode_id) {
+            inode.ctime = current_timestamp();
+        }
+        self.super_block.dirty = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XATTR_MAX_BYTES;
+    use crate::fs::tests::test_fs;
+
+    #[test]
+    fn test_xattr_set_get_list_remove() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"hi").unwrap();
+        let free = fs.super_block.free_blocks;
+
+        fs.set_xattr("/a.txt", "user.tag", b"red").unwrap();
+        fs.set_xattr("/a.txt", "user.author", b"me").unwrap();
+        assert_eq!(fs.super_block.free_blocks, free - 1);
+        assert_eq!(fs.get_xattr("/a.txt", "user.tag").unwrap().unwrap(), b"red");
+        assert_eq!(fs.get_xattr("/a.txt", "missing").unwrap(), None);
+        assert_eq!(
+            fs.list_xattr("/a.txt").unwrap(),
+            ["user.author", "user.tag"]
+        );
+
+        // 改写文件内容不影响属性
+        fs.write_file("/a.txt", b"new content").unwrap();
+        assert_eq!(fs.get_xattr("/a.txt", "user.tag").unwrap().unwrap(), b"red");
+
+        // 超过上限时拒绝，原有属性保持不变
+        let big = vec![0u8; XATTR_MAX_BYTES];
+        assert!(fs.set_xattr("/a.txt", "user.big", &big).is_err());
+        assert_eq!(fs.list_xattr("/a.txt").unwrap().len(), 2);
+
+        fs.remove_xattr("/a.txt", "user.tag").unwrap();
+        assert!(fs.remove_xattr("/a.txt", "user.tag").is_err());
+        fs.remove_xattr("/a.txt", "user.author").unwrap();
+        assert!(fs.list_xattr("/a.txt").unwrap().is_empty());
+        assert_eq!(fs.super_block.free_blocks, free);
+
+        // 删除文件时一并释放属性块
+        fs.set_xattr("/a.txt", "user.tag", b"red").unwrap();
+        fs.delete_file("/", "a.txt").unwrap();
+        assert_eq!(fs.fsck(false).leaked_blocks, Vec::<u64>::new());
+    }
+}
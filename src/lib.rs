@@ -0,0 +1,17 @@
+//! MiniFS：一个运行在单个镜像文件上的教学用文件系统。
+//!
+//! 二进制入口只是对 [`shell`] 的薄封装，文件系统本身可以直接嵌入使用：
+//! 打开 [`FileDisk`]，交给 [`FileSystem::new`]，再 `format` 或 `mount`。
+//! 测试时可以用 [`FileSystem::new_in_memory`] 在 [`MemDisk`] 上直接得到格式化好的实例。
+
+pub mod disk;
+pub mod fs;
+pub mod shell;
+pub mod utils;
+
+pub use disk::{BlockCache, BlockDevice, FileDisk, MemDisk};
+pub use fs::{
+    error::{FileSystemError, Result},
+    inode_table::Inode,
+    FileAttr, FileHandle, FileSystem, OpenFlags, WriteOutcome,
+};
@@ -1,10 +1,39 @@
-use crate::shell::start_shell;
-
-mod disk;
-mod fs;
-mod shell;
-mod utils;
+use mini_fs::{
+    disk::init::image_from_args,
+    shell::{
+        color::ColorMode,
+        script::{run_script_file, script_from_args},
+        start_shell,
+    },
+};
 
 fn main() {
-    start_shell();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match ColorMode::from_args(args.clone()) {
+        Ok(mode) => mode.apply(),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
+    let image = match image_from_args(args.clone(), std::env::var_os("MINIFS_IMAGE")) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    match script_from_args(args) {
+        Ok(Some(script)) => {
+            if let Err(e) = run_script_file(&script, &image) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Ok(None) => start_shell(&image),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
 }
@@ -0,0 +1,87 @@
+use std::io::IsTerminal;
+
+/// 全局着色模式，对应命令行参数 `--color never|auto|always`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    Never,
+    #[default]
+    Auto,
+    Always,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "never" => Ok(ColorMode::Never),
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            other => Err(format!(
+                "invalid color mode '{}', expected never|auto|always",
+                other
+            )),
+        }
+    }
+
+    /// 从启动参数中读取 `--color=<mode>` 或 `--color <mode>`，未指定时为 auto
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut mode = ColorMode::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if let Some(value) = arg.strip_prefix("--color=") {
+                mode = Self::parse(value)?;
+            } else if arg == "--color" {
+                let value = args.next().ok_or("--color requires a value")?;
+                mode = Self::parse(&value)?;
+            }
+        }
+        Ok(mode)
+    }
+
+    /// 应用到 colored 的全局开关
+    pub fn apply(self) {
+        colored::control::set_override(self.enabled(std::io::stdout().is_terminal()));
+    }
+
+    // 辅助方法：是否着色；auto 模式下只有 stdout 是终端时才着色
+    fn enabled(self, stdout_is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Never => false,
+            ColorMode::Always => true,
+            ColorMode::Auto => stdout_is_terminal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_color_args() {
+        assert_eq!(ColorMode::from_args(args(&[])).unwrap(), ColorMode::Auto);
+        assert_eq!(
+            ColorMode::from_args(args(&["--color=never"])).unwrap(),
+            ColorMode::Never
+        );
+        assert_eq!(
+            ColorMode::from_args(args(&["--color", "always"])).unwrap(),
+            ColorMode::Always
+        );
+        assert!(ColorMode::from_args(args(&["--color=rainbow"])).is_err());
+        assert!(ColorMode::from_args(args(&["--color"])).is_err());
+    }
+
+    // 不调用 apply：colored 的全局开关会影响并行运行的其他测试
+    #[test]
+    fn test_color_enabled() {
+        for tty in [false, true] {
+            assert!(!ColorMode::Never.enabled(tty));
+            assert!(ColorMode::Always.enabled(tty));
+            assert_eq!(ColorMode::Auto.enabled(tty), tty);
+        }
+    }
+}
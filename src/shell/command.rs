@@ -1,134 +1,359 @@
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::{BTreeMap, VecDeque};
 use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
 use std::{thread, time::Duration};
 
-use crate::fs::directory::DirEntryType;
-use crate::fs::{FileSystem, OpenFlags};
-use crate::utils::format_time;
+use crate::fs::directory::{DirEntry, DirEntryType};
+use crate::fs::inode_table::InodeType;
+use crate::fs::{FileHandle, FileSystem, FormatOptions, OpenFlags, SortBy, WriteOutcome};
+use crate::utils::{
+    format_mode, format_size, format_time, format_uuid, hex_dump, join_path, split_path,
+};
 
 #[derive(Debug)]
 pub enum Command {
     Help,
-    Ls,
+    Ls(bool, SortBy, bool), // (-l, 排序方式, -r)
     Pwd,
-    Mkdir(String),
+    Mkdir(String, bool),
     Rmdir(String),
-    Create(String),
-    Rm(String),
+    Create(String, Option<String>),
+    Rm(String, bool, bool), // (路径, -r, -i)
+    Touch(String),
     Cd(String),
+    SetHome(String),
     Read(String),
+    Head(String, u64),
+    Tail(String, u64),
     Write(String, String),
+    Append(String, String),
+    Truncate(String, u64),
     Stat(String),
+    Wc(String),
+    Chmod(String, u16),
+    Chown(String, u32, u32),
+    SetXattr(String, String, String),
+    GetXattr(String, Option<String>), // None 时列出全部属性
     Open(String),
-    Format,
+    ReadFd(u32, usize),
+    Close(u32),
+    Cp(String, String, bool),
+    Mv(String, String),
+    Ln(String, String),
+    Import(String, String),
+    Export(String, String),
+    Walk(Option<String>),
+    Tree(Option<String>),
+    Find(String, String),
+    Diff(String, String),
+    DirDump(String),
+    DumpBlock(u64),
+    Depth,
+    MountInfo,
+    Df,
+    Du(Option<String>),
+    Fsck(bool),
+    Defrag,
+    Sync,
+    Snapshot(String),
+    Restore(String),
+    Snapshots,
+    ReadOnly(Option<bool>),      // None 时只显示当前状态
+    Verbose(Option<bool>),       // None 时只显示当前状态
+    History(bool),               // true 表示 `history clear`
+    Format(FormatOptions, bool), // bool 为 true 表示 `-y`，跳过确认
     Exit,
 }
 
+// 命令输出统一写到 out，写失败时与其他错误一样返回给调用者
+macro_rules! outln {
+    ($out:expr) => {
+        writeln!($out)?
+    };
+    ($out:expr, $($arg:tt)*) => {
+        writeln!($out, $($arg)*)?
+    };
+}
+
+/// Shell 会话状态
+#[derive(Debug)]
+pub struct ShellState {
+    pub current_dir: String,                // 当前工作目录
+    pub home: String,                       // `cd` / `cd ~` 的目标目录
+    pub handles: BTreeMap<u32, FileHandle>, // open 得到的句柄表，键为 fd
+    // 确认提示的答案来源：None 时从标准输入读取，否则依次取出，取完视为否（脚本、测试用）
+    pub answers: Option<VecDeque<String>>,
+    next_fd: u32,
+}
+
+impl ShellState {
+    pub fn new() -> Self {
+        Self {
+            current_dir: String::from("/"),
+            home: String::from("/"),
+            handles: BTreeMap::new(),
+            answers: None,
+            next_fd: 3, // 与 Unix 一致，0-2 留给标准输入输出
+        }
+    }
+}
+
+impl Default for ShellState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn execute_command(
     cmd: &Command,
-    current_dir: &mut String,
-    fs: &mut FileSystem, // 添加 FileSystem 参数
+    state: &mut ShellState,
+    fs: &mut FileSystem,
 ) -> Result<(), Box<dyn Error>> {
+    execute_command_to(cmd, state, fs, &mut std::io::stdout())
+}
+
+/// 执行一条命令，输出写到 `out`（交互时是 stdout，脚本模式下收集到缓冲区）
+pub fn execute_command_to(
+    cmd: &Command,
+    state: &mut ShellState,
+    fs: &mut FileSystem,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    let current_dir = &mut state.current_dir;
     match cmd {
-        Command::Help => print_help(),
-        Command::Ls => match fs.list_dir(current_dir) {
+        Command::Help => print_help(out)?,
+        Command::Ls(true, sort, reverse) => match fs.list_dir_long(current_dir, *sort, *reverse) {
+            Ok(entries) => {
+                for (e, inode) in entries {
+                    let mut name = e.name.clone();
+                    if e.entry_type == DirEntryType::Symlink {
+                        let target = fs
+                            .read_link(&join_path(current_dir, &e.name))
+                            .unwrap_or_else(|_| "?".into());
+                        name = format!("{} -> {}", name, target);
+                    }
+                    outln!(
+                        out,
+                        "{} {:>3} {:>5} {:>5} {:>8} {} {}",
+                        format_mode(inode.inode_type.mode_char(), inode.permissions),
+                        inode.link_count,
+                        inode.uid,
+                        inode.gid,
+                        inode.size,
+                        format_time(inode.mtime),
+                        name
+                    );
+                }
+            }
+            Err(e) => outln!(out, "❌ {}", e),
+        },
+        Command::Ls(false, sort, reverse) => match fs.list_dir_sorted(current_dir, *sort, *reverse)
+        {
             Ok(entries) => {
                 for e in entries {
                     match e.entry_type {
-                        DirEntryType::Directory => println!("📁  {}", e.name),
-                        DirEntryType::File => println!("📄  {}", e.name),
+                        DirEntryType::Directory => outln!(out, "📁  {}", e.name),
+                        DirEntryType::File => outln!(out, "📄  {}", e.name),
+                        DirEntryType::Symlink => {
+                            let target = fs
+                                .read_link(&join_path(current_dir, &e.name))
+                                .unwrap_or_else(|_| "?".into());
+                            outln!(out, "🔗  {} -> {}", e.name, target)
+                        }
                     }
                 }
             }
-            Err(e) => println!("❌ {}", e),
-        },
-        Command::Pwd => println!("📍 {}", current_dir.cyan()),
-        Command::Mkdir(name) => match fs.create_dir(current_dir, name) {
-            Ok(_) => println!(
-                "✅ Created directory: {}",
-                format!("{}/{}", current_dir, name).green()
-            ),
-            Err(e) => println!("❌ {}, current_dir: {}, name: {}", e, current_dir, name),
-        },
-        Command::Rmdir(name) => match fs.delete_dir(current_dir, name) {
-            Ok(_) => println!(
-                "🗑️ Removed directory: {}",
-                format!("{}/{}", current_dir, name).red()
-            ),
-            Err(e) => println!("❌ {}", e),
+            Err(e) => outln!(out, "❌ {}", e),
         },
-        Command::Create(name) => match fs.create_or_write_file(current_dir, name, &[]) {
-            Ok(_) => println!(
-                "📝 Created file: {}",
-                format!("{}/{}", current_dir, name).green()
-            ),
-            Err(e) => println!("❌ {}", e),
-        },
-        Command::Rm(name) => match fs.delete_file(current_dir, name) {
-            Ok(_) => println!(
-                "❌ Deleted file: {}",
-                format!("{}/{}", current_dir, name).red()
-            ),
-            Err(e) => println!("❌ {}", e),
-        },
-        Command::Cd(path) => {
-            if path == ".." {
-                if let Some(pos) = current_dir.rfind('/') {
-                    current_dir.truncate(pos);
-                    if current_dir.is_empty() {
-                        *current_dir = "/".to_string();
-                    }
+        Command::Pwd => outln!(out, "📍 {}", current_dir.cyan()),
+        Command::Mkdir(path, true) => {
+            let target = fs.resolve(current_dir, path);
+            match fs.create_dir_all(&target) {
+                Ok(_) => outln!(out, "✅ Created directory: {}", target.green()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Mkdir(path, false) => {
+            let target = fs.resolve(current_dir, path);
+            match split_path(&target).and_then(|(parent, name)| fs.create_dir(parent, name)) {
+                Ok(_) => outln!(out, "✅ Created directory: {}", target.green()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Rmdir(path) => {
+            let target = fs.resolve(current_dir, path);
+            match split_path(&target).and_then(|(parent, name)| fs.delete_dir(parent, name)) {
+                Ok(_) => outln!(out, "🗑️ Removed directory: {}", target.red()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Create(path, None) => {
+            let target = fs.resolve(current_dir, path);
+            let result = split_path(&target)
+                .and_then(|(parent, name)| fs.create_or_write_file(parent, name, &[]));
+            match result {
+                Ok(WriteOutcome::Created(_)) => outln!(out, "📝 Created file: {}", target.green()),
+                Ok(WriteOutcome::Overwrote(_)) => {
+                    outln!(out, "⚠️  Overwrote existing file: {}", target.yellow())
                 }
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Create(path, Some(content)) => {
+            // 带初始内容时类似 O_EXCL：文件已存在直接报错，不覆盖
+            let target = fs.resolve(current_dir, path);
+            let result = split_path(&target)
+                .and_then(|(parent, name)| fs.create_file(parent, name))
+                .and_then(|_| fs.write_file(&target, content.as_bytes()));
+            match result {
+                Ok(_) => outln!(
+                    out,
+                    "📝 Created file: {} ({} bytes)",
+                    target.green(),
+                    content.len()
+                ),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Rm(path, _, true)
+            if !confirm(
+                out,
+                &mut state.answers,
+                &format!("remove '{}'? (y/N) ", path),
+            ) =>
+        {
+            outln!(out, "Skipped {}", path);
+        }
+        Command::Rm(path, true, _) => {
+            let target = fs.resolve(current_dir, path);
+            match fs.remove_dir_recursive(&target) {
+                Ok(_) => outln!(out, "🗑️ Removed tree: {}", target.red()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Rm(path, false, _) => {
+            let target = fs.resolve(current_dir, path);
+            if fs.is_dir(&target) {
+                outln!(out, "❌ {} is a directory (use rm -r)", target);
             } else {
-                // 验证目录是否存在
-                let target_path = if current_dir == "/" {
-                    format!("/{}", path)
-                } else {
-                    format!("{}/{}", current_dir, path)
-                };
-
-                if fs.find_inode(&target_path).is_ok() {
-                    if current_dir != "/" {
-                        current_dir.push('/');
-                    }
-                    current_dir.push_str(path);
-                } else {
-                    println!("❌ Directory not found: {}", path);
-                    return Ok(());
+                match split_path(&target).and_then(|(parent, name)| fs.delete_file(parent, name)) {
+                    Ok(_) => outln!(out, "🗑️ Deleted file: {}", target.red()),
+                    Err(e) => outln!(out, "❌ {}", e),
                 }
             }
-            println!("📂 Moved to {}", current_dir.blue());
         }
-        Command::Read(file) => match fs.read_file(current_dir, file) {
-            Ok(content) => {
-                println!(
-                    "📖 Reading file: {}",
-                    format!("{}/{}", current_dir, file).cyan()
-                );
-                if let Ok(content_str) = String::from_utf8(content) {
-                    println!("{}", content_str);
-                } else {
-                    println!("<binary data>");
+        Command::Touch(path) => {
+            let target = fs.resolve(current_dir, path);
+            match fs.touch(&target) {
+                Ok(_) => outln!(out, "👆 Touched {}", target.green()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Cd(path) if path == "~" || path.starts_with("~/") => {
+            let target = match path.strip_prefix("~/") {
+                Some(rest) => fs.resolve(&state.home, rest),
+                None => state.home.clone(),
+            };
+            if !fs.is_dir(&target) {
+                outln!(out, "❌ Directory not found: {}", target);
+                return Ok(());
+            }
+            *current_dir = target;
+            outln!(out, "📂 Moved to {}", current_dir.blue());
+        }
+        Command::SetHome(path) => {
+            let target = fs.resolve(current_dir, path);
+            if fs.is_dir(&target) {
+                outln!(out, "🏠 Home set to {}", target.blue());
+                state.home = target;
+            } else {
+                outln!(out, "❌ Not a directory: {}", target);
+            }
+        }
+        Command::Cd(path) => {
+            let target = fs.resolve(current_dir, path);
+            if !fs.is_dir(&target) {
+                outln!(out, "❌ Directory not found: {}", path);
+                return Ok(());
+            }
+            *current_dir = target;
+            outln!(out, "📂 Moved to {}", current_dir.blue());
+        }
+        Command::Read(file) => {
+            let path = fs.resolve(current_dir, file);
+            match fs.read_path(&path) {
+                Ok(content) => {
+                    outln!(out, "📖 Reading file: {}", path.cyan());
+                    if let Ok(content_str) = String::from_utf8(content) {
+                        outln!(out, "{}", content_str);
+                    } else {
+                        outln!(out, "<binary data>");
+                    }
                 }
+                Err(e) => outln!(out, "❌ {}", e),
             }
-            Err(e) => println!("❌ {}", e),
-        },
+        }
+        Command::Head(file, n) | Command::Tail(file, n) => {
+            let path = fs.resolve(current_dir, file);
+            let start = match cmd {
+                Command::Tail(..) => fs
+                    .stat_path(&path)
+                    .map(|inode| inode.size.saturating_sub(*n))
+                    .unwrap_or(0),
+                _ => 0,
+            };
+            match fs.read_range(&path, start, *n) {
+                Ok(bytes) => outln!(out, "{}", String::from_utf8_lossy(&bytes)),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
         Command::Write(file, content) => {
-            match fs.create_or_write_file(current_dir, file, content.as_bytes()) {
-                Ok(_) => {
-                    println!(
-                        "✏️  Writing to {}",
-                        format!("{}/{}", current_dir, file).cyan()
-                    );
-                    println!("{} {}", "✅ Content:".green(), content);
+            let target = fs.resolve(current_dir, file);
+            let result = split_path(&target).and_then(|(parent, name)| {
+                fs.create_or_write_file(parent, name, content.as_bytes())
+            });
+            match result {
+                Ok(outcome) => {
+                    let verb = match outcome {
+                        WriteOutcome::Created(_) => "Created",
+                        WriteOutcome::Overwrote(_) => "Overwrote",
+                    };
+                    outln!(out, "✏️  {} {}", verb, target.cyan());
+                    outln!(out, "{} {}", "✅ Content:".green(), content);
                 }
-                Err(e) => println!("❌ {}", e),
+                Err(e) => outln!(out, "❌ {}", e),
             }
         }
-        Command::Stat(file) => match fs.stat(current_dir, file) {
-            Ok(inode) => {
-                println!(
+        Command::Truncate(file, size) => {
+            let target = fs.resolve(current_dir, file);
+            match fs.truncate_to(&target, *size) {
+                Ok(_) => outln!(out, "✂️  Resized {} to {} bytes", target.cyan(), size),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Append(file, content) => {
+            let target = fs.resolve(current_dir, file);
+            match append_to(fs, &target, content.as_bytes()) {
+                Ok(size) => outln!(
+                    out,
+                    "✏️  Appended {} bytes to {} (now {} bytes)",
+                    content.len(),
+                    target.cyan(),
+                    size
+                ),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Stat(file) => match fs
+            .stat_path(&fs.resolve(current_dir, file))
+            .and_then(|inode| Ok((fs.block_count(&inode)?, inode)))
+        {
+            Ok((blocks, inode)) => {
+                outln!(
+                    out,
                     "{}\n\
              {}: {}\n\
              {}: {:?}\n\
@@ -141,18 +366,18 @@ pub fn execute_command(
              {}: {}\n\
              {}: {}\n\
              {}: {}\n\
-             {}: {}\n",
+             {}: {}",
                     "📊 File Info".bright_yellow().bold(),
                     "Name".blue(),
                     file,
                     "Type".blue(),
                     inode.inode_type,
                     "Inode ID".blue(),
-                    inode.id,
+                    format_uuid(&inode.id),
                     "Size".blue(),
                     inode.size,
                     "Blocks".blue(),
-                    inode.block_count(),
+                    blocks,
                     "Links".blue(),
                     inode.link_count,
                     "Permissions".blue(),
@@ -168,79 +393,1086 @@ pub fn execute_command(
                     "Change".blue(),
                     format_time(inode.ctime),
                 );
+                if inode.inode_type == InodeType::Symlink {
+                    let target = fs
+                        .read_link(&fs.resolve(current_dir, file))
+                        .unwrap_or_else(|_| "?".into());
+                    outln!(out, "{}: {}", "Target".blue(), target);
+                }
+                outln!(out);
             }
-            Err(e) => println!("❌ {}", e),
+            Err(e) => outln!(out, "❌ {}", e),
         },
+        Command::Wc(file) => {
+            let path = fs.resolve(current_dir, file);
+            match fs.count_stats(&path) {
+                Ok((lines, words, bytes)) => {
+                    outln!(
+                        out,
+                        "{:>8} {:>8} {:>8} {}",
+                        lines,
+                        words,
+                        bytes,
+                        path.cyan()
+                    )
+                }
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Chmod(path, mode) => {
+            let target = fs.resolve(current_dir, path);
+            match fs.chmod(&target, *mode) {
+                Ok(_) => outln!(out, "🔐 Mode of {} set to {:04o}", target.cyan(), mode),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Chown(path, uid, gid) => {
+            let target = fs.resolve(current_dir, path);
+            match fs.chown(&target, *uid, *gid) {
+                Ok(_) => outln!(out, "👤 Owner of {} set to {}:{}", target.cyan(), uid, gid),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::SetXattr(path, key, value) => {
+            let target = fs.resolve(current_dir, path);
+            match fs.set_xattr(&target, key, value.as_bytes()) {
+                Ok(_) => outln!(out, "🏷️ {} set on {}", key.yellow(), target.cyan()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::GetXattr(path, key) => {
+            let target = fs.resolve(current_dir, path);
+            let keys = match key {
+                Some(key) => Ok(vec![key.clone()]),
+                None => fs.list_xattr(&target),
+            };
+            match keys {
+                Ok(keys) if keys.is_empty() => {
+                    outln!(out, "{}", "(no extended attributes)".bright_black())
+                }
+                Ok(keys) => {
+                    for key in keys {
+                        match fs.get_xattr(&target, &key) {
+                            Ok(Some(value)) => outln!(
+                                out,
+                                "{}=\"{}\"",
+                                key.yellow(),
+                                String::from_utf8_lossy(&value)
+                            ),
+                            Ok(None) => outln!(out, "❌ No such attribute: {}", key),
+                            Err(e) => outln!(out, "❌ {}", e),
+                        }
+                    }
+                }
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
         Command::Open(file) => {
-            let path = format!("{}/{}", current_dir, file);
+            let path = fs.resolve(current_dir, file);
 
-            // 打开文件（read-only）
+            // 以只读方式打开，句柄留在 shell 的句柄表里供 readfd 使用
             match fs.open(&path, OpenFlags::READ) {
-                Ok(mut fh) => {
-                    println!("📂 Opening file: {}", path.cyan());
-
-                    // 读取整个文件内容
-                    let inode = fs
-                        .inode_table
-                        .get_inode(fh.inode_id)
-                        .ok_or("Inode not found")?;
-
-                    let mut content = vec![0u8; inode.size as usize];
-                    match fs.read_file(&path, file) {
-                        Ok(content) => {
-                            if let Ok(s) = String::from_utf8(content) {
-                                println!("{}", s);
-                            } else {
-                                println!("<binary data>");
-                            }
+                Ok(fh) => {
+                    let fd = state.next_fd;
+                    state.next_fd += 1;
+                    state.handles.insert(fd, fh);
+                    outln!(
+                        out,
+                        "📂 Opened {} as fd {}",
+                        path.cyan(),
+                        fd.to_string().green()
+                    );
+                }
+                Err(e) => outln!(out, "❌ open error: {}", e),
+            }
+        }
+        Command::ReadFd(fd, len) => match state.handles.get_mut(fd) {
+            Some(fh) => {
+                // len 来自用户输入，缓冲区最多开到文件剩余的字节数
+                let remaining = fs
+                    .inode_table
+                    .get_inode(fh.inode_id)
+                    .map_or(0, |inode| inode.size.saturating_sub(fh.offset));
+                let mut buf = vec![0u8; (*len as u64).min(remaining) as usize];
+                match fs.read_at(fh, &mut buf) {
+                    Ok(0) => outln!(out, "📭 EOF (offset {})", fh.offset),
+                    Ok(n) => {
+                        outln!(out, "{}", String::from_utf8_lossy(&buf[..n]));
+                        outln!(
+                            out,
+                            "{}",
+                            format!("({} bytes, offset now {})", n, fh.offset).bright_black()
+                        );
+                    }
+                    Err(e) => outln!(out, "❌ {}", e),
+                }
+            }
+            None => outln!(out, "❌ Bad file descriptor: {}", fd),
+        },
+        Command::Close(fd) => match state.handles.remove(fd) {
+            Some(fh) => match fs.close(fh) {
+                Ok(_) => outln!(out, "🔒 Closed fd {}", fd),
+                Err(e) => outln!(out, "❌ {}", e),
+            },
+            None => outln!(out, "❌ Bad file descriptor: {}", fd),
+        },
+        Command::Cp(src, dst, verify) => {
+            let src = fs.resolve(current_dir, src);
+            let dst = fs.resolve(current_dir, dst);
+            match fs.copy_file(&src, &dst) {
+                Ok(_) => {
+                    if *verify {
+                        if let Err(e) = fs.verify_copy(&src, &dst) {
+                            outln!(out, "❌ {}", e);
+                            return Ok(());
                         }
-                        Err(e) => println!("❌ {}", e),
+                        outln!(out, "🔍 Verified {} against {}", dst.cyan(), src.cyan());
                     }
+                    outln!(out, "📋 Copied {} -> {}", src.cyan(), dst.green());
                 }
-                Err(e) => println!("❌ open error: {}", e),
+                Err(e) => outln!(out, "❌ {}", e),
             }
         }
-        Command::Format => match fs.format() {
-            Ok(_) => {
-                println!("💾 Formatting virtual disk...");
-                let pb = ProgressBar::new(100);
-                pb.set_style(
-                    ProgressStyle::with_template("[{bar:40.green/black}] {pos:>3}% {msg}")
-                        .unwrap()
-                        .progress_chars("#>-"),
+        Command::Mv(src, dst) => {
+            let src = fs.resolve(current_dir, src);
+            let mut dst = fs.resolve(current_dir, dst);
+            // 目标是已存在的目录时，移动到该目录下并保留原名
+            if fs.is_dir(&dst) {
+                let name = src.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+                dst = join_path(&dst, name);
+            }
+            match fs.rename(&src, &dst) {
+                Ok(_) => outln!(out, "🚚 Moved {} -> {}", src.cyan(), dst.green()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Ln(existing, new) => {
+            let existing = fs.resolve(current_dir, existing);
+            let new = fs.resolve(current_dir, new);
+            match fs.link(&existing, &new) {
+                Ok(_) => outln!(out, "🔗 Linked {} -> {}", new.green(), existing.cyan()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Import(host, dst) => {
+            let mut target = fs.resolve(current_dir, dst);
+            // 目标是已有目录时沿用宿主机文件名
+            if fs.is_dir(&target) {
+                let name = Path::new(host)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                target = join_path(&target, &name);
+            }
+            match fs.import_host_file(Path::new(host), &target) {
+                Ok(n) => outln!(
+                    out,
+                    "📥 Imported {} -> {} ({})",
+                    host.cyan(),
+                    target.green(),
+                    format_size(n)
+                ),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Export(src, host) => {
+            let src = fs.resolve(current_dir, src);
+            match fs.export_to_host(&src, Path::new(host)) {
+                Ok(n) => outln!(
+                    out,
+                    "📤 Exported {} -> {} ({})",
+                    src.cyan(),
+                    host.green(),
+                    format_size(n)
+                ),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Walk(path) => {
+            let root = match path {
+                Some(p) => fs.resolve(current_dir, p),
+                None => current_dir.clone(),
+            };
+            match fs.walk(&root) {
+                Ok(walk) => {
+                    for (path, inode_type) in walk {
+                        match inode_type {
+                            InodeType::Directory => outln!(out, "{}/", path),
+                            _ => outln!(out, "{}", path),
+                        }
+                    }
+                }
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Tree(path) => {
+            let root = match path {
+                Some(p) => fs.resolve(current_dir, p),
+                None => current_dir.clone(),
+            };
+            let mut items = Vec::new();
+            match fs.visit_tree(&root, TREE_MAX_DEPTH, &mut |depth, entry| {
+                items.push((depth, entry.clone()))
+            }) {
+                Ok(_) => {
+                    outln!(out, "📁 {}", root.blue());
+                    for line in render_tree(&items) {
+                        outln!(out, "{}", line);
+                    }
+                }
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Find(dir, pattern) => {
+            match fs.find_by_name(&fs.resolve(current_dir, dir), pattern) {
+                Ok(paths) if paths.is_empty() => {
+                    outln!(out, "🔍 No match for {}", pattern.yellow())
+                }
+                Ok(paths) => {
+                    for path in paths {
+                        outln!(out, "{}", path);
+                    }
+                }
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Diff(image_a, image_b) => {
+            // 当前挂载的镜像被自己锁住，直接拿内存中的 fs 比较（含未 sync 的修改）；
+            // 其余镜像只读打开，比较不应改动两边的镜像文件
+            let open = |path: &str| -> std::io::Result<Option<FileSystem>> {
+                match fs.image_path() {
+                    Some(live) if same_file(live, path) => Ok(None),
+                    _ => FileSystem::open_image_readonly(path).map(Some),
+                }
+            };
+            let (a, b) = match open(image_a).and_then(|a| Ok((a, open(image_b)?))) {
+                Ok(pair) => pair,
+                Err(e) => {
+                    outln!(out, "❌ {}", e);
+                    return Ok(());
+                }
+            };
+            let diff = a.as_ref().unwrap_or(fs).diff(b.as_ref().unwrap_or(fs));
+
+            if diff.is_empty() {
+                outln!(out, "✅ Images are identical");
+            }
+            for path in &diff.only_in_a {
+                outln!(out, "{} {}", "- only in A:".red(), path);
+            }
+            for path in &diff.only_in_b {
+                outln!(out, "{} {}", "+ only in B:".green(), path);
+            }
+            for path in &diff.changed {
+                outln!(out, "{} {}", "~ differs:  ".yellow(), path);
+            }
+        }
+        Command::DirDump(path) => match fs.dir_dump(&fs.resolve(current_dir, path)) {
+            Ok(dumps) => {
+                for dump in dumps {
+                    outln!(
+                        out,
+                        "{} block {} ({} bytes serialized)",
+                        "🧱".bright_yellow(),
+                        dump.block_id,
+                        dump.byte_len
+                    );
+                    for (i, entry) in dump.entries.iter().enumerate() {
+                        outln!(
+                            out,
+                            "  [{}] {:<20} inode={:<6} {:?}",
+                            i,
+                            entry.name,
+                            entry.inode_index,
+                            entry.entry_type
+                        );
+                    }
+                }
+            }
+            Err(e) => outln!(out, "❌ {}", e),
+        },
+        Command::DumpBlock(n) => {
+            let total = fs.super_block.total_blocks;
+            let block = if *n >= total {
+                outln!(
+                    out,
+                    "{} Block {} is past the end of the image, showing last block {}",
+                    "⚠️".yellow(),
+                    n,
+                    total - 1
                 );
-                for i in 0..=100 {
-                    pb.set_position(i);
-                    thread::sleep(Duration::from_millis(20));
+                total - 1
+            } else {
+                *n
+            };
+            let region = fs.block_region(block);
+            if region != "data area" {
+                outln!(
+                    out,
+                    "{} Block {} is in the {} (metadata), not the data area",
+                    "⚠️".yellow(),
+                    block,
+                    region
+                );
+            }
+            if fs.has_unsynced_changes() {
+                outln!(
+                    out,
+                    "{}",
+                    "⚠️ Unsynced changes are not on disk yet, run sync first to see them".yellow()
+                );
+            }
+            match fs.dump_block(block) {
+                Some(data) => {
+                    outln!(out, "{} block {} ({})", "🧱".bright_yellow(), block, region);
+                    for line in hex_dump(&data) {
+                        outln!(out, "{}", line);
+                    }
+                }
+                None => outln!(out, "❌ Failed to read block {}", block),
+            }
+        }
+        Command::Depth => match fs.depth_report() {
+            Ok(report) => {
+                outln!(out, "🌲 Max depth: {}", report.max_depth.to_string().cyan());
+                outln!(
+                    out,
+                    "📏 Longest path ({} chars): {}",
+                    report.longest_path.len(),
+                    report.longest_path.cyan()
+                );
+            }
+            Err(e) => outln!(out, "❌ {}", e),
+        },
+        Command::MountInfo => {
+            let footprint = fs.footprint();
+            let mib = |bytes: usize| format!("{:.2} MiB", bytes as f64 / (1024.0 * 1024.0));
+            match fs.mount_time {
+                Some(t) => outln!(out, "⏱️  Mount time: {:.2?}", t),
+                None => outln!(out, "⏱️  Mount time: n/a"),
+            }
+            outln!(out, "📀 Format version: {}", fs.super_block.version);
+            outln!(out, "🧠 Memory footprint:");
+            outln!(out, "  data cache    {}", mib(footprint.data_area));
+            outln!(out, "  inode table   {}", mib(footprint.inode_table));
+            outln!(out, "  inode bitmap  {}", mib(footprint.inode_bitmap));
+            outln!(out, "  data bitmap   {}", mib(footprint.data_bitmap));
+            outln!(
+                out,
+                "  {}         {}",
+                "total".bold(),
+                mib(footprint.total())
+            );
+        }
+        Command::Df => {
+            let st = fs.statfs();
+            let percent = |used: u64, total: u64| used as f64 * 100.0 / total.max(1) as f64;
+            outln!(
+                out,
+                "{:<8} {:>8} {:>8} {:>8} {:>6}",
+                "",
+                "Total",
+                "Used",
+                "Free",
+                "Use%"
+            );
+            outln!(
+                out,
+                "{:<8} {:>8} {:>8} {:>8} {:>5.1}%",
+                "blocks",
+                st.total_blocks,
+                st.used_blocks(),
+                st.free_blocks,
+                percent(st.used_blocks(), st.total_blocks)
+            );
+            outln!(
+                out,
+                "{:<8} {:>8} {:>8} {:>8} {:>5.1}%",
+                "inodes",
+                st.total_inodes,
+                st.used_inodes(),
+                st.free_inodes,
+                percent(st.used_inodes(), st.total_inodes)
+            );
+            outln!(
+                out,
+                "💽 {} total, {} used ({} metadata), {} free",
+                format_size(st.total_blocks * st.block_size).cyan(),
+                format_size(st.used_blocks() * st.block_size),
+                format_size(st.metadata_blocks * st.block_size),
+                format_size(st.free_blocks * st.block_size).green()
+            );
+        }
+        Command::Du(path) => {
+            let root = match path {
+                Some(p) => fs.resolve(current_dir, p),
+                None => current_dir.clone(),
+            };
+            // 目录先列出每个直接子项的小计，再给出总计
+            if let Ok(entries) = fs.list_dir(&root) {
+                for entry in entries.iter().filter(|e| e.name != "." && e.name != "..") {
+                    let child = join_path(&root, &entry.name);
+                    match fs.disk_usage(&child) {
+                        Ok(bytes) => outln!(out, "{:>12}  {}", format_size(bytes), child),
+                        Err(e) => outln!(out, "❌ {}: {}", child, e),
+                    }
+                }
+            }
+            match fs.disk_usage(&root) {
+                Ok(bytes) => outln!(out, "{:>12}  {}", format_size(bytes).bold(), root.cyan()),
+                Err(e) => outln!(out, "❌ {}", e),
+            }
+        }
+        Command::Fsck(repair) => {
+            let report = fs.fsck(*repair);
+            let (sb, bitmap) = report.free_inodes;
+            outln!(out, "🔎 free inodes: superblock {} / bitmap {}", sb, bitmap);
+            let (sb, bitmap) = report.free_blocks;
+            outln!(out, "🔎 free blocks: superblock {} / bitmap {}", sb, bitmap);
+            // 能反查到路径时附在 inode 编号后面，方便定位
+            let describe = |id: u64| match fs.path_of(id) {
+                Some(path) => format!("inode {} ({})", id, path),
+                None => format!("inode {}", id),
+            };
+            for id in &report.inode_mismatches {
+                outln!(
+                    out,
+                    "{} {} bitmap and table disagree",
+                    "⚠️".yellow(),
+                    describe(*id)
+                );
+            }
+            for (id, block) in &report.unmarked_blocks {
+                outln!(
+                    out,
+                    "{} {} uses block {} marked free",
+                    "⚠️".yellow(),
+                    describe(*id),
+                    block
+                );
+            }
+            for block in &report.leaked_blocks {
+                outln!(
+                    out,
+                    "{} block {} is used but unreferenced",
+                    "⚠️".yellow(),
+                    block
+                );
+            }
+            for path in &report.dangling_entries {
+                outln!(out, "{} {} points at a free inode", "⚠️".yellow(), path);
+            }
+            for id in &report.orphan_inodes {
+                outln!(
+                    out,
+                    "{} inode {} has no links and is not open",
+                    "⚠️".yellow(),
+                    id
+                );
+            }
+            if report.repaired {
+                outln!(
+                    out,
+                    "🔧 Orphan inodes reclaimed, superblock counters reconciled with bitmaps"
+                );
+            }
+            if report.is_clean() {
+                outln!(out, "{}", "✅ File system is consistent".green());
+            }
+        }
+        // 需要访问行编辑器，实际由 shell 主循环处理
+        Command::History(_) => {}
+        Command::Defrag => match fs.defragment() {
+            Ok(report) => {
+                outln!(
+                    out,
+                    "🧩 Defragmented {} inodes ({} were fragmented), {} blocks moved",
+                    report.inodes,
+                    report.fragmented_before,
+                    report.blocks_moved.to_string().cyan()
+                );
+                if report.interrupted {
+                    outln!(
+                        out,
+                        "{}",
+                        "⚠️ Interrupted, run defrag again to finish".yellow()
+                    );
+                }
+            }
+            Err(e) => outln!(out, "❌ Defrag failed: {}", e),
+        },
+        Command::Sync => match fs.sync() {
+            Ok(stats) => outln!(
+                out,
+                "💾 Synced {} blocks ({})",
+                stats.blocks.to_string().cyan(),
+                format_size(stats.bytes)
+            ),
+            Err(e) => outln!(out, "❌ Sync failed: {}", e),
+        },
+        Command::Snapshot(label) => match fs.snapshot(label) {
+            Ok(path) => outln!(
+                out,
+                "📸 Snapshot {} saved to {}",
+                label.cyan(),
+                path.display()
+            ),
+            Err(e) => outln!(out, "❌ Snapshot failed: {}", e),
+        },
+        Command::Restore(label) => match fs.restore(label) {
+            Ok(()) => {
+                outln!(out, "⏪ Restored snapshot {}", label.cyan());
+                // 回滚后当前目录和 home 可能已不存在
+                if !fs.is_dir(current_dir) {
+                    *current_dir = "/".to_string();
+                    outln!(out, "📂 Moved to {}", current_dir.blue());
+                }
+                if !fs.is_dir(&state.home) {
+                    state.home = "/".to_string();
+                }
+            }
+            Err(e) => outln!(out, "❌ Restore failed: {}", e),
+        },
+        Command::Snapshots => match fs.snapshots() {
+            Ok(labels) if labels.is_empty() => outln!(out, "📸 No snapshots"),
+            Ok(labels) => {
+                for label in labels {
+                    outln!(out, "📸 {}", label);
                 }
-                pb.finish_with_message("✅ Disk formatted successfully!");
             }
-            Err(e) => println!("❌ Format failed: {}", e),
+            Err(e) => outln!(out, "❌ {}", e),
         },
-        Command::Exit => println!("{}", "👋 Exiting MiniFS shell...".yellow().bold()),
+        Command::ReadOnly(mode) => {
+            // 切到只读前先把未写回的修改落盘，否则之后 sync/unmount 都会被拒绝，修改随退出丢失
+            if *mode == Some(true) && !fs.read_only {
+                if let Err(e) = fs.sync() {
+                    outln!(out, "❌ Sync failed, staying read-write: {}", e);
+                    return Ok(());
+                }
+            }
+            if let Some(enabled) = mode {
+                fs.read_only = *enabled;
+            }
+            let status = if fs.read_only {
+                "read-only".yellow()
+            } else {
+                "read-write".green()
+            };
+            outln!(out, "🔒 File system is {}", status);
+        }
+        Command::Verbose(mode) => {
+            if let Some(enabled) = mode {
+                fs.verbose = *enabled;
+            }
+            let status = if fs.verbose {
+                "on".green()
+            } else {
+                "off".yellow()
+            };
+            outln!(out, "📣 Verbose output is {}", status);
+        }
+        Command::Format(_, false)
+            if !confirm(
+                out,
+                &mut state.answers,
+                "⚠️  This erases everything on the disk. Continue? [y/N] ",
+            ) =>
+        {
+            outln!(out, "Format cancelled");
+        }
+        Command::Format(options, _) => match fs.format_with(options) {
+            Ok(_) => {
+                // 旧的路径和句柄在新文件系统里都不再有效
+                state.current_dir = String::from("/");
+                state.handles.clear();
+                outln!(out, "💾 Formatting virtual disk...");
+                // 进度条只是给终端看的动画；脚本运行或输出不是终端时直接写结果
+                if state.answers.is_some() || !std::io::stdout().is_terminal() {
+                    outln!(out, "✅ Disk formatted successfully!");
+                } else {
+                    let pb = ProgressBar::new(100);
+                    pb.set_style(
+                        ProgressStyle::with_template("[{bar:40.green/black}] {pos:>3}% {msg}")
+                            .unwrap()
+                            .progress_chars("#>-"),
+                    );
+                    for i in 0..=100 {
+                        pb.set_position(i);
+                        thread::sleep(Duration::from_millis(20));
+                    }
+                    pb.finish_with_message("✅ Disk formatted successfully!");
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                // 中断时根目录已经写好，旧的路径和句柄同样失效
+                state.current_dir = String::from("/");
+                state.handles.clear();
+                outln!(out, "⚠️  Format stopped early: {}", e);
+            }
+            Err(e) => outln!(out, "❌ Format failed: {}", e),
+        },
+        Command::Exit => outln!(out, "{}", "👋 Exiting MiniFS shell...".yellow().bold()),
     }
     Ok(())
 }
 
-fn print_help() {
-    println!("{}", "📘 MiniFS Commands".bright_cyan().bold());
-    println!(
+// 两个路径是否指向同一个宿主文件（任一方不存在时视为不同）
+fn same_file(a: &str, b: &str) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// tree 命令最多展开的层数
+const TREE_MAX_DEPTH: usize = 32;
+
+// 把先序遍历得到的 (深度, 目录项) 画成带 ├──/└── 连接线的树
+fn render_tree(items: &[(usize, DirEntry)]) -> Vec<String> {
+    let mut lines = Vec::with_capacity(items.len());
+    // open[d] 为 true 表示第 d 层的祖先后面还有兄弟，需要画竖线
+    let mut open: Vec<bool> = Vec::new();
+
+    for (i, (depth, entry)) in items.iter().enumerate() {
+        // 后面第一个深度不大于当前的项若同层，则当前项不是最后一个
+        let is_last = items[i + 1..]
+            .iter()
+            .find(|(d, _)| d <= depth)
+            .is_none_or(|(d, _)| d < depth);
+        open.truncate(*depth);
+
+        let mut line: String = open
+            .iter()
+            .map(|&more| if more { "│   " } else { "    " })
+            .collect();
+        line.push_str(if is_last { "└── " } else { "├── " });
+        match entry.entry_type {
+            DirEntryType::Directory => line.push_str(&format!("📁 {}", entry.name)),
+            DirEntryType::Symlink => line.push_str(&format!("🔗 {}", entry.name)),
+            DirEntryType::File => line.push_str(&format!("📄 {}", entry.name)),
+        }
+        lines.push(line);
+        open.push(!is_last);
+    }
+    lines
+}
+
+// 向用户确认危险操作：提示写到 out，只有回答 y / yes 才返回 true，读不到输入时视为否
+fn confirm(out: &mut dyn Write, answers: &mut Option<VecDeque<String>>, question: &str) -> bool {
+    let _ = write!(out, "{}", question.yellow());
+    let _ = out.flush();
+    let answer = match answers {
+        // 预置答案不经过终端，回显出来让输出里能看到选择
+        Some(queue) => {
+            let answer = queue.pop_front().unwrap_or_default();
+            let _ = writeln!(out, "{}", answer);
+            answer
+        }
+        None => {
+            let mut answer = String::new();
+            if std::io::stdin().read_line(&mut answer).is_err() {
+                return false;
+            }
+            answer
+        }
+    };
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// 以 APPEND 方式打开（不存在则创建）并写到末尾，返回追加后的文件大小
+fn append_to(fs: &mut FileSystem, path: &str, data: &[u8]) -> Result<u64, String> {
+    let mut fh = fs.open(
+        path,
+        OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::CREATE,
+    )?;
+    let written = fs.write_at(&mut fh, data);
+    let size = fh.offset;
+    fs.close(fh)?;
+    written.map(|_| size)
+}
+
+fn print_help(out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "{}", "📘 MiniFS Commands".bright_cyan().bold())?;
+    writeln!(
+        out,
         "{}",
         "
-  ls                 List files in current directory
+  ls [-l] [-S|-t] [-r]
+                     List files in current directory (-l: mode, links, owner, size, mtime;
+                     -S: by size, -t: by mtime, -r: reverse)
   pwd                Print current path
-  mkdir <dir>        Create directory
+  mkdir [-p] <dir>   Create directory (-p: create missing parents)
   rmdir <dir>        Remove directory
-  create <file>      Create file
-  rm <file>          Remove file
-  cd <dir>           Change directory
+  create <file> [str]
+                     Create file (with content: fail if it exists)
+  rm [-r] [-i] <path>
+                     Remove file (-r: directory tree, -i: ask first)
+  touch <file>       Create empty file or update its timestamps
+  cd [dir]           Change directory (bare `cd` or `cd ~` goes home)
+  sethome <dir>      Set the session home directory
   read <file>        Read file content
+  head [-c N] <file> Print the first N bytes (default 1024)
+  tail [-c N] <file> Print the last N bytes (default 1024)
   write <file> <str> Write string into file
+  append <file> <str>
+                     Append string to the end of file
+  truncate <file> <size>
+                     Shrink or zero-extend file to size bytes
   stat <file>        Show file info
-  format             Format virtual disk
+  wc <file>          Count lines, words and bytes
+  open <file>        Open file read-only and print its fd
+  readfd <fd> <n>    Read up to n bytes from an open fd
+  close <fd>         Close an open fd
+  chmod <mode> <path>
+                     Change permissions (octal, e.g. 600)
+  chown <uid>:<gid> <path>
+                     Change owner and group
+  setxattr <path> <key> <value>
+                     Set an extended attribute
+  getxattr <path> [key]
+                     Show one or all extended attributes
+  cp [--verify] <src> <dst>
+                     Copy file (optionally verify content)
+  mv <src> <dst>     Move or rename a file or directory
+  ln <file> <link>   Create a hard link
+  import <host> <path>
+                     Copy a host file into MiniFS
+  export <path> <host>
+                     Copy a MiniFS file out to the host
+  walk [dir]         List every path under dir (pre-order)
+  tree [dir]         Show the directory hierarchy
+  find <dir> <pattern>
+                     Find entries by name (* and ? wildcards)
+  diff <imgA> <imgB> Compare two disk images
+  dirdump <dir>      Dump raw directory entries
+  dumpblock <n>      Hex dump of raw disk block n (absolute block number)
+  depth              Show max nesting depth and longest path
+  mountinfo          Show mount time and memory footprint
+  df                 Show block and inode usage
+  du [path]          Show disk usage of a file or subtree
+  fsck [--repair]    Check consistency (optionally fix counters)
+  defrag             Move each file's blocks into one contiguous run
+  sync               Write all pending changes to disk
+  snapshot <label>   Save a copy of the whole disk image next to it
+  restore <label>    Roll the image back to a snapshot (discards unsynced changes)
+  snapshots          List snapshots of the current image
+  readonly [on|off]  Show or toggle read-only mode
+  verbose [on|off]   Show or toggle file system progress output
+  history [clear]    Show (or clear) command history
+  format [--fill <byte>] [--inodes <n>] [--size <MiB>] [-y]
+                     Format virtual disk (asks for confirmation unless -y)
   help               Show this help message
   exit               Quit the shell
 "
         .bright_black()
-    );
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::tests::test_fs;
+    use crate::shell::parse::parse_command;
+
+    #[test]
+    fn test_cd_home() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "home").unwrap();
+        let mut state = ShellState::new();
+
+        execute_command(&Command::SetHome("/home".into()), &mut state, &mut fs).unwrap();
+        assert_eq!(state.home, "/home");
+        assert_eq!(state.current_dir, "/");
+
+        execute_command(&Command::Cd("~".into()), &mut state, &mut fs).unwrap();
+        assert_eq!(state.current_dir, "/home");
+
+        execute_command(&Command::Cd("..".into()), &mut state, &mut fs).unwrap();
+        assert_eq!(state.current_dir, "/");
+
+        // 不带参数的 cd 同样回到 home
+        let cmd = parse_command("cd").unwrap();
+        execute_command(&cmd, &mut state, &mut fs).unwrap();
+        assert_eq!(state.current_dir, "/home");
+    }
+
+    #[test]
+    fn test_cd_multi_level() {
+        let mut fs = test_fs();
+        fs.create_dir_all("/a/b").unwrap();
+        fs.create_or_write_file("/a", "file", b"x").unwrap();
+        let mut state = ShellState::new();
+
+        execute_command(&Command::Cd("/a/b".into()), &mut state, &mut fs).unwrap();
+        assert_eq!(state.current_dir, "/a/b");
+        execute_command(&Command::Cd("../..".into()), &mut state, &mut fs).unwrap();
+        assert_eq!(state.current_dir, "/");
+        execute_command(&Command::Cd("./a/./b/".into()), &mut state, &mut fs).unwrap();
+        assert_eq!(state.current_dir, "/a/b");
+
+        // 目标不存在或不是目录时保持原位置
+        execute_command(&Command::Cd("../file".into()), &mut state, &mut fs).unwrap();
+        execute_command(&Command::Cd("/nope".into()), &mut state, &mut fs).unwrap();
+        assert_eq!(state.current_dir, "/a/b");
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let entry = |name: &str, is_dir: bool| DirEntry {
+            name: name.to_string(),
+            inode_index: 0,
+            entry_type: if is_dir {
+                DirEntryType::Directory
+            } else {
+                DirEntryType::File
+            },
+        };
+        let items = vec![
+            (0, entry("a", true)),
+            (1, entry("b", true)),
+            (2, entry("c.txt", false)),
+            (1, entry("d.txt", false)),
+            (0, entry("e.txt", false)),
+        ];
+        assert_eq!(
+            render_tree(&items),
+            [
+                "├── 📁 a",
+                "│   ├── 📁 b",
+                "│   │   └── 📄 c.txt",
+                "│   └── 📄 d.txt",
+                "└── 📄 e.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_read_close_fd() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"hello world")
+            .unwrap();
+        let mut state = ShellState::new();
+
+        execute_command(&parse_command("open a.txt").unwrap(), &mut state, &mut fs).unwrap();
+        assert_eq!(state.handles.keys().copied().collect::<Vec<_>>(), [3]);
+
+        execute_command(&parse_command("readfd 3 5").unwrap(), &mut state, &mut fs).unwrap();
+        assert_eq!(state.handles[&3].offset, 5);
+        execute_command(&Command::ReadFd(3, 100), &mut state, &mut fs).unwrap();
+        assert_eq!(state.handles[&3].offset, 11);
+        // 超大的长度不会按原值分配缓冲区
+        execute_command(
+            &parse_command("readfd 3 99999999999").unwrap(),
+            &mut state,
+            &mut fs,
+        )
+        .unwrap();
+        assert_eq!(state.handles[&3].offset, 11);
+
+        execute_command(&parse_command("close 3").unwrap(), &mut state, &mut fs).unwrap();
+        assert!(state.handles.is_empty());
+        // 关闭后的 fd 不会被复用
+        execute_command(&parse_command("open a.txt").unwrap(), &mut state, &mut fs).unwrap();
+        assert!(state.handles.contains_key(&4));
+    }
+
+    #[test]
+    fn test_ls_long() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "docs").unwrap();
+        fs.create_or_write_file("/", "a.txt", b"hello").unwrap();
+        fs.chmod("/a.txt", 0o640).unwrap();
+        let mut state = ShellState::new();
+
+        let mut out = Vec::new();
+        execute_command_to(
+            &Command::Ls(true, SortBy::Name, false),
+            &mut state,
+            &mut fs,
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let line = |name: &str| {
+            out.lines()
+                .find(|l| l.ends_with(&format!(" {}", name)))
+                .unwrap()
+                .to_string()
+        };
+        assert!(line("docs").starts_with("drwxr-xr-x "));
+        let file = line("a.txt");
+        assert!(file.starts_with("-rw-r-----   1"));
+        assert!(file.contains("        5 "));
+    }
+
+    #[test]
+    fn test_append_keeps_prior_content() {
+        let mut fs = test_fs();
+        let mut state = ShellState::new();
+
+        execute_command(
+            &parse_command("append log.txt one").unwrap(),
+            &mut state,
+            &mut fs,
+        )
+        .unwrap();
+        execute_command(
+            &parse_command("append log.txt two three").unwrap(),
+            &mut state,
+            &mut fs,
+        )
+        .unwrap();
+        assert_eq!(fs.read_path("/log.txt").unwrap(), b"onetwo three");
+        assert_eq!(append_to(&mut fs, "/log.txt", b"!").unwrap(), 13);
+    }
+
+    #[test]
+    fn test_relative_paths_everywhere() {
+        let mut fs = test_fs();
+        fs.create_dir("/", "a").unwrap();
+        let mut state = ShellState::new();
+        state.current_dir = "/a".into();
+
+        for line in ["mkdir ../b", "create ../b//x.txt", "write ./../b/y.txt hi"] {
+            execute_command(&parse_command(line).unwrap(), &mut state, &mut fs).unwrap();
+        }
+        assert!(fs.exists("/b/x.txt"));
+        assert_eq!(fs.read_path("/b/y.txt").unwrap(), b"hi");
+
+        for line in ["rm ../b/x.txt", "rm /b/y.txt", "rmdir ../b"] {
+            execute_command(&parse_command(line).unwrap(), &mut state, &mut fs).unwrap();
+        }
+        assert!(!fs.exists("/b"));
+    }
+
+    #[test]
+    fn test_create_with_content() {
+        let mut fs = test_fs();
+        let mut state = ShellState::new();
+        fs.create_or_write_file("/", "existing", b"old").unwrap();
+
+        let cmd = parse_command("create existing x").unwrap();
+        execute_command(&cmd, &mut state, &mut fs).unwrap();
+        assert_eq!(fs.read_file("/", "existing").unwrap(), b"old");
+
+        let cmd = parse_command("create new x").unwrap();
+        execute_command(&cmd, &mut state, &mut fs).unwrap();
+        assert_eq!(fs.read_file("/", "new").unwrap(), b"x");
+    }
+
+    #[test]
+    fn test_mv_into_directory() {
+        let mut fs = test_fs();
+        let mut state = ShellState::new();
+        fs.create_dir("/", "docs").unwrap();
+        fs.create_or_write_file("/", "a.txt", b"hi").unwrap();
+
+        let cmd = parse_command("mv a.txt docs").unwrap();
+        execute_command(&cmd, &mut state, &mut fs).unwrap();
+        assert!(!fs.exists("/a.txt"));
+        assert_eq!(fs.read_file("/docs", "a.txt").unwrap(), b"hi");
+
+        let cmd = parse_command("mv /docs/a.txt /docs/b.txt").unwrap();
+        execute_command(&cmd, &mut state, &mut fs).unwrap();
+        assert!(fs.exists("/docs/b.txt"));
+    }
+
+    #[test]
+    fn test_walk_missing_root() {
+        let mut fs = test_fs();
+        let mut state = ShellState::new();
+        let mut out = Vec::new();
+        let cmd = parse_command("walk nowhere").unwrap();
+        execute_command_to(&cmd, &mut state, &mut fs, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("❌"));
+    }
+
+    #[test]
+    fn test_readonly_on_keeps_pending_writes() {
+        let mut fs = test_fs();
+        let mut state = ShellState::new();
+        for line in ["write a.txt hello", "readonly on", "exit"] {
+            execute_command(&parse_command(line).unwrap(), &mut state, &mut fs).unwrap();
+        }
+        assert!(fs.read_only);
+
+        // 退出时的 unmount 不再因为有未写回的修改而失败，重新挂载后内容还在
+        fs.unmount().unwrap();
+        fs.mount().unwrap();
+        assert_eq!(fs.read_path("/a.txt").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_diff_does_not_touch_inputs() {
+        let mut fs = test_fs();
+        let mut state = ShellState::new();
+        let short =
+            std::env::temp_dir().join(format!("minifs_diff_short_{}.img", std::process::id()));
+        std::fs::write(&short, b"not an image").unwrap();
+        let short = short.to_str().unwrap().to_string();
+
+        let mut out = Vec::new();
+        let cmd = Command::Diff(short.clone(), short.clone());
+        execute_command_to(&cmd, &mut state, &mut fs, &mut out).unwrap();
+        let len = std::fs::metadata(&short).unwrap().len();
+        std::fs::remove_file(&short).unwrap();
+
+        // 读不出超级块时在本命令里报错，文件保持原样
+        assert!(String::from_utf8(out).unwrap().starts_with("❌ "));
+        assert_eq!(len, b"not an image".len() as u64);
+    }
+
+    #[test]
+    fn test_diff_against_live_image() {
+        let dir = std::env::temp_dir();
+        let live = dir.join(format!("minifs_diff_live_{}.img", std::process::id()));
+        let other = dir.join(format!("minifs_diff_other_{}.img", std::process::id()));
+        let (live, other) = (live.to_str().unwrap(), other.to_str().unwrap());
+        let (tx, _rx) = std::sync::mpsc::channel();
+
+        let mut b = FileSystem::new(crate::disk::FileDisk::new(other, &tx).unwrap());
+        b.format().unwrap();
+        b.create_or_write_file("/", "b.txt", b"b").unwrap();
+        b.unmount().unwrap();
+        drop(b);
+
+        let mut fs = FileSystem::with_image_file(live, &tx).unwrap();
+        fs.format().unwrap();
+        fs.create_or_write_file("/", "a.txt", b"a").unwrap();
+
+        // 挂载中的镜像自己持有排他锁，diff 不能再去打开它
+        let mut state = ShellState::new();
+        let mut out = Vec::new();
+        let cmd = Command::Diff(live.to_string(), other.to_string());
+        execute_command_to(&cmd, &mut state, &mut fs, &mut out).unwrap();
+        drop(fs);
+        std::fs::remove_file(live).unwrap();
+        std::fs::remove_file(other).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("/a.txt"), "{}", out);
+        assert!(out.contains("/b.txt"), "{}", out);
+    }
+
+    #[test]
+    fn test_confirm_uses_preset_answers() {
+        let mut fs = test_fs();
+        fs.create_or_write_file("/", "a.txt", b"a").unwrap();
+        let mut state = ShellState::new();
+        state.answers = Some(VecDeque::from(["n".to_string(), "y".to_string()]));
+
+        let mut out = Vec::new();
+        let cmd = parse_command("rm -i a.txt").unwrap();
+        execute_command_to(&cmd, &mut state, &mut fs, &mut out).unwrap();
+        assert!(fs.exists("/a.txt"));
+        execute_command_to(&cmd, &mut state, &mut fs, &mut out).unwrap();
+        assert!(!fs.exists("/a.txt"));
+
+        // 提示和回答都写进 out，而不是终端
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("remove 'a.txt'? (y/N) n\n"));
+        assert!(out.contains("Skipped a.txt"));
+        assert!(out.contains("🗑️ Deleted file"));
+        assert!(!out.contains('❌'));
+    }
 }
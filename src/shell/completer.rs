@@ -0,0 +1,129 @@
+use std::sync::{Arc, Mutex};
+
+use reedline::{Completer, Span, Suggestion};
+
+use crate::fs::{directory::DirEntryType, FileSystem};
+use crate::shell::command::ShellState;
+use crate::utils::normalize_path;
+
+/// Tab 补全：第一个词补全命令名，之后的参数补全当前文件系统中的路径
+pub struct PathCompleter {
+    commands: Vec<String>,
+    fs: Arc<Mutex<FileSystem>>,
+    state: Arc<Mutex<ShellState>>,
+}
+
+impl PathCompleter {
+    pub fn new(
+        commands: Vec<String>,
+        fs: Arc<Mutex<FileSystem>>,
+        state: Arc<Mutex<ShellState>>,
+    ) -> Self {
+        Self {
+            commands,
+            fs,
+            state,
+        }
+    }
+
+    fn complete_command(&self, word: &str, span: Span) -> Vec<Suggestion> {
+        self.commands
+            .iter()
+            .filter(|cmd| cmd.starts_with(word))
+            .map(|cmd| Suggestion {
+                value: cmd.clone(),
+                span,
+                append_whitespace: true,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn complete_path(&self, word: &str, span: Span) -> Vec<Suggestion> {
+        // "docs/re" -> 在 docs/ 下查找以 "re" 开头的目录项
+        let (dir_part, prefix) = match word.rfind('/') {
+            Some(pos) => word.split_at(pos + 1),
+            None => ("", word),
+        };
+        let current_dir = self.state.lock().unwrap().current_dir.clone();
+        let dir = normalize_path(&current_dir, dir_part);
+
+        let entries = match self.fs.lock().unwrap().list_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        entries
+            .into_iter()
+            .filter(|entry| entry.name != "." && entry.name != "..")
+            .filter(|entry| entry.name.starts_with(prefix))
+            .map(|entry| {
+                let is_dir = entry.entry_type == DirEntryType::Directory;
+                let mut value = format!("{}{}", dir_part, entry.name);
+                if is_dir {
+                    value.push('/');
+                }
+                Suggestion {
+                    value,
+                    span,
+                    // 目录补全后通常还要继续输入下一级，不追加空格
+                    append_whitespace: !is_dir,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Completer for PathCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let span = Span::new(start, pos);
+
+        if before[..start].trim().is_empty() {
+            self.complete_command(word, span)
+        } else {
+            self.complete_path(word, span)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::tests::test_fs;
+
+    fn values(suggestions: Vec<Suggestion>) -> Vec<String> {
+        suggestions.into_iter().map(|s| s.value).collect()
+    }
+
+    #[test]
+    fn test_complete_paths() {
+        let mut fs = test_fs();
+        fs.create_dir_all("/docs/reports").unwrap();
+        fs.create_or_write_file("/docs", "readme.txt", b"hi")
+            .unwrap();
+        fs.create_or_write_file("/", "data.bin", b"x").unwrap();
+
+        let state = Arc::new(Mutex::new(ShellState::new()));
+        let commands = vec!["cd".to_string(), "cp".to_string(), "read".to_string()];
+        let mut completer = PathCompleter::new(commands, Arc::new(Mutex::new(fs)), state.clone());
+
+        assert_eq!(values(completer.complete("c", 1)), ["cd", "cp"]);
+        assert_eq!(values(completer.complete("cd d", 4)), ["docs/", "data.bin"]);
+        assert_eq!(
+            values(completer.complete("read docs/re", 12)),
+            ["docs/reports/", "docs/readme.txt"]
+        );
+
+        // 相对路径以当前目录为基准
+        state.lock().unwrap().current_dir = "/docs".into();
+        let suggestions = completer.complete("read rea", 8);
+        assert_eq!(suggestions[0].value, "readme.txt");
+        assert_eq!(suggestions[0].span, Span::new(5, 8));
+        assert!(suggestions[0].append_whitespace);
+        assert!(completer.complete("read missing/", 13).is_empty());
+    }
+}
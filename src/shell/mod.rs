@@ -1,25 +1,39 @@
+pub mod color;
 pub mod command;
+pub mod completer;
 pub mod parse;
+pub mod script;
 
 use crate::{
     disk::perform_disk_initialization,
     fs::FileSystem,
-    shell::{command::execute_command, parse::parse_command},
+    shell::{
+        command::{execute_command, ShellState},
+        completer::PathCompleter,
+        parse::parse_command,
+    },
 };
 
 use colored::*;
 use crossterm::{
-    cursor, execute,
+    cursor, event, execute,
     style::{Color, Print, ResetColor, SetForegroundColor},
-    terminal::{Clear, ClearType},
+    terminal::{self, Clear, ClearType},
 };
 use indicatif::{ProgressBar, ProgressStyle};
 use reedline::{
-    default_emacs_keybindings, ColumnarMenu, DefaultCompleter, DefaultPrompt, Emacs,
-    FileBackedHistory, KeyCode, KeyModifiers, MenuBuilder, Reedline, ReedlineEvent, ReedlineMenu,
+    default_emacs_keybindings, ColumnarMenu, DefaultPrompt, Emacs, FileBackedHistory, KeyCode,
+    KeyModifiers, MenuBuilder, Reedline, ReedlineEvent, ReedlineMenu, SearchDirection, SearchQuery,
     Signal,
 };
-use std::{io::stdout, path::PathBuf, sync::mpsc, thread, time::Duration};
+use std::{
+    ffi::OsString,
+    io::stdout,
+    path::PathBuf,
+    sync::{atomic::Ordering, mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
 use whoami::fallible;
 
 // 启动信息和进度更新的消息类型
@@ -27,21 +41,37 @@ use whoami::fallible;
 pub enum BootProgress {
     Step(&'static str),
     Progress(u64),
-    Finished(Result<FileSystem, Box<dyn std::error::Error + Send>>),
+    Finished(Result<Box<FileSystem>, Box<dyn std::error::Error + Send>>),
 }
 
-pub fn start_shell() {
-    let mut file_system = match initialize_fs() {
-        Ok(fs) => fs,
+/// 在 `image` 镜像上启动交互式 shell；镜像不存在时先格式化
+pub fn start_shell(image: &str) {
+    let file_system = match initialize_fs(image) {
+        Ok(fs) => Arc::new(Mutex::new(fs)),
         Err(e) => {
             eprintln!("{} {}", "🔥 Fatal Error on boot:".red().bold(), e);
             return;
         }
     };
 
+    // 执行命令期间的 Ctrl-C 只置位中断标志，由长操作自行停下并同步；
+    // 标志还没被处理时再按一次 Ctrl-C 直接退出，卡住的命令不会锁死 shell。
+    // 退出钩子要先注册，才能在置位之前看到上一次留下的标志
+    let interrupt = Arc::clone(&file_system.lock().unwrap().interrupt);
+    let installed = signal_hook::flag::register_conditional_shutdown(
+        signal_hook::consts::SIGINT,
+        130,
+        Arc::clone(&interrupt),
+    )
+    .and_then(|_| signal_hook::flag::register(signal_hook::consts::SIGINT, interrupt));
+    if let Err(e) = installed {
+        eprintln!("Cannot install Ctrl-C handler: {}", e);
+    }
+
     let username = whoami::username();
     let hostname = fallible::hostname().unwrap();
-    let mut current_dir = String::from("/");
+    // 补全器在读取输入时也要访问文件系统和当前目录，因此两者共享
+    let state = Arc::new(Mutex::new(ShellState::new()));
 
     println!(
         "{}",
@@ -50,20 +80,71 @@ pub fn start_shell() {
     );
 
     // 初始化 reedline
-    let histroy_path = dirs::home_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".minifs_history");
+    let histroy_path = history_path(std::env::var_os("MINIFS_HISTORY"));
 
     // 命令补全
     let commands = vec![
-        "help", "ls", "pwd", "mkdir", "rmdir", "create", "rm", "cd", "read", "write", "stat",
-        "format", "exit",
+        "help",
+        "ls",
+        "pwd",
+        "mkdir",
+        "rmdir",
+        "create",
+        "rm",
+        "cd",
+        "sethome",
+        "read",
+        "write",
+        "stat",
+        "cp",
+        "walk",
+        "diff",
+        "dirdump",
+        "dumpblock",
+        "depth",
+        "format",
+        "mountinfo",
+        "chmod",
+        "mv",
+        "ln",
+        "df",
+        "du",
+        "fsck",
+        "touch",
+        "tree",
+        "open",
+        "readfd",
+        "close",
+        "import",
+        "export",
+        "wc",
+        "chown",
+        "setxattr",
+        "getxattr",
+        "find",
+        "head",
+        "tail",
+        "sync",
+        "snapshot",
+        "restore",
+        "snapshots",
+        "defrag",
+        "history",
+        "append",
+        "truncate",
+        "readonly",
+        "verbose",
+        "exit",
     ]
     .into_iter()
     .map(String::from)
     .collect::<Vec<_>>();
 
-    let completer = Box::new(DefaultCompleter::new_with_wordlen(commands.clone(), 2));
+    let completer = Box::new(PathCompleter::new(
+        commands,
+        file_system.clone(),
+        state.clone(),
+    ));
 
     // 使用互动菜单从补全器中选择选项
     let menu = Box::new(ColumnarMenu::default().with_name("completion_menu"));
@@ -89,11 +170,18 @@ pub fn start_shell() {
         .with_edit_mode(edit_mode);
 
     loop {
+        // 有尚未写回磁盘的修改时，在路径后显示 `*`
+        let dirty_marker = if file_system.lock().unwrap().super_block.dirty {
+            "*".yellow().bold().to_string()
+        } else {
+            String::new()
+        };
         let left_prompt = format!(
-            "{}@{}:{}",
+            "{}@{}:{}{}",
             username.green().bold(),
             hostname.cyan().bold(),
-            current_dir.blue()
+            state.lock().unwrap().current_dir.blue(),
+            dirty_marker
         );
 
         let prompt = DefaultPrompt::new(
@@ -111,17 +199,20 @@ pub fn start_shell() {
                 }
 
                 match parse_command(trimmed) {
+                    // history 需要访问行编辑器，由主循环直接处理
+                    Some(command::Command::History(clear)) => {
+                        show_or_clear_history(&mut line_editor, clear)
+                    }
                     Some(cmd) => {
                         // 传递 file_system 给 execute_command
-                        if let Err(e) = execute_command(&cmd, &mut current_dir, &mut file_system) {
+                        let mut state = state.lock().unwrap();
+                        let mut fs = file_system.lock().unwrap();
+                        // 提示符下按的 Ctrl-C 不应中断下一条命令
+                        fs.interrupt.store(false, Ordering::SeqCst);
+                        if let Err(e) = execute_command(&cmd, &mut state, &mut fs) {
                             println!("{} {}", "❌ Error:".red().bold(), e);
                         }
                         if matches!(cmd, command::Command::Exit) {
-                            // 退出前同步文件系统
-                            if let Err(e) = file_system.unmount() {
-                                eprintln!("Error unmounting file system: {}", e);
-                            }
-                            println!("{}", "👋 Bye!".bright_yellow());
                             break;
                         }
                     }
@@ -145,22 +236,68 @@ pub fn start_shell() {
             }
         }
     }
+
+    // 无论 exit、Ctrl-D 还是读取出错，退出前都要同步文件系统
+    if let Err(e) = file_system.lock().unwrap().unmount() {
+        eprintln!("Error unmounting file system: {}", e);
+    }
+    println!("{}", "👋 Bye!".bright_yellow());
+}
+
+// 历史文件位置：MINIFS_HISTORY 优先，否则为 ~/.minifs_history
+fn history_path(override_path: Option<OsString>) -> PathBuf {
+    match override_path {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".minifs_history"),
+    }
+}
+
+// 打印带编号的历史命令，或清空历史（连同历史文件）
+fn show_or_clear_history(line_editor: &mut Reedline, clear: bool) {
+    if clear {
+        match line_editor.history_mut().clear() {
+            Ok(_) => println!("🧹 History cleared"),
+            Err(e) => println!("❌ Failed to clear history: {}", e),
+        }
+        return;
+    }
+
+    let query = SearchQuery::everything(SearchDirection::Forward, None);
+    match line_editor.history().search(query) {
+        Ok(items) => {
+            for (i, item) in items.iter().enumerate() {
+                println!(
+                    "{:>5}  {}",
+                    (i + 1).to_string().bright_black(),
+                    item.command_line
+                );
+            }
+        }
+        Err(e) => println!("❌ Failed to read history: {}", e),
+    }
 }
 
 // 动态欢迎动画
-fn initialize_fs() -> Result<FileSystem, Box<dyn std::error::Error + Send>> {
+fn initialize_fs(image: &str) -> Result<FileSystem, Box<dyn std::error::Error + Send>> {
     let mut stdout = stdout();
 
     execute!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0)).unwrap();
     println!("{}", "[MiniFS Booting...]".bright_yellow().bold());
+    println!(
+        "{}",
+        "⏳ Please wait, input is ignored until boot completes.".bright_black()
+    );
 
     thread::sleep(Duration::from_millis(300));
 
     // 创建一个通道用于线程间通信
     let (tx, rx) = mpsc::channel::<BootProgress>();
 
+    let image = image.to_string();
     let worker_handle = thread::spawn(move || {
-        perform_disk_initialization(tx);
+        perform_disk_initialization(&image, tx);
     });
 
     // 主线程负责 UI 更新
@@ -183,6 +320,7 @@ fn initialize_fs() -> Result<FileSystem, Box<dyn std::error::Error + Send>> {
             BootProgress::Finished(result) => {
                 pb.finish_with_message("✅ Ready!");
                 thread::sleep(Duration::from_millis(400));
+                discard_boot_input();
                 execute!(
                     stdout,
                     Clear(ClearType::All),
@@ -196,8 +334,66 @@ fn initialize_fs() -> Result<FileSystem, Box<dyn std::error::Error + Send>> {
                 // 等待工作线程完全结束
                 worker_handle.join().unwrap();
                 // 将最终结果返回给调用者
-                return result;
+                return result.map(|fs| *fs);
             }
         }
     }
 }
+
+// 丢弃启动期间在终端里积压的按键，保证第一个提示符从干净状态开始
+fn discard_boot_input() {
+    if terminal::enable_raw_mode().is_err() {
+        return;
+    }
+    discard_pending(
+        || event::poll(Duration::ZERO).unwrap_or(false),
+        || {
+            let _ = event::read();
+        },
+    );
+    let _ = terminal::disable_raw_mode();
+}
+
+// 只要还有待读取的输入就读出并丢弃，返回丢弃的事件数
+fn discard_pending(mut has_pending: impl FnMut() -> bool, mut discard: impl FnMut()) -> usize {
+    let mut discarded = 0;
+    while has_pending() {
+        discard();
+        discarded += 1;
+    }
+    discarded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::VecDeque};
+
+    #[test]
+    fn test_boot_input_is_discarded() {
+        // 启动期间用户敲下的内容
+        let queue = RefCell::new(VecDeque::from(vec!["format", "\n", "rm", "\n"]));
+
+        let discarded = discard_pending(
+            || !queue.borrow().is_empty(),
+            || {
+                queue.borrow_mut().pop_front();
+            },
+        );
+
+        // 全部被丢弃，第一个提示符读不到任何残留命令
+        assert_eq!(discarded, 4);
+        assert!(queue.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_history_path_override() {
+        let custom = history_path(Some(OsString::from("/tmp/my_history")));
+        assert_eq!(custom, PathBuf::from("/tmp/my_history"));
+
+        // 未设置或为空时回到默认位置
+        let default = history_path(None);
+        assert!(default.ends_with(".minifs_history"));
+        assert_eq!(history_path(Some(OsString::new())), default);
+    }
+}
@@ -1,4 +1,51 @@
-use crate::shell::command::Command;
+use crate::{
+    disk::BLOCK_SIZE,
+    fs::{FormatOptions, SortBy},
+    shell::command::Command,
+};
+
+// head / tail 不带 -c 时输出的字节数
+const DEFAULT_PEEK_BYTES: u64 = 1024;
+// format --size 以 MiB 为单位
+const BLOCKS_PER_MIB: u64 = 1024 * 1024 / BLOCK_SIZE as u64;
+
+// rm [-r] [-i] <path>，标志可以合写成 -ri
+fn parse_rm(args: &[&str]) -> Option<Command> {
+    let (path, flags) = args.split_last()?;
+    if path.starts_with('-') {
+        return None;
+    }
+    let (mut recursive, mut interactive) = (false, false);
+    for flag in flags {
+        let letters = flag.strip_prefix('-').filter(|l| !l.is_empty())?;
+        for letter in letters.chars() {
+            match letter {
+                'r' => recursive = true,
+                'i' => interactive = true,
+                _ => return None,
+            }
+        }
+    }
+    Some(Command::Rm(path.to_string(), recursive, interactive))
+}
+
+// ls [-l] [-S|-t] [-r]，标志可以合写成 -ltr；-S 和 -t 同时出现时以后者为准
+fn parse_ls(args: &[&str]) -> Option<Command> {
+    let (mut long, mut sort, mut reverse) = (false, SortBy::Name, false);
+    for flag in args {
+        let letters = flag.strip_prefix('-').filter(|l| !l.is_empty())?;
+        for letter in letters.chars() {
+            match letter {
+                'l' => long = true,
+                'S' => sort = SortBy::Size,
+                't' => sort = SortBy::Mtime,
+                'r' => reverse = true,
+                _ => return None,
+            }
+        }
+    }
+    Some(Command::Ls(long, sort, reverse))
+}
 
 pub fn parse_command(input: &str) -> Option<Command> {
     let tokens: Vec<&str> = input.trim().split_ascii_whitespace().collect();
@@ -11,28 +58,454 @@ pub fn parse_command(input: &str) -> Option<Command> {
 
     match cmd {
         "help" => Some(Command::Help),
-        "ls" => Some(Command::Ls),
+        "ls" => parse_ls(args),
         "pwd" => Some(Command::Pwd),
-        "mkdir" => args.get(0).map(|&name| Command::Mkdir(name.to_string())),
-        "rmdir" => args.get(0).map(|&name| Command::Rmdir(name.to_string())),
-        "create" => args.get(0).map(|&name| Command::Create(name.to_string())),
-        "rm" => args.get(0).map(|&name| Command::Rm(name.to_string())),
-        "cd" => args.get(0).map(|&name| Command::Cd(name.to_string())),
-        "read" => args.get(0).map(|&name| Command::Read(name.to_string())),
-        "write" => {
+        "mkdir" => match args {
+            ["-p", path] => Some(Command::Mkdir(path.to_string(), true)),
+            [name] => Some(Command::Mkdir(name.to_string(), false)),
+            _ => None,
+        },
+        "rmdir" => args.first().map(|&name| Command::Rmdir(name.to_string())),
+        "create" => match args {
+            [] => None,
+            [name] => Some(Command::Create(name.to_string(), None)),
+            [name, content @ ..] => Some(Command::Create(
+                name.to_string(),
+                Some(unquote(&content.join(" ")).to_string()),
+            )),
+        },
+        "rm" => parse_rm(args),
+        "touch" => args.first().map(|&name| Command::Touch(name.to_string())),
+        "cd" => Some(Command::Cd(args.first().unwrap_or(&"~").to_string())),
+        "sethome" => args.first().map(|&name| Command::SetHome(name.to_string())),
+        "read" => args.first().map(|&name| Command::Read(name.to_string())),
+        "head" | "tail" => {
+            let (n, file) = match args {
+                ["-c", n, file] => (n.parse().ok()?, file),
+                [file] => (DEFAULT_PEEK_BYTES, file),
+                _ => return None,
+            };
+            Some(if cmd == "head" {
+                Command::Head(file.to_string(), n)
+            } else {
+                Command::Tail(file.to_string(), n)
+            })
+        }
+        // "wirte" 是早期拼写错误，保留为隐藏别名
+        "write" | "wirte" => {
             if args.len() >= 2 {
                 Some(Command::Write(
-                    args.get(0)?.to_string(),
+                    args.first()?.to_string(),
                     args[1..].join(" "),
                 ))
             } else {
                 None
             }
         }
-        "open" => args.get(0).map(|&name| Command::Open(name.to_string())),
-        "stat" => args.get(0).map(|&name| Command::Stat(name.to_string())),
-        "format" => Some(Command::Format),
+        "append" => match args {
+            [file, rest @ ..] if !rest.is_empty() => {
+                Some(Command::Append(file.to_string(), rest.join(" ")))
+            }
+            _ => None,
+        },
+        "truncate" => match args {
+            [file, size] => Some(Command::Truncate(file.to_string(), size.parse().ok()?)),
+            _ => None,
+        },
+        "cp" => match args {
+            ["--verify", src, dst] => Some(Command::Cp(src.to_string(), dst.to_string(), true)),
+            [src, dst] => Some(Command::Cp(src.to_string(), dst.to_string(), false)),
+            _ => None,
+        },
+        "chmod" => match args {
+            [mode, path] => Some(Command::Chmod(
+                path.to_string(),
+                u16::from_str_radix(mode, 8).ok()?,
+            )),
+            _ => None,
+        },
+        "chown" => match args {
+            [owner, path] => {
+                let (uid, gid) = owner.split_once(':')?;
+                Some(Command::Chown(
+                    path.to_string(),
+                    uid.parse().ok()?,
+                    gid.parse().ok()?,
+                ))
+            }
+            _ => None,
+        },
+        "setxattr" => match args {
+            [path, key, value @ ..] if !value.is_empty() => Some(Command::SetXattr(
+                path.to_string(),
+                key.to_string(),
+                value.join(" "),
+            )),
+            _ => None,
+        },
+        "getxattr" => match args {
+            [path] => Some(Command::GetXattr(path.to_string(), None)),
+            [path, key] => Some(Command::GetXattr(path.to_string(), Some(key.to_string()))),
+            _ => None,
+        },
+        "mv" => match args {
+            [src, dst] => Some(Command::Mv(src.to_string(), dst.to_string())),
+            _ => None,
+        },
+        "ln" => match args {
+            [existing, new] => Some(Command::Ln(existing.to_string(), new.to_string())),
+            _ => None,
+        },
+        "open" => args.first().map(|&name| Command::Open(name.to_string())),
+        "readfd" => match args {
+            [fd, len] => Some(Command::ReadFd(fd.parse().ok()?, len.parse().ok()?)),
+            _ => None,
+        },
+        "close" => args
+            .first()
+            .and_then(|fd| fd.parse().ok())
+            .map(Command::Close),
+        "import" => match args {
+            [host, dst] => Some(Command::Import(host.to_string(), dst.to_string())),
+            _ => None,
+        },
+        "export" => match args {
+            [src, host] => Some(Command::Export(src.to_string(), host.to_string())),
+            _ => None,
+        },
+        "wc" => args.first().map(|&name| Command::Wc(name.to_string())),
+        "stat" => args.first().map(|&name| Command::Stat(name.to_string())),
+        "tree" => Some(Command::Tree(args.first().map(|p| p.to_string()))),
+        "find" => match args {
+            [dir, pattern] => Some(Command::Find(dir.to_string(), pattern.to_string())),
+            _ => None,
+        },
+        "walk" => Some(Command::Walk(args.first().map(|p| p.to_string()))),
+        "diff" => match args {
+            [a, b] => Some(Command::Diff(a.to_string(), b.to_string())),
+            _ => None,
+        },
+        "dirdump" => args.first().map(|&name| Command::DirDump(name.to_string())),
+        "dumpblock" => match args {
+            [n] => Some(Command::DumpBlock(n.parse().ok()?)),
+            _ => None,
+        },
+        "sync" => Some(Command::Sync),
+        "snapshot" => match args {
+            [label] => Some(Command::Snapshot(label.to_string())),
+            _ => None,
+        },
+        "restore" => match args {
+            [label] => Some(Command::Restore(label.to_string())),
+            _ => None,
+        },
+        "snapshots" => Some(Command::Snapshots),
+        "defrag" => Some(Command::Defrag),
+        "history" => match args {
+            [] => Some(Command::History(false)),
+            ["clear"] => Some(Command::History(true)),
+            _ => None,
+        },
+        "depth" => Some(Command::Depth),
+        "mountinfo" => Some(Command::MountInfo),
+        "df" => Some(Command::Df),
+        "fsck" => match args {
+            [] => Some(Command::Fsck(false)),
+            ["--repair"] => Some(Command::Fsck(true)),
+            _ => None,
+        },
+        "readonly" => match args {
+            [] => Some(Command::ReadOnly(None)),
+            ["on"] => Some(Command::ReadOnly(Some(true))),
+            ["off"] => Some(Command::ReadOnly(Some(false))),
+            _ => None,
+        },
+        "verbose" => match args {
+            [] => Some(Command::Verbose(None)),
+            ["on"] => Some(Command::Verbose(Some(true))),
+            ["off"] => Some(Command::Verbose(Some(false))),
+            _ => None,
+        },
+        "du" => Some(Command::Du(args.first().map(|p| p.to_string()))),
+        "format" => parse_format(args),
         "exit" => Some(Command::Exit),
         _ => None,
     }
 }
+
+// format [--fill <byte>] [--inodes <n>] [--size <MiB>] [-y]，选项顺序任意
+fn parse_format(mut args: &[&str]) -> Option<Command> {
+    let mut options = FormatOptions::default();
+    let mut assume_yes = false;
+    loop {
+        args = match args {
+            [] => break,
+            ["-y" | "--yes", rest @ ..] => {
+                assume_yes = true;
+                rest
+            }
+            ["--fill", byte, rest @ ..] => {
+                options.fill_byte = Some(parse_u8(byte)?);
+                rest
+            }
+            ["--inodes", n, rest @ ..] => {
+                options.total_inodes = n.parse().ok()?;
+                rest
+            }
+            ["--size", mib, rest @ ..] => {
+                options.total_blocks = mib.parse::<u64>().ok()?.checked_mul(BLOCKS_PER_MIB)?;
+                rest
+            }
+            _ => return None,
+        };
+    }
+    Some(Command::Format(options, assume_yes))
+}
+
+// 去掉一对包住整段内容的引号：`create foo "initial text"` 存入的是 initial text
+fn unquote(s: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = s.strip_prefix(quote).and_then(|s| s.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    s
+}
+
+// 支持十进制和 0x 前缀的十六进制
+fn parse_u8(s: &str) -> Option<u8> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_create_quoted() {
+        let content = |input| match parse_command(input) {
+            Some(Command::Create(_, content)) => content,
+            _ => panic!("not a create: {}", input),
+        };
+        assert_eq!(
+            content("create foo \"initial text\"").as_deref(),
+            Some("initial text")
+        );
+        assert_eq!(content("create foo 'x'").as_deref(), Some("x"));
+        assert_eq!(
+            content("create foo plain words").as_deref(),
+            Some("plain words")
+        );
+        // 只去掉成对的外层引号
+        assert_eq!(content("create foo \"open").as_deref(), Some("\"open"));
+        assert_eq!(content("create foo").as_deref(), None);
+    }
+
+    #[test]
+    fn test_parse_write() {
+        for input in ["write foo hello world", "wirte foo hello world"] {
+            assert!(matches!(
+                parse_command(input),
+                Some(Command::Write(file, content)) if file == "foo" && content == "hello world"
+            ));
+        }
+        assert!(parse_command("write foo").is_none());
+    }
+
+    #[test]
+    fn test_parse_chmod() {
+        assert!(matches!(
+            parse_command("chmod 600 notes.txt"),
+            Some(Command::Chmod(path, 0o600)) if path == "notes.txt"
+        ));
+        assert!(parse_command("chmod 9 notes.txt").is_none());
+        assert!(parse_command("chmod 600").is_none());
+    }
+
+    #[test]
+    fn test_parse_xattr() {
+        assert!(matches!(
+            parse_command("setxattr a.txt user.tag two words"),
+            Some(Command::SetXattr(path, key, value))
+                if path == "a.txt" && key == "user.tag" && value == "two words"
+        ));
+        assert!(parse_command("setxattr a.txt user.tag").is_none());
+        assert!(matches!(
+            parse_command("getxattr a.txt"),
+            Some(Command::GetXattr(path, None)) if path == "a.txt"
+        ));
+        assert!(matches!(
+            parse_command("getxattr a.txt user.tag"),
+            Some(Command::GetXattr(_, Some(key))) if key == "user.tag"
+        ));
+    }
+
+    #[test]
+    fn test_parse_chown() {
+        assert!(matches!(
+            parse_command("chown 1000:100 a.txt"),
+            Some(Command::Chown(path, 1000, 100)) if path == "a.txt"
+        ));
+        assert!(parse_command("chown 1000 a.txt").is_none());
+        assert!(parse_command("chown x:1 a.txt").is_none());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert!(matches!(
+            parse_command("format"),
+            Some(Command::Format(
+                FormatOptions {
+                    fill_byte: None,
+                    ..
+                },
+                false
+            ))
+        ));
+        match parse_command("format --inodes 8192 --size 32 -y --fill 0xAA") {
+            Some(Command::Format(options, true)) => {
+                assert_eq!(options.total_inodes, 8192);
+                assert_eq!(options.total_blocks, 32 * BLOCKS_PER_MIB);
+                assert_eq!(options.fill_byte, Some(0xAA));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(parse_command("format --inodes").is_none());
+        assert!(parse_command("format --size big").is_none());
+    }
+
+    #[test]
+    fn test_parse_readonly() {
+        assert!(matches!(
+            parse_command("readonly"),
+            Some(Command::ReadOnly(None))
+        ));
+        assert!(matches!(
+            parse_command("readonly on"),
+            Some(Command::ReadOnly(Some(true)))
+        ));
+        assert!(parse_command("readonly maybe").is_none());
+    }
+
+    #[test]
+    fn test_parse_ls() {
+        assert!(matches!(
+            parse_command("ls"),
+            Some(Command::Ls(false, SortBy::Name, false))
+        ));
+        assert!(matches!(
+            parse_command("ls -l"),
+            Some(Command::Ls(true, SortBy::Name, false))
+        ));
+        assert!(matches!(
+            parse_command("ls -S"),
+            Some(Command::Ls(false, SortBy::Size, false))
+        ));
+        assert!(matches!(
+            parse_command("ls -ltr"),
+            Some(Command::Ls(true, SortBy::Mtime, true))
+        ));
+        assert!(matches!(
+            parse_command("ls -l -r"),
+            Some(Command::Ls(true, SortBy::Name, true))
+        ));
+        assert!(parse_command("ls -x").is_none());
+    }
+
+    #[test]
+    fn test_parse_truncate() {
+        assert!(matches!(
+            parse_command("truncate a.txt 100"),
+            Some(Command::Truncate(file, 100)) if file == "a.txt"
+        ));
+        assert!(parse_command("truncate a.txt -1").is_none());
+        assert!(parse_command("truncate a.txt").is_none());
+    }
+
+    #[test]
+    fn test_parse_defrag() {
+        assert!(matches!(parse_command("defrag"), Some(Command::Defrag)));
+    }
+
+    #[test]
+    fn test_parse_snapshot() {
+        assert!(matches!(
+            parse_command("snapshot v1"),
+            Some(Command::Snapshot(label)) if label == "v1"
+        ));
+        assert!(matches!(
+            parse_command("restore v1"),
+            Some(Command::Restore(label)) if label == "v1"
+        ));
+        assert!(parse_command("restore").is_none());
+        assert!(matches!(
+            parse_command("snapshots"),
+            Some(Command::Snapshots)
+        ));
+    }
+
+    #[test]
+    fn test_parse_dumpblock() {
+        assert!(matches!(
+            parse_command("dumpblock 42"),
+            Some(Command::DumpBlock(42))
+        ));
+        assert!(parse_command("dumpblock x").is_none());
+        assert!(parse_command("dumpblock").is_none());
+    }
+
+    #[test]
+    fn test_parse_verbose() {
+        assert!(matches!(
+            parse_command("verbose off"),
+            Some(Command::Verbose(Some(false)))
+        ));
+        assert!(parse_command("verbose loud").is_none());
+    }
+
+    #[test]
+    fn test_parse_head_tail() {
+        assert!(matches!(
+            parse_command("head -c 16 a.txt"),
+            Some(Command::Head(file, 16)) if file == "a.txt"
+        ));
+        assert!(matches!(
+            parse_command("tail a.txt"),
+            Some(Command::Tail(_, DEFAULT_PEEK_BYTES))
+        ));
+        assert!(parse_command("tail -c x a.txt").is_none());
+    }
+
+    #[test]
+    fn test_parse_recursive_flags() {
+        assert!(matches!(
+            parse_command("mkdir -p a/b/c"),
+            Some(Command::Mkdir(path, true)) if path == "a/b/c"
+        ));
+        assert!(matches!(
+            parse_command("mkdir docs"),
+            Some(Command::Mkdir(path, false)) if path == "docs"
+        ));
+        assert!(matches!(
+            parse_command("rm -r docs"),
+            Some(Command::Rm(path, true, false)) if path == "docs"
+        ));
+        assert!(matches!(
+            parse_command("rm a.txt"),
+            Some(Command::Rm(path, false, false)) if path == "a.txt"
+        ));
+        assert!(matches!(
+            parse_command("rm -i a.txt"),
+            Some(Command::Rm(path, false, true)) if path == "a.txt"
+        ));
+        assert!(matches!(
+            parse_command("rm -ri docs"),
+            Some(Command::Rm(_, true, true))
+        ));
+        assert!(parse_command("rm -x a.txt").is_none());
+        assert!(parse_command("rm -i").is_none());
+    }
+}
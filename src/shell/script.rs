@@ -0,0 +1,157 @@
+use std::{collections::VecDeque, error::Error, io::Write, path::Path};
+
+use colored::*;
+
+use crate::{
+    fs::FileSystem,
+    shell::{
+        command::{execute_command_to, Command, ShellState},
+        parse::parse_command,
+    },
+};
+
+/// 非交互地依次解析并执行每一行命令，返回全部输出（按行）。
+/// 空行和 `#` 开头的注释被跳过；解析失败或执行出错的行记一行错误后继续，遇到 exit 停止
+pub fn run_script(fs: &mut FileSystem, cwd: &mut String, lines: &[&str]) -> Vec<String> {
+    let mut state = ShellState::new();
+    state.current_dir = cwd.clone();
+    // 脚本不与终端交互，所有确认提示都回答否
+    state.answers = Some(VecDeque::new());
+    let mut out: Vec<u8> = Vec::new();
+
+    for line in lines.iter().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // 写入 Vec 不会失败，忽略 writeln 的结果
+        match parse_command(line) {
+            Some(cmd) => {
+                if let Err(e) = execute_command_to(&cmd, &mut state, fs, &mut out) {
+                    let _ = writeln!(out, "{} {}", "❌ Error:".red().bold(), e);
+                }
+                if matches!(cmd, Command::Exit) {
+                    break;
+                }
+            }
+            None => {
+                let _ = writeln!(out, "{} {}", "⚠️  Unknown command:".yellow(), line);
+            }
+        }
+    }
+
+    *cwd = state.current_dir;
+    String::from_utf8_lossy(&out)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+/// `--script <file>`：在 `image` 镜像上回放脚本文件，输出打印到 stdout，结束时卸载
+pub fn run_script_file(script: &str, image: &str) -> Result<(), Box<dyn Error>> {
+    let text = std::fs::read_to_string(script)
+        .map_err(|e| format!("Cannot read script {}: {}", script, e))?;
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut fs = if Path::new(image).exists() {
+        FileSystem::open_image(image)?
+    } else {
+        // 与交互启动一样，只有镜像不存在时才格式化
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let mut fs = FileSystem::with_image_file(image, &tx)?;
+        fs.format()?;
+        fs.mount()?;
+        fs
+    };
+
+    let mut cwd = String::from("/");
+    for line in run_script(&mut fs, &mut cwd, &lines) {
+        println!("{}", line);
+    }
+    fs.unmount()?;
+    Ok(())
+}
+
+/// 从命令行参数中取出 `--script <file>` / `--script=<file>`
+pub fn script_from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<String>, String> {
+    let mut script = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--script=") {
+            script = Some(value.to_string());
+        } else if arg == "--script" {
+            script = Some(args.next().ok_or("--script requires a file")?);
+        }
+    }
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::tests::test_fs;
+
+    #[test]
+    fn test_run_script_collects_output() {
+        let mut fs = test_fs();
+        let mut cwd = String::from("/");
+        let output = run_script(
+            &mut fs,
+            &mut cwd,
+            &[
+                "# 注释和空行会被跳过",
+                "",
+                "mkdir docs",
+                "cd docs",
+                "write a.txt hello script",
+                "read a.txt",
+                "frobnicate",
+                "read missing.txt",
+                "rm -i a.txt",
+                "exit",
+                "mkdir after_exit",
+            ],
+        );
+
+        assert_eq!(cwd, "/docs");
+        assert_eq!(fs.read_path("/docs/a.txt").unwrap(), b"hello script");
+        assert!(output.iter().any(|line| line == "hello script"));
+        assert!(output
+            .iter()
+            .any(|line| line.contains("Unknown command") && line.contains("frobnicate")));
+        assert!(output.iter().any(|line| line.contains("missing.txt")));
+        // 确认提示不会阻塞在终端上，一律按否处理
+        assert!(fs.exists("/docs/a.txt"));
+        assert!(output.iter().any(|line| line.contains("Skipped a.txt")));
+        assert!(!fs.exists("/docs/after_exit"));
+    }
+
+    #[test]
+    fn test_scripted_format_skips_animation() {
+        let mut fs = test_fs();
+        let mut cwd = String::from("/");
+        let start = std::time::Instant::now();
+        let output = run_script(&mut fs, &mut cwd, &["mkdir docs", "format -y"]);
+
+        // 不跑约 2 秒的进度条动画，结果也收集在输出里
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert!(output
+            .iter()
+            .any(|line| line.contains("Disk formatted successfully")));
+        assert!(!fs.exists("/docs"));
+    }
+
+    #[test]
+    fn test_script_from_args() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(script_from_args(args(&[])).unwrap(), None);
+        assert_eq!(
+            script_from_args(args(&["--color=never", "--script", "demo.txt"])).unwrap(),
+            Some("demo.txt".to_string())
+        );
+        assert_eq!(
+            script_from_args(args(&["--script=demo.txt"])).unwrap(),
+            Some("demo.txt".to_string())
+        );
+        assert!(script_from_args(args(&["--script"])).is_err());
+    }
+}
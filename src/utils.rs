@@ -1,28 +1,47 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+/// 当前时间，单位为毫秒（inode 中的时间戳都用这个单位）
 pub fn current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_secs()
+        .as_millis() as u64
 }
 
 /// 生成一个随机唯一 ID
-pub fn generate_uuid() -> String {
-    Uuid::new_v4().to_string()
+pub fn generate_uuid() -> [u8; 16] {
+    *Uuid::new_v4().as_bytes()
 }
 
-/// 格式化时间戳为可读字符串
+/// 把 16 字节 ID 格式化为标准的 UUID 字符串
+pub fn format_uuid(id: &[u8; 16]) -> String {
+    Uuid::from_bytes(*id).to_string()
+}
+
+/// 格式化毫秒时间戳为可读字符串
 pub fn format_time(ts: u64) -> String {
     use chrono::{DateTime, Local};
     use std::time::{Duration, UNIX_EPOCH};
 
-    let dt = UNIX_EPOCH + Duration::from_secs(ts);
+    let dt = UNIX_EPOCH + Duration::from_millis(ts);
     let datetime: DateTime<Local> = dt.into();
     datetime.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+/// 把类型字符（见 `InodeType::mode_char`）和权限位格式化为 `ls -l` 风格的字符串，如 `drwxr-xr-x`
+pub fn format_mode(kind: char, perms: u16) -> String {
+    let mut mode = String::with_capacity(10);
+    mode.push(kind);
+    for shift in [6, 3, 0] {
+        let bits = (perms >> shift) & 0o7;
+        mode.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        mode.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        mode.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    mode
+}
+
 pub fn split_path(path: &str) -> Result<(&str, &str), String> {
     let path = path.trim();
 
@@ -53,3 +72,165 @@ pub fn split_path(path: &str) -> Result<(&str, &str), String> {
         }
     }
 }
+
+/// 拼接父目录与名字，避免出现 "//"
+pub fn join_path(parent: &str, name: &str) -> String {
+    if parent.ends_with('/') {
+        format!("{}{}", parent, name)
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// 以 `base` 为当前目录规范化 `path`：支持绝对路径、`.` 与多级 `..`，结果总以 `/` 开头
+pub fn normalize_path(base: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    let full = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        join_path(base, path)
+    };
+    for part in full.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            name => parts.push(name),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// 简单的通配符匹配：`*` 匹配任意长度（含空）字符，`?` 匹配单个字符
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    // 最近一个 `*` 的位置，以及它当时对应的 name 位置，用于回溯
+    let mut star: Option<(usize, usize)> = None;
+
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            // 让上一个 `*` 多吞一个字符再试
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// CRC-32（IEEE 802.3，与 zlib 相同的多项式）
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// 经典的十六进制 + ASCII 转储：每行 16 字节，行首为偏移；
+/// 与上一行完全相同的连续行折叠为一行 `*`（同 `hexdump -C`）
+pub fn hex_dump(data: &[u8]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut prev: Option<&[u8]> = None;
+    let mut folded = false;
+    for (i, row) in data.chunks(16).enumerate() {
+        if prev == Some(row) {
+            if !folded {
+                lines.push("*".to_string());
+                folded = true;
+            }
+            continue;
+        }
+        prev = Some(row);
+        folded = false;
+
+        let mut hex = String::new();
+        for (j, byte) in row.iter().enumerate() {
+            if j == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", byte));
+        }
+        let ascii: String = row
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        lines.push(format!("{:08x}  {:<49} |{}|", i * 16, hex, ascii));
+    }
+    lines.push(format!("{:08x}", data.len()));
+    lines
+}
+
+/// 把字节数格式化为人类可读的形式，如 `4.00 KiB`、`64.00 MiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode('d', 0o755), "drwxr-xr-x");
+        assert_eq!(format_mode('-', 0o640), "-rw-r-----");
+        assert_eq!(format_mode('l', 0o777), "lrwxrwxrwx");
+        assert_eq!(format_mode('-', 0), "----------");
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let mut data = b"Hello, block!".to_vec();
+        data.resize(64, 0);
+        let lines = hex_dump(&data);
+        assert_eq!(
+            lines[0],
+            "00000000  48 65 6c 6c 6f 2c 20 62  6c 6f 63 6b 21 00 00 00  |Hello, block!...|"
+        );
+        // 后面全零的行折叠成一行 *，最后一行是总长度
+        assert_eq!(
+            lines[1],
+            "00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|"
+        );
+        assert_eq!(lines[2], "*");
+        assert_eq!(lines[3], "00000040");
+        assert_eq!(lines.len(), 4);
+
+        // 不足 16 字节的末行照样对齐
+        assert_eq!(
+            hex_dump(b"\x01A")[0],
+            format!("00000000  01 41 {:<43} |.A|", "")
+        );
+    }
+}
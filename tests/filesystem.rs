@@ -0,0 +1,47 @@
+use mini_fs::{disk::types::DISK_SIZE, FileDisk, FileSystem, OpenFlags};
+use std::{path::PathBuf, sync::mpsc::channel};
+
+// 在临时目录创建一块格式化好的镜像，返回文件系统和镜像路径
+fn fresh_fs(name: &str) -> (FileSystem, PathBuf) {
+    let path = std::env::temp_dir().join(format!("minifs_it_{}_{}.img", name, std::process::id()));
+    std::fs::File::create(&path)
+        .unwrap()
+        .set_len(DISK_SIZE)
+        .unwrap();
+
+    let (tx, _rx) = channel();
+    let disk = FileDisk::new(path.to_str().unwrap(), &tx).unwrap();
+    let mut fs = FileSystem::new(disk);
+    fs.format().unwrap();
+    (fs, path)
+}
+
+#[test]
+fn persists_across_reopen() {
+    let (mut fs, path) = fresh_fs("reopen");
+    fs.create_dir_all("/docs/notes").unwrap();
+    fs.create_or_write_file("/docs/notes", "todo.txt", b"write tests")
+        .unwrap();
+    fs.unmount().unwrap();
+    drop(fs);
+
+    let mut fs = FileSystem::open_image(path.to_str().unwrap()).unwrap();
+    assert_eq!(
+        fs.read_file("/docs/notes", "todo.txt").unwrap(),
+        b"write tests"
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_respects_permissions() {
+    let mut fs = FileSystem::new_in_memory().unwrap();
+
+    fs.create_or_write_file("/", "secret", b"x").unwrap();
+    fs.chmod("/secret", 0o000).unwrap();
+    assert!(fs.open("/secret", OpenFlags::READ).is_err());
+
+    fs.chmod("/secret", 0o644).unwrap();
+    let fh = fs.open("/secret", OpenFlags::READ).unwrap();
+    assert_eq!(fh.offset, 0);
+}